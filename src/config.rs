@@ -1,14 +1,23 @@
 use anyhow::Context;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use strum_macros::EnumString;
 use std::default::Default;
 use std::{str, env};
+use std::path::Path;
+use std::collections::HashMap;
 use crate::database::models::punishment::PunishmentType;
+use crate::util::ip_hash::IpHasher;
 use crate::util::webhook::WebhookUtils;
 
 use super::database::models::level_color::LevelColor;
 use super::database::models::join_sound::JoinSound;
 use super::database::models::broadcast::Broadcast;
+use super::database::models::preference::PreferenceDefinition;
+use super::database::models::cosmetic::Cosmetic;
+use super::database::models::chat_channel::ChatChannelSettings;
+use super::database::models::chat_filter::ChatFilterRule;
+use super::socket::chat::chat_filter::ChatFilter;
 use super::util::file::{read_file, deserialize_properties_file};
 
 #[derive(Debug)]
@@ -68,6 +77,7 @@ impl From<ConfigParseError> for ConfigDeserializeError {
 }
 
 const TOKEN_ENV_VARIABLE : &'static str = "MARS_API_TOKEN";
+const IP_HASH_KEYS_ENV_VARIABLE : &str = "MARS_IP_HASH_KEYS";
 
 pub async fn deserialize_mars_config() -> anyhow::Result<MarsConfig> {
     let token = env::var(TOKEN_ENV_VARIABLE).context(format!("Missing API environment variable {}", TOKEN_ENV_VARIABLE))?;
@@ -78,9 +88,12 @@ pub async fn deserialize_mars_config() -> anyhow::Result<MarsConfig> {
     let webhooks = WebhookUtils::new(
         &(if options.reports_webhook_url.is_empty() { None } else { Some(options.reports_webhook_url.clone()) }), 
         &(if options.punishments_webhook_url.is_empty() { None } else { Some(options.punishments_webhook_url.clone()) }), 
-        &(if options.notes_webhook_url.is_empty() { None } else { Some(options.notes_webhook_url.clone()) })
+        &(if options.notes_webhook_url.is_empty() { None } else { Some(options.notes_webhook_url.clone()) }),
+        &(if options.appeals_webhook_url.is_empty() { None } else { Some(options.appeals_webhook_url.clone()) }),
+        &(if options.evasion_webhook_url.is_empty() { None } else { Some(options.evasion_webhook_url.clone()) })
     );
-    Ok(MarsConfig { token, options, data, webhooks })
+    let ip_hasher = IpHasher::new(options.enable_ip_hashing, env::var(IP_HASH_KEYS_ENV_VARIABLE).ok(), options.ip_hash_dual_read)?;
+    Ok(MarsConfig { token, options, data, webhooks, ip_hasher })
 }
 
 async fn deserialize_mars_options() -> Result<MarsConfigOptions, ConfigDeserializeError> {
@@ -94,13 +107,33 @@ async fn deserialize_mars_options() -> Result<MarsConfigOptions, ConfigDeseriali
             "mongo-url" => {config.mongo_url = v.to_string();},
             "redis-host" => { config.redis_host = Some(v.to_string()); },
             "enable-ip-hashing" => { if let Ok(b) = v.to_string().parse::<bool>() { config.enable_ip_hashing = b; } },
+            "ip-hashing.dual-read" => { if let Ok(b) = v.to_string().parse::<bool>() { config.ip_hash_dual_read = b; } },
             "webhooks.punishments" => { config.punishments_webhook_url = v.to_string(); },
             "webhooks.reports" => { config.reports_webhook_url = v.to_string(); },
             "webhooks.notes" => { config.notes_webhook_url = v.to_string(); },
+            "webhooks.appeals" => { config.appeals_webhook_url = v.to_string(); },
+            "webhooks.evasion" => { config.evasion_webhook_url = v.to_string(); },
             "webhooks.debug" => { config.debug_log_webhook_url = v.to_string(); },
             "enable-exponential-exp" => { if let Ok(b) = v.to_string().parse::<bool>() { config.use_exponential_exp = b; } },
             "images-path" => { config.images_path = Some(v.to_string()); },
             "avif-transcode" => { config.avif_transcode = false; }
+            "clans.max-members" => { if let Ok(i) = v.to_string().parse::<u32>() { config.clan_max_members = i; } },
+            "clans.rename-cooldown-hours" => { if let Ok(i) = v.to_string().parse::<u64>() { config.clan_rename_cooldown_hours = i; } },
+            "messages.rate-limit" => { if let Ok(i) = v.to_string().parse::<u32>() { config.direct_message_rate_limit = i; } },
+            "messages.rate-limit-window-seconds" => { if let Ok(i) = v.to_string().parse::<u64>() { config.direct_message_rate_limit_window_seconds = i; } },
+            "messages.offline-retention-days" => { if let Ok(i) = v.to_string().parse::<u64>() { config.direct_message_offline_retention_days = i; } },
+            "punishments.offence-decay-days" => { if let Ok(i) = v.to_string().parse::<u64>() { config.punishment_offence_decay_days = i; } },
+            "reports.merge-window-minutes" => { if let Ok(i) = v.to_string().parse::<u64>() { config.report_merge_window_minutes = i; } },
+            "reports.cooldown-seconds" => { if let Ok(i) = v.to_string().parse::<u64>() { config.report_cooldown_seconds = i; } },
+            "ban-evasion.policy" => { if let Ok(policy) = v.to_string().parse::<BanEvasionPolicy>() { config.ban_evasion_policy = policy; } },
+            "ban-evasion.alert-cooldown-minutes" => { if let Ok(i) = v.to_string().parse::<u64>() { config.ban_evasion_alert_cooldown_minutes = i; } },
+            "alts.max-depth" => { if let Ok(i) = v.to_string().parse::<u32>() { config.alt_lookup_max_depth = i; } },
+            "alts.max-ip-fanout" => { if let Ok(i) = v.to_string().parse::<usize>() { config.alt_lookup_max_ip_fanout = i; } },
+            "alts.max-accounts" => { if let Ok(i) = v.to_string().parse::<usize>() { config.alt_lookup_max_accounts = i; } },
+            "shared-ips.max-identity-players" => { if let Ok(i) = v.to_string().parse::<usize>() { config.shared_ip_max_identity_players = i; } },
+            "chat-log.retention-days" => { if let Ok(i) = v.to_string().parse::<u64>() { config.chat_log_retention_days = i; } },
+            "friends.max" => { if let Ok(i) = v.to_string().parse::<u32>() { config.max_friends = i; } },
+            "stat-snapshots.retention-days" => { if let Ok(i) = v.to_string().parse::<u64>() { config.stat_snapshot_retention_days = i; } },
             _ => {}
         }
     });
@@ -112,29 +145,66 @@ async fn deserialize_mars_data() -> Result<MarsConfigData, ConfigDeserializeErro
     let join_sounds_path = env::var("MARS_JOIN_SOUNDS_PATH").unwrap_or("./join_sounds.yml".to_string());
     let broadcasts_path = env::var("MARS_BROADCASTS_PATH").unwrap_or("./broadcasts.yml".to_string());
     let pun_types_path = env::var("MARS_PUNTYPES_PATH").unwrap_or("./punishment_types.yml".to_string());
+    let preferences_path = env::var("MARS_PREFERENCES_PATH").unwrap_or("./preferences.yml".to_string());
+    let cosmetics_path = env::var("MARS_COSMETICS_PATH").unwrap_or("./cosmetics.yml".to_string());
+    let currency_rates_path = env::var("MARS_CURRENCY_RATES_PATH").unwrap_or("./currency_rates.yml".to_string());
+    let chat_channels_path = env::var("MARS_CHAT_CHANNELS_PATH").unwrap_or("./chat_channels.yml".to_string());
+    let chat_filters_path = env::var("MARS_CHAT_FILTERS_PATH").unwrap_or("./chat_filters.yml".to_string());
 
     let (
         level_colors, 
         join_sounds, 
         broadcasts, 
-        punishment_types
+        punishment_types,
+        preferences,
+        mut cosmetics,
+        currency_rates,
+        chat_channels,
+        chat_filter_rules
     ) = match tokio::try_join!(
         deserialize_mars_data_component::<Vec<LevelColor>>(&level_colors_path),
         deserialize_mars_data_component::<Vec<JoinSound>>(&join_sounds_path),
         deserialize_mars_data_component::<Vec<Broadcast>>(&broadcasts_path),
-        deserialize_mars_data_component::<Vec<PunishmentType>>(&pun_types_path)
+        deserialize_mars_data_component::<Vec<PunishmentType>>(&pun_types_path),
+        deserialize_optional_mars_data_component::<Vec<PreferenceDefinition>>(&preferences_path),
+        deserialize_optional_mars_data_component::<Vec<Cosmetic>>(&cosmetics_path),
+        deserialize_optional_mars_data_component::<HashMap<String, u32>>(&currency_rates_path),
+        deserialize_optional_mars_data_component::<Vec<ChatChannelSettings>>(&chat_channels_path),
+        deserialize_optional_mars_data_component::<Vec<ChatFilterRule>>(&chat_filters_path)
     ) {
         Ok(values) => values,
         Err(e) => return Err(e)
     };
+    for join_sound in join_sounds.iter() {
+        if !cosmetics.iter().any(|cosmetic| cosmetic.id == join_sound.id) {
+            cosmetics.push(Cosmetic::from(join_sound));
+        };
+    }
     Ok(MarsConfigData { 
         level_colors,
         join_sounds,
         broadcasts,
-        punishment_types
+        punishment_types,
+        preferences,
+        cosmetics,
+        currency_rates,
+        chat_channels: if chat_channels.is_empty() { ChatChannelSettings::defaults() } else { chat_channels },
+        chat_filter: ChatFilter::compile(&chat_filter_rules),
+        chat_filter_rules
     })
 }
 
+// for data files that deployments may leave out entirely
+async fn deserialize_optional_mars_data_component<T: DeserializeOwned + Default>(
+    file_path: &String,
+) -> Result<T, ConfigDeserializeError> {
+    if !Path::new(file_path).exists() {
+        warn!("{} not found, using defaults", file_path);
+        return Ok(T::default());
+    };
+    deserialize_mars_data_component::<T>(file_path).await
+}
+
 async fn deserialize_mars_data_component<T: DeserializeOwned>(
     file_path: &String,
 ) -> Result<T, ConfigDeserializeError> {
@@ -153,7 +223,8 @@ pub struct MarsConfig {
     pub token: String,
     pub options: MarsConfigOptions,
     pub data: MarsConfigData,
-    pub webhooks: WebhookUtils
+    pub webhooks: WebhookUtils,
+    pub ip_hasher: IpHasher
 }
 
 // impl Default for MarsConfig {
@@ -173,14 +244,54 @@ pub struct MarsConfigOptions {
     pub mongo_url: String,
     pub redis_host: Option<String>,
     pub enable_ip_hashing: bool,
+    // also match ips stored under older hash keys, turn off once re-keying has finished
+    pub ip_hash_dual_read: bool,
     pub punishments_webhook_url: String,
     pub reports_webhook_url: String,
     pub notes_webhook_url: String,
+    pub appeals_webhook_url: String,
+    pub evasion_webhook_url: String,
     pub debug_log_webhook_url: String,
     pub use_exponential_exp: bool,
     pub images_path: Option<String>,
     // not supported yet
-    pub avif_transcode: bool
+    pub avif_transcode: bool,
+    // 0 keeps snapshots forever
+    pub stat_snapshot_retention_days: u64,
+    pub max_friends: u32,
+    pub clan_max_members: u32,
+    pub clan_rename_cooldown_hours: u64,
+    // direct messages allowed per player within the window
+    pub direct_message_rate_limit: u32,
+    pub direct_message_rate_limit_window_seconds: u64,
+    pub direct_message_offline_retention_days: u64,
+    // 0 keeps chat messages forever
+    pub chat_log_retention_days: u64,
+    // days after which a punishment stops counting as a prior offence, 0 counts it forever
+    pub punishment_offence_decay_days: u64,
+    // duplicate reports against a target within this window are merged
+    pub report_merge_window_minutes: u64,
+    // per reporter, 0 disables the cooldown
+    pub report_cooldown_seconds: u64,
+    // what prelogin does when a player shares an IP with a banned account
+    pub ban_evasion_policy: BanEvasionPolicy,
+    // per player, 0 alerts on every join
+    pub ban_evasion_alert_cooldown_minutes: u64,
+    // upper bound on the depth an alt lookup may request
+    pub alt_lookup_max_depth: u32,
+    // ips shared by more accounts than this are not followed during alt lookups
+    pub alt_lookup_max_ip_fanout: usize,
+    pub alt_lookup_max_accounts: usize,
+    // accounts recorded against an ip on the shared list before it stops growing
+    pub shared_ip_max_identity_players: usize
+}
+
+#[derive(PartialEq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum BanEvasionPolicy {
+    Off,
+    Alert,
+    Block
 }
 
 impl Default for MarsConfigOptions {
@@ -191,13 +302,33 @@ impl Default for MarsConfigOptions {
             host: String::new(), 
             redis_host: None, 
             enable_ip_hashing: false,
+            ip_hash_dual_read: true,
             punishments_webhook_url: String::new(),
             reports_webhook_url: String::new(),
             notes_webhook_url: String::new(),
+            appeals_webhook_url: String::new(),
+            evasion_webhook_url: String::new(),
             debug_log_webhook_url: String::new(),
             use_exponential_exp: false,
             images_path: None,
-            avif_transcode: false
+            avif_transcode: false,
+            stat_snapshot_retention_days: 365,
+            max_friends: 200,
+            clan_max_members: 50,
+            clan_rename_cooldown_hours: 168,
+            direct_message_rate_limit: 5,
+            direct_message_rate_limit_window_seconds: 10,
+            direct_message_offline_retention_days: 7,
+            chat_log_retention_days: 30,
+            punishment_offence_decay_days: 0,
+            report_merge_window_minutes: 10,
+            report_cooldown_seconds: 60,
+            ban_evasion_policy: BanEvasionPolicy::Alert,
+            ban_evasion_alert_cooldown_minutes: 60,
+            alt_lookup_max_depth: 3,
+            alt_lookup_max_ip_fanout: 20,
+            alt_lookup_max_accounts: 100,
+            shared_ip_max_identity_players: 50
         }
     }
 }
@@ -207,5 +338,14 @@ pub struct MarsConfigData {
     pub level_colors: Vec<LevelColor>,
     pub join_sounds: Vec<JoinSound>,
     pub broadcasts: Vec<Broadcast>,
-    pub punishment_types: Vec<PunishmentType>
+    pub punishment_types: Vec<PunishmentType>,
+    pub preferences: Vec<PreferenceDefinition>,
+    pub cosmetics: Vec<Cosmetic>,
+    // currency earned per XP reason, e.g. "Kill" or "Victory"
+    pub currency_rates: HashMap<String, u32>,
+    pub chat_channels: Vec<ChatChannelSettings>,
+    pub chat_filter_rules: Vec<ChatFilterRule>,
+    // compiled from chat_filter_rules
+    #[serde(skip)]
+    pub chat_filter: ChatFilter
 }
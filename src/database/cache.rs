@@ -31,6 +31,11 @@ impl<R> Cache<R> where R: CollectionOwner<R> + DeserializeOwned + Unpin + Sync +
         if let Ok(value) = self.redis.get(&resource_key).await { Some(value) } else { None }
     }
 
+    pub async fn query_many(&self, keys: &[String]) -> Vec<Option<R>> {
+        let resource_keys : Vec<String> = keys.iter().map(|key| self.generate_formatted_key(key)).collect();
+        self.redis.get_many(&resource_keys).await
+    }
+
     pub async fn get(&self, database: &Database, key: &str) -> Option<R> {
         if let Some(datum) = self.query(key).await { Some(datum) } 
         else {
@@ -66,6 +71,18 @@ impl<R> Cache<R> where R: CollectionOwner<R> + DeserializeOwned + Unpin + Sync +
         self.redis.set_with_expiry(&resource_key, value, expiry_ms).await;
     }
 
+    // for backfilling from the database, entries already cached may be newer and are left alone
+    pub async fn set_many_if_absent(&self, entries: &[(String, &R)]) {
+        let formatted : Vec<(String, &R)> = entries.iter()
+            .map(|(key, value)| (self.generate_formatted_key(key), *value))
+            .collect();
+        self.redis.set_many_if_absent(&formatted, self.lifetime_ms as usize).await;
+    }
+
+    pub async fn invalidate(&self, key: &str) {
+        self.redis.delete(&self.generate_formatted_key(key)).await;
+    }
+
     pub async fn persist_cached_value(&self, database: &Database, key: &String) {
         if let Some(record) = self.query(key).await {
             database.save(&record).await;
@@ -161,6 +178,89 @@ impl RedisAdapter {
         };
     }
 
+    pub async fn set_many_if_absent<T>(&self, entries: &[(String, &T)], expiry_ms: usize) where T: Serialize {
+        if entries.is_empty() {
+            return;
+        };
+        let mut conn = match self.pool.get().await {
+            Ok(conn) => conn,
+            Err(_) => return
+        };
+        let mut pipe = redis::pipe();
+        for (key, value) in entries.iter() {
+            if let Ok(stringified) = json::to_string(value) {
+                pipe.cmd("SET").arg(key).arg(stringified).arg("PX").arg(expiry_ms).arg("NX").ignore();
+            };
+        }
+        let _ : RedisResult<()> = pipe.query_async(&mut *conn).await;
+    }
+
+    pub async fn get_many<T>(&self, keys: &[String]) -> Vec<Option<T>> where T: DeserializeOwned {
+        if keys.is_empty() {
+            return Vec::new();
+        };
+        let mut conn = match self.pool.get().await {
+            Ok(conn) => conn,
+            Err(_) => return keys.iter().map(|_| None).collect()
+        };
+        let raw : Vec<Option<String>> = match redis::cmd("MGET").arg(keys).query_async::<Connection, Vec<Option<String>>>(&mut conn).await {
+            Ok(raw) => raw,
+            Err(_) => return keys.iter().map(|_| None).collect()
+        };
+        raw.into_iter().map(|value| value.and_then(|value| json::from_str::<T>(&value).ok())).collect()
+    }
+
+    pub async fn delete(&self, key: &str) {
+        let mut conn = match self.pool.get().await {
+            Ok(conn) => conn,
+            Err(_) => return
+        };
+        let _ = redis::cmd("DEL").arg(key).query_async::<Connection, ()>(&mut conn).await;
+    }
+
+    // counter for fixed-window rate limits, the window starts with the first hit
+    pub async fn increment_with_expiry(&self, key: &str, expiry_ms: usize) -> Option<u64> {
+        let mut conn = self.pool.get().await.ok()?;
+        let count = redis::cmd("INCR").arg(key).query_async::<Connection, u64>(&mut conn).await.ok()?;
+        if count == 1 {
+            let _ = redis::cmd("PEXPIRE").arg(key).arg(expiry_ms).query_async::<Connection, ()>(&mut conn).await;
+        };
+        Some(count)
+    }
+
+    // keeps the newest `max_length` entries
+    pub async fn push_capped<T>(&self, key: &str, value: &T, max_length: usize, expiry_ms: usize) where T: Serialize {
+        let mut conn = match self.pool.get().await {
+            Ok(conn) => conn,
+            Err(_) => return
+        };
+        let stringified = match json::to_string(value) {
+            Ok(stringified) => stringified,
+            Err(_) => return
+        };
+        let _ : RedisResult<()> = redis::pipe()
+            .cmd("RPUSH").arg(key).arg(stringified).ignore()
+            .cmd("LTRIM").arg(key).arg(-(max_length as isize)).arg(-1).ignore()
+            .cmd("PEXPIRE").arg(key).arg(expiry_ms).ignore()
+            .query_async(&mut *conn).await;
+    }
+
+    // reads and clears the list in one transaction
+    pub async fn take_list<T>(&self, key: &str) -> Vec<T> where T: DeserializeOwned {
+        let mut conn = match self.pool.get().await {
+            Ok(conn) => conn,
+            Err(_) => return Vec::new()
+        };
+        let result : RedisResult<(Vec<String>,)> = redis::pipe().atomic()
+            .cmd("LRANGE").arg(key).arg(0).arg(-1)
+            .cmd("DEL").arg(key).ignore()
+            .query_async(&mut *conn).await;
+        match result {
+            Ok((raw,)) => raw.into_iter().filter_map(|value| json::from_str::<T>(&value).ok()).collect(),
+            Err(_) => Vec::new()
+        }
+    }
+
     pub async fn get_unchecked<T>(&self, key: &str) -> Option<T> where T: DeserializeOwned {
         match self.get(key).await {
             Ok(val) => Some(val),
@@ -93,7 +93,7 @@ impl DenormalizeIpIdentitiesMigration {
                     }
                     None => {
                         let ip_identity = IpIdentity {
-                            ip, players
+                            ip, players, sightings: Vec::new()
                         };
                         database.save(&ip_identity).await
                     }
@@ -0,0 +1,40 @@
+use futures::StreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::FindOptions;
+use crate::database::Database;
+use crate::database::migrations::DatabaseMigration;
+use crate::util::string::name_grams;
+
+// fills in the name grams fuzzy search is indexed on for players who have not
+// logged in (and so had them set) since they were introduced
+pub struct IndexNameGramsMigration {}
+
+#[async_trait]
+impl DatabaseMigration for IndexNameGramsMigration {
+    fn get_id(&self) -> String {
+        String::from("index_name_grams")
+    }
+
+    async fn perform(&self, database: &Database) {
+        info!("Indexing player name grams...");
+        let players = database.players.clone_with_type::<Document>();
+        let opts = FindOptions::builder().batch_size(Some(10_000)).projection(doc! { "nameLower": 1 }).build();
+        let mut cursor = match players.find(doc! { "nameGrams": { "$exists": false } }, Some(opts)).await {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                warn!("Could not read players: {}", e);
+                return
+            }
+        };
+        let mut modified = 0u64;
+        while let Some(Ok(document)) = cursor.next().await {
+            let grams = match document.get_str("nameLower") {
+                Ok(name_lower) => name_grams(name_lower),
+                Err(_) => continue
+            };
+            let _ = players.update_one(doc! { "_id": document.get("_id").cloned().unwrap_or(Bson::Null) }, doc! { "$set": { "nameGrams": grams } }, None).await;
+            modified += 1;
+        }
+        info!("Indexed name grams of {} player(s)", modified);
+    }
+}
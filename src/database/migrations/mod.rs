@@ -1,9 +1,14 @@
 use crate::database::Database;
 use crate::database::migrations::denormalize_ip_identities::DenormalizeIpIdentitiesMigration;
 use crate::database::migrations::reset_stats::ResetStatsMigration;
+use crate::database::migrations::rekey_ip_hashes::RekeyIpHashesMigration;
+use crate::database::migrations::index_name_grams::IndexNameGramsMigration;
+use crate::config::MarsConfig;
 
 pub mod denormalize_ip_identities;
 mod reset_stats;
+mod rekey_ip_hashes;
+mod index_name_grams;
 
 #[async_trait]
 pub trait DatabaseMigration {
@@ -16,15 +21,21 @@ pub struct MigrationExecutor {
 }
 
 impl MigrationExecutor {
-    pub fn new() -> Self {
+    pub fn new(config: &MarsConfig) -> Self {
         let denormalize_ip_identities_migration =
             Box::new(DenormalizeIpIdentitiesMigration {});
         let reset_stats_migration =
             Box::new(ResetStatsMigration {});
+        let rekey_ip_hashes_migration =
+            Box::new(RekeyIpHashesMigration { hasher: config.ip_hasher.clone() });
+        let index_name_grams_migration =
+            Box::new(IndexNameGramsMigration {});
         Self {
             migrations: vec![
                 denormalize_ip_identities_migration,
-                reset_stats_migration
+                reset_stats_migration,
+                rekey_ip_hashes_migration,
+                index_name_grams_migration
             ]
         }
     }
@@ -0,0 +1,140 @@
+use futures::StreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::Collection;
+use mongodb::options::FindOptions;
+use crate::database::Database;
+use crate::database::migrations::DatabaseMigration;
+use crate::database::models::ip_identity::IpIdentity;
+use crate::database::models::shared_ip::SharedIpKind;
+use crate::util::ip_hash::IpHasher;
+
+// moves every stored ip (or older hash of one) onto the current hash key,
+// the API keeps matching the old values meanwhile as long as dual-read is on
+pub struct RekeyIpHashesMigration {
+    pub hasher: IpHasher
+}
+
+#[async_trait]
+impl DatabaseMigration for RekeyIpHashesMigration {
+    fn get_id(&self) -> String {
+        String::from("rekey_ip_hashes")
+    }
+
+    async fn perform(&self, database: &Database) {
+        let version = match self.hasher.current_version() {
+            Some(version) => version,
+            None => {
+                warn!("No IP hash keys are configured, nothing to re-key");
+                return
+            }
+        };
+        info!("Re-keying stored IPs to hash key version {}...", version);
+        self.rekey_array_field(&database.players.clone_with_type::<Document>(), "ips").await;
+        self.rekey_string_field(&database.sessions.clone_with_type::<Document>(), "ip").await;
+        self.rekey_array_field(&database.punishments.clone_with_type::<Document>(), "targetIps").await;
+        self.rekey_ip_identities(database).await;
+        self.rekey_shared_ips(database).await;
+        info!("Re-keyed stored IPs. Cached players should be flushed before turning dual-read off");
+    }
+}
+
+impl RekeyIpHashesMigration {
+    fn batch_options(field: &str) -> FindOptions {
+        FindOptions::builder().batch_size(Some(10_000)).projection(doc! { field: 1 }).build()
+    }
+
+    async fn rekey_array_field(&self, collection: &Collection<Document>, field: &str) {
+        let mut cursor = match collection.find(doc! { field: { "$exists": true, "$ne": [] } }, Some(Self::batch_options(field))).await {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                warn!("Could not read {}.{}: {}", collection.name(), field, e);
+                return
+            }
+        };
+        let mut modified = 0u64;
+        while let Some(Ok(document)) = cursor.next().await {
+            let stored : Vec<String> = document.get_array(field).map(|values| {
+                values.iter().filter_map(|value| value.as_str().map(|value| value.to_owned())).collect()
+            }).unwrap_or_default();
+            if stored.iter().all(|ip| self.hasher.is_current(ip)) {
+                continue;
+            };
+            let mut rekeyed : Vec<String> = Vec::with_capacity(stored.len());
+            for ip in stored.iter() {
+                let ip = self.hasher.rekey(ip).unwrap_or_else(|| ip.clone());
+                if !rekeyed.contains(&ip) {
+                    rekeyed.push(ip);
+                };
+            }
+            if rekeyed != stored {
+                let _ = collection.update_one(doc! { "_id": document.get("_id").cloned().unwrap_or(Bson::Null) }, doc! { "$set": { field: rekeyed } }, None).await;
+                modified += 1;
+            };
+        }
+        info!("Re-keyed {} document(s) in {}.{}", modified, collection.name(), field);
+    }
+
+    async fn rekey_string_field(&self, collection: &Collection<Document>, field: &str) {
+        let mut cursor = match collection.find(doc! { field: { "$type": "string", "$ne": "" } }, Some(Self::batch_options(field))).await {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                warn!("Could not read {}.{}: {}", collection.name(), field, e);
+                return
+            }
+        };
+        let mut modified = 0u64;
+        while let Some(Ok(document)) = cursor.next().await {
+            let rekeyed = match document.get_str(field).ok().and_then(|ip| self.hasher.rekey(ip)) {
+                Some(rekeyed) => rekeyed,
+                None => continue
+            };
+            let _ = collection.update_one(doc! { "_id": document.get("_id").cloned().unwrap_or(Bson::Null) }, doc! { "$set": { field: rekeyed } }, None).await;
+            modified += 1;
+        }
+        info!("Re-keyed {} document(s) in {}.{}", modified, collection.name(), field);
+    }
+
+    async fn rekey_ip_identities(&self, database: &Database) {
+        // collect first, re-keying inserts into the collection being read
+        let cursor = database.ip_identities.clone_with_type::<Document>()
+            .find(doc! {}, Some(Self::batch_options("_id"))).await.ok();
+        let stored : Vec<String> = Database::consume_cursor_into_owning_vec_option(cursor).await.into_iter()
+            .filter_map(|document| document.get_str("_id").ok().map(|ip| ip.to_owned()))
+            .collect();
+        let stale : Vec<(String, String)> = stored.iter()
+            .filter_map(|ip| self.hasher.rekey(ip).map(|rekeyed| (ip.clone(), rekeyed)))
+            .collect();
+        // chained values are replaced by direct hashes as players rejoin, older keys are needed until then
+        let chained = stored.iter().filter(|ip| !self.hasher.is_current(ip)).count();
+        info!("{} ip identities to re-key, {} not yet hashed directly under the current key", stale.len(), chained);
+        for (ip, rekeyed) in stale.iter() {
+            IpIdentity::rekey(database, ip, rekeyed).await;
+        }
+    }
+
+    async fn rekey_shared_ips(&self, database: &Database) {
+        let entries = Database::consume_cursor_into_owning_vec_option(database.shared_ips.find(doc! {}, None).await.ok()).await;
+        for mut entry in entries.into_iter() {
+            let changed = match entry.kind {
+                // entries added before hashing was on still hold the raw ip
+                SharedIpKind::Ip => match self.hasher.rekey(&entry.value) {
+                    Some(rekeyed) => {
+                        entry.kind = SharedIpKind::Hash;
+                        entry.value = rekeyed;
+                        entry.key = None;
+                        true
+                    },
+                    None => false
+                },
+                SharedIpKind::Hash => match self.hasher.rekey(&entry.value) {
+                    Some(rekeyed) => { entry.value = rekeyed; true },
+                    None => false
+                },
+                SharedIpKind::Cidr => false
+            };
+            if changed {
+                database.save(&entry).await;
+            };
+        }
+    }
+}
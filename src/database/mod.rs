@@ -1,37 +1,46 @@
-use std::{str::FromStr, time::Duration};
-use std::collections::HashSet;
+use std::{str::FromStr, sync::Arc, time::Duration};
+use std::collections::{HashMap, HashSet};
 
 use anyhow::anyhow;
-use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use mars_api_rs_macro::IdentifiableDocument;
 use mongodb::bson::Document;
-use mongodb::{bson::{doc, oid::ObjectId}, Client, Collection, Cursor, options::{ClientOptions, FindOneOptions, UpdateOptions}, results::DeleteResult};
+use mongodb::{bson::{doc, oid::ObjectId}, Client, IndexModel, Collection, Cursor, options::{ClientOptions, FindOneAndUpdateOptions, FindOneOptions, IndexOptions, ReturnDocument, UpdateOptions}, results::DeleteResult};
 use mongodb::options::FindOptions;
+use mongodb::error::TRANSIENT_TRANSACTION_ERROR;
 use rand::Rng;
-use rocket::form::validate::Contains;
 use rocket::serde::DeserializeOwned;
 use serde::Serialize;
+use tokio::sync::RwLock;
 
 use models::tag::Tag;
 
 use crate::database::models::player::Player;
-use crate::database::models::ip_identity::IpIdentity;
-use crate::database::models::player::SimplePlayer;
+use crate::database::models::ip_identity::{AltAccount, AltGraph, AltGraphBounds, AltLink, IpIdentity};
+use crate::database::models::player::{PlayerNameProjection, SimplePlayer};
 use crate::util::validation::verbose_result_ok;
+use crate::util::ip_hash::IpHasher;
+use crate::util::pagination::PageCursor;
+use crate::util::string::name_grams;
+use crate::util::time::get_u64_time_millis;
 
-use self::models::{achievement::Achievement, death::Death, level::Level, punishment::Punishment, r#match::Match, rank::Rank, session::Session};
+use self::models::{achievement::Achievement, death::Death, level::Level, punishment::{Punishment, PunishmentEdit, PunishmentKind, PunishmentReversion, PunishmentType}, r#match::Match, rank::Rank, session::Session, stat_snapshot::PlayerStatSnapshot, friendship::Friendship, clan::Clan, preference::PlayerPreferences, cosmetic::CosmeticOwnership, currency::{CurrencyBalance, CurrencyTransaction, CurrencyTransactionError, RecordedCurrencyTransaction}, chat_message::ChatMessage, chat_filter::ChatFilterHit, appeal::{Appeal, AppealComment, AppealStatus}, report::{Report, ReportStatus, ReportSubmission}, audit_entry::AuditEntry, shared_ip::{SharedIp, SharedIpList}, gate_policy::GatePolicies};
 
 pub mod models;
 pub mod migrations;
 pub mod cache;
 
+const SHARED_IP_LIST_LIFETIME_MILLIS : u64 = 60_000;
+const CURRENCY_TRANSACTION_ATTEMPTS : usize = 3;
+
 pub trait CollectionOwner<T> {
     fn get_collection(database: &Database) -> &Collection<T>;
     fn get_collection_name() -> &'static str;
 }
 
 pub struct Database {
+    // kept for sessions, multi-document transactions are started from the client
+    pub client: Client,
     pub mongo: mongodb::Database,
     pub tags: Collection<Tag>,
     pub achievements: Collection<Achievement>,
@@ -42,7 +51,23 @@ pub struct Database {
     pub matches: Collection<Match>,
     pub deaths: Collection<Death>,
     pub levels: Collection<Level>,
-    pub ip_identities: Collection<IpIdentity>
+    pub ip_identities: Collection<IpIdentity>,
+    pub stat_snapshots: Collection<PlayerStatSnapshot>,
+    pub friendships: Collection<Friendship>,
+    pub clans: Collection<Clan>,
+    pub player_preferences: Collection<PlayerPreferences>,
+    pub cosmetic_ownerships: Collection<CosmeticOwnership>,
+    pub currency_transactions: Collection<CurrencyTransaction>,
+    pub currency_balances: Collection<CurrencyBalance>,
+    pub chat_messages: Collection<ChatMessage>,
+    pub chat_filter_hits: Collection<ChatFilterHit>,
+    pub appeals: Collection<Appeal>,
+    pub reports: Collection<Report>,
+    pub audit_entries: Collection<AuditEntry>,
+    pub shared_ips: Collection<SharedIp>,
+    pub gate_policies: Collection<GatePolicies>,
+    // read on every prelogin, reloaded when it changes or goes stale (another instance changed it)
+    shared_ip_list: RwLock<Option<(u64, Arc<SharedIpList>)>>
 }
 
 impl Database {
@@ -130,7 +155,7 @@ impl Database {
         let _ = self.players.update_many(doc! {
             "$and": [{"nameLower": name.to_lowercase()}, {"$not": {"_id": &keep_id}}]
         }, doc! {
-            "$set": {"name": &temp_name, "nameLower": &temp_name}
+            "$set": {"name": &temp_name, "nameLower": &temp_name, "nameGrams": name_grams(&temp_name.to_lowercase())}
         }, None).await;
     }
 
@@ -170,27 +195,84 @@ impl Database {
         }
     }
 
-    pub async fn get_alts_for_player(&self, player: &Player) -> Vec<Player> {
-        let unordered_futures = FuturesUnordered::new();
-        for ip in &player.ips {
-            unordered_futures.push(IpIdentity::find_players_for_ip(self, ip));
-        }
-        let players = {
-            let players_with_duplicates = unordered_futures.collect::<Vec<_>>().await
-                .into_iter().flatten().collect::<Vec<_>>();
-            let mut player_set : Vec<Player> = Vec::new();
-            for player in players_with_duplicates {
-                if !player_set.iter().map(|p| p.id.clone()).collect::<Vec<_>>().contains(&player.id) {
-                    player_set.push(player);
+    // breadth-first walk over shared ips, each level one more hop away from the player
+    pub async fn get_alt_graph(&self, player: &Player, bounds: &AltGraphBounds, hasher: &IpHasher) -> AltGraph {
+        let mut visited : HashSet<String> = HashSet::from([player.id.clone()]);
+        let mut expanded_ips : HashSet<String> = HashSet::new();
+        let mut frontier : Vec<Player> = vec![player.clone()];
+        let mut graph = AltGraph { accounts: Vec::new(), skipped_ips: Vec::new(), truncated: false };
+        let shared_ips = self.get_shared_ip_list(hasher).await;
+        for depth in 1..=bounds.depth {
+            // ips not yet re-keyed may already have their identity under the current key
+            let ips : Vec<String> = frontier.iter()
+                .flat_map(|p| p.ips.iter().flat_map(|ip| [Some(ip.clone()), hasher.rekey(ip)]).flatten())
+                .filter(|ip| expanded_ips.insert(ip.clone()))
+                .collect();
+            if ips.is_empty() {
+                break;
+            };
+            let frontier_ids : HashSet<&String> = frontier.iter().map(|p| &p.id).collect();
+            let mut discovered : HashMap<String, Vec<AltLink>> = HashMap::new();
+            for identity in IpIdentity::get_ip_identities_by_ips(self, &ips).await {
+                if shared_ips.is_shared_key(&identity.ip) {
+                    graph.skipped_ips.push(identity.ip.clone());
+                    continue;
+                };
+                // direct alts are always shown, the bound only stops the walk from spreading further
+                if depth > 1 && identity.players.len() > bounds.max_ip_fanout {
+                    graph.skipped_ips.push(identity.ip.clone());
+                    graph.truncated = true;
+                    continue;
+                };
+                let via = match identity.players.iter().find(|id| frontier_ids.contains(id)) {
+                    Some(via) => via,
+                    None => continue
+                };
+                for linked_id in identity.players.iter().filter(|id| !visited.contains(*id)) {
+                    let sighting = identity.get_sighting(linked_id);
+                    discovered.entry(linked_id.clone()).or_default().push(AltLink {
+                        ip: identity.ip.clone(),
+                        via: via.clone(),
+                        shared_by: identity.players.len(),
+                        first_seen_at: sighting.map(|s| s.first_seen_at),
+                        last_seen_at: sighting.map(|s| s.last_seen_at)
+                    });
                 }
             }
-            player_set
-        };
-        players
-        // let cursor = unwrap_helper::result_return_default!(self.players.find(doc! {
-        //     "ips": {"$in": &player.ips}, "_id": {"$ne": &player.id}
-        // }, None).await, Vec::new());
-        // Database::consume_cursor_into_owning_vec(cursor).await
+            if discovered.is_empty() {
+                break;
+            };
+
+            // keep the most strongly linked accounts when over the limit
+            let remaining = bounds.max_accounts.saturating_sub(graph.accounts.len());
+            let mut candidates : Vec<(String, Vec<AltLink>)> = discovered.into_iter().collect();
+            if candidates.len() > remaining {
+                candidates.sort_by_key(|(_, links)| std::cmp::Reverse(links.len()));
+                candidates.truncate(remaining);
+                graph.truncated = true;
+            };
+            let ids : Vec<&String> = candidates.iter().map(|(id, _)| id).collect();
+            let cursor = self.players.find(doc! { "_id": { "$in": ids } }, None).await.ok();
+            let mut players : HashMap<String, Player> = Self::consume_cursor_into_owning_vec_option(cursor).await
+                .into_iter().map(|p| (p.id.clone(), p)).collect();
+
+            frontier = Vec::new();
+            for (id, links) in candidates {
+                visited.insert(id.clone());
+                let found = match players.remove(&id) {
+                    Some(found) => found,
+                    None => continue
+                };
+                frontier.push(found.clone());
+                let confidence = AltGraph::score_links(&links, depth);
+                graph.accounts.push(AltAccount { player: found, depth, links, confidence });
+            }
+            if graph.accounts.len() >= bounds.max_accounts {
+                break;
+            };
+        }
+        graph.accounts.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+        graph
     }
 
     pub async fn save<R>(&self, record: &R) where R: CollectionOwner<R> + Serialize + IdentifiableDocument {
@@ -235,6 +317,481 @@ impl Database {
         Self::consume_cursor_into_owning_vec_option(cursor).await
     }
 
+    pub async fn find_players_by_ids_or_names(&self, keys: &[String]) -> Vec<Player> {
+        if keys.is_empty() {
+            return Vec::new();
+        };
+        let lowercase_keys : Vec<String> = keys.iter().map(|key| key.to_lowercase()).collect();
+        let cursor = self.players.find(doc! {
+            "$or": [
+                { "_id": { "$in": keys } },
+                { "nameLower": { "$in": &lowercase_keys } }
+            ]
+        }, None).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    pub async fn find_players_by_exact_name(&self, name_lower: &str) -> Vec<PlayerNameProjection> {
+        let opts = FindOptions::builder()
+            .projection(doc! { "_id": 1, "name": 1, "nameLower": 1, "lastJoinedAt": 1, "pastNames": 1 })
+            .build();
+        let cursor = self.players.clone_with_type::<PlayerNameProjection>().find(doc! {
+            "$or": [{ "nameLower": name_lower }, { "pastNames.nameLower": name_lower }]
+        }, Some(opts)).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    // players sharing at least min_shared name grams, the closest and then most recently seen first
+    pub async fn find_players_by_name_grams(&self, grams: &[String], min_shared: usize, limit: i64) -> Vec<PlayerNameProjection> {
+        let pipeline = vec![
+            doc! { "$match": { "nameGrams": { "$in": grams } } },
+            doc! { "$project": {
+                "_id": 1, "name": 1, "nameLower": 1, "lastJoinedAt": 1, "pastNames": 1,
+                "shared": { "$size": { "$setIntersection": ["$nameGrams", grams] } }
+            } },
+            doc! { "$match": { "shared": { "$gte": min_shared as i64 } } },
+            doc! { "$sort": { "shared": -1, "lastJoinedAt": -1 } },
+            doc! { "$limit": limit }
+        ];
+        let mut cursor = match self.players.aggregate(pipeline, None).await {
+            Ok(cursor) => cursor,
+            Err(_) => return Vec::new()
+        };
+        let mut candidates = Vec::new();
+        while let Some(Ok(document)) = cursor.next().await {
+            if let Ok(candidate) = mongodb::bson::from_document::<PlayerNameProjection>(document) {
+                candidates.push(candidate);
+            };
+        }
+        candidates
+    }
+
+    pub async fn find_players_by_name_prefix(&self, prefix: &str, limit: i64) -> Vec<PlayerNameProjection> {
+        // names are restricted to [a-z0-9_] by the caller, so the prefix is safe to embed in an anchored regex
+        let pattern = format!("^{}", prefix.to_lowercase());
+        let opts = FindOptions::builder()
+            .projection(doc! { "_id": 1, "name": 1, "nameLower": 1, "lastJoinedAt": 1, "pastNames": 1 })
+            .sort(doc! { "lastJoinedAt": -1 })
+            .limit(limit)
+            .build();
+        let cursor = self.players.clone_with_type::<PlayerNameProjection>().find(doc! {
+            "$or": [
+                { "nameLower": { "$regex": &pattern } },
+                { "pastNames.nameLower": { "$regex": &pattern } }
+            ]
+        }, Some(opts)).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    pub async fn ensure_indexes(&self) {
+        let player_indexes = vec![
+            IndexModel::builder().keys(doc! { "nameLower": 1 }).build(),
+            IndexModel::builder().keys(doc! { "pastNames.nameLower": 1 }).build(),
+            IndexModel::builder().keys(doc! { "nameGrams": 1 }).build(),
+            IndexModel::builder().keys(doc! { "lastJoinedAt": -1 }).build()
+        ];
+        if let Err(e) = self.players.create_indexes(player_indexes, None).await {
+            warn!("Could not create player indexes: {}", e);
+        };
+        let session_indexes = vec![
+            IndexModel::builder().keys(doc! { "endedAt": 1, "player.id": 1 }).build(),
+            IndexModel::builder().keys(doc! { "ip": 1, "endedAt": 1 }).build()
+        ];
+        if let Err(e) = self.sessions.create_indexes(session_indexes, None).await {
+            warn!("Could not create session indexes: {}", e);
+        };
+        let snapshot_index = IndexModel::builder().keys(doc! { "playerId": 1, "date": -1 }).build();
+        if let Err(e) = self.stat_snapshots.create_index(snapshot_index, None).await {
+            warn!("Could not create stat snapshot indexes: {}", e);
+        };
+        let friendship_indexes = vec![
+            IndexModel::builder().keys(doc! { "requester.id": 1 }).build(),
+            IndexModel::builder().keys(doc! { "recipient.id": 1 }).build(),
+            IndexModel::builder().keys(doc! { "pairKey": 1 }).options(IndexOptions::builder().unique(true).sparse(true).build()).build()
+        ];
+        if let Err(e) = self.friendships.create_indexes(friendship_indexes, None).await {
+            warn!("Could not create friendship indexes: {}", e);
+        };
+        let unique = IndexOptions::builder().unique(true).build();
+        let clan_indexes = vec![
+            IndexModel::builder().keys(doc! { "nameLower": 1 }).options(unique.clone()).build(),
+            IndexModel::builder().keys(doc! { "tagLower": 1 }).options(unique).build(),
+            IndexModel::builder().keys(doc! { "members.player.id": 1 }).build()
+        ];
+        if let Err(e) = self.clans.create_indexes(clan_indexes, None).await {
+            warn!("Could not create clan indexes: {}", e);
+        };
+        let ownership_index = IndexModel::builder().keys(doc! { "playerId": 1 }).build();
+        if let Err(e) = self.cosmetic_ownerships.create_index(ownership_index, None).await {
+            warn!("Could not create cosmetic ownership indexes: {}", e);
+        };
+        let transaction_indexes = vec![
+            IndexModel::builder().keys(doc! { "playerId": 1, "createdAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "idempotencyKey": 1 }).options(IndexOptions::builder().unique(true).sparse(true).build()).build()
+        ];
+        if let Err(e) = self.currency_transactions.create_indexes(transaction_indexes, None).await {
+            warn!("Could not create currency transaction indexes: {}", e);
+        };
+        // expiry is stamped on each message, so a retention change only affects new messages
+        let chat_indexes = vec![
+            IndexModel::builder().keys(doc! { "player.id": 1, "createdAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "serverId": 1, "createdAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "expiresAt": 1 }).options(IndexOptions::builder().expire_after(Duration::from_secs(0)).build()).build()
+        ];
+        if let Err(e) = self.chat_messages.create_indexes(chat_indexes, None).await {
+            warn!("Could not create chat message indexes: {}", e);
+        };
+        let filter_hit_indexes = vec![
+            IndexModel::builder().keys(doc! { "ruleId": 1, "createdAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "player.id": 1, "createdAt": -1, "_id": -1 }).build()
+        ];
+        if let Err(e) = self.chat_filter_hits.create_indexes(filter_hit_indexes, None).await {
+            warn!("Could not create chat filter hit indexes: {}", e);
+        };
+        let punishment_indexes = vec![
+            IndexModel::builder().keys(doc! { "target.id": 1 }).build(),
+            IndexModel::builder().keys(doc! { "punisher.id": 1, "issuedAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "action.kind": 1, "issuedAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "issuedAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "expiredAt": 1, "reversion": 1, "action.length": 1 }).build()
+        ];
+        if let Err(e) = self.punishments.create_indexes(punishment_indexes, None).await {
+            warn!("Could not create punishment indexes: {}", e);
+        };
+        let appeal_indexes = vec![
+            // one open appeal per punishment, closed ones always have closedAt set
+            IndexModel::builder().keys(doc! { "punishmentId": 1 }).options(
+                IndexOptions::builder().name(String::from("punishmentId_active")).unique(true)
+                    .partial_filter_expression(doc! { "closedAt": { "$type": "null" } }).build()
+            ).build(),
+            IndexModel::builder().keys(doc! { "status": 1, "createdAt": -1 }).build(),
+            IndexModel::builder().keys(doc! { "player.id": 1, "createdAt": -1 }).build()
+        ];
+        if let Err(e) = self.appeals.create_indexes(appeal_indexes, None).await {
+            warn!("Could not create appeal indexes: {}", e);
+        };
+        let report_indexes = vec![
+            IndexModel::builder().keys(doc! { "target.id": 1, "status": 1, "lastReportedAt": -1 }).build(),
+            IndexModel::builder().keys(doc! { "status": 1, "createdAt": -1 }).build(),
+            IndexModel::builder().keys(doc! { "assignee.id": 1, "createdAt": -1 }).build()
+        ];
+        if let Err(e) = self.reports.create_indexes(report_indexes, None).await {
+            warn!("Could not create report indexes: {}", e);
+        };
+        let audit_indexes = vec![
+            IndexModel::builder().keys(doc! { "createdAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "actor.player.id": 1, "createdAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "target.id": 1, "createdAt": -1, "_id": -1 }).build(),
+            IndexModel::builder().keys(doc! { "action": 1, "createdAt": -1, "_id": -1 }).build()
+        ];
+        if let Err(e) = self.audit_entries.create_indexes(audit_indexes, None).await {
+            warn!("Could not create audit indexes: {}", e);
+        };
+        let shared_ip_index = IndexModel::builder().keys(doc! { "value": 1 }).options(IndexOptions::builder().unique(true).build()).build();
+        if let Err(e) = self.shared_ips.create_index(shared_ip_index, None).await {
+            warn!("Could not create shared ip indexes: {}", e);
+        };
+    }
+
+    pub async fn get_player_stat_history(&self, player_id: &str, from: u64, to: u64) -> Vec<PlayerStatSnapshot> {
+        let opts = FindOptions::builder().sort(doc! { "date": 1 }).build();
+        let cursor = self.stat_snapshots.find(doc! {
+            "playerId": player_id, "date": { "$gte": from as i64, "$lte": to as i64 }
+        }, Some(opts)).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    pub async fn get_friendships(&self, player_id: &String) -> Vec<Friendship> {
+        let cursor = self.friendships.find(doc! {
+            "$or": [{ "requester.id": player_id }, { "recipient.id": player_id }]
+        }, None).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    pub async fn find_friendship(&self, player_id: &String, other_id: &String) -> Option<Friendship> {
+        self.friendships.find_one(doc! {
+            "$or": [
+                { "requester.id": player_id, "recipient.id": other_id },
+                { "requester.id": other_id, "recipient.id": player_id }
+            ]
+        }, None).await.unwrap_or(None)
+    }
+
+    pub async fn get_active_sessions_for_players(&self, player_ids: &[String]) -> Vec<Session> {
+        if player_ids.is_empty() {
+            return Vec::new();
+        };
+        let cursor = self.sessions.find(doc! { "endedAt": null, "player.id": { "$in": player_ids } }, None).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    pub async fn count_online_players(&self) -> u64 {
+        self.sessions.count_documents(doc! { "endedAt": null }, None).await.unwrap_or(0)
+    }
+
+    // distinct accounts with an open session from any of the given ips
+    pub async fn get_online_player_ids_for_ips(&self, ips: &[String]) -> Vec<String> {
+        match self.sessions.distinct("player.id", doc! { "endedAt": null, "ip": { "$in": ips } }, None).await {
+            Ok(ids) => ids.into_iter().filter_map(|id| id.as_str().map(String::from)).collect(),
+            Err(_) => Vec::new()
+        }
+    }
+
+    pub async fn get_player_preferences(&self, player_id: &str) -> Option<PlayerPreferences> {
+        self.player_preferences.find_one(doc! { "_id": player_id }, None).await.unwrap_or(None)
+    }
+
+    pub async fn get_cosmetic_ownerships(&self, player_id: &str) -> Vec<CosmeticOwnership> {
+        let cursor = self.cosmetic_ownerships.find(doc! { "playerId": player_id }, None).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    // the balance change and the ledger row are written in one transaction (which needs a replica set),
+    // so the balance never moves without a ledger entry and a spend can never take it below zero;
+    // a transaction whose idempotency key was already used resolves to the earlier one
+    pub async fn record_currency_transaction(&self, transaction: CurrencyTransaction) -> Result<RecordedCurrencyTransaction, CurrencyTransactionError> {
+        if let Some(existing) = self.find_currency_transaction_by_key(&transaction).await {
+            let balance = self.get_currency_balance(&existing.player_id).await;
+            return Ok(RecordedCurrencyTransaction { transaction: existing, created: false, balance });
+        };
+        if self.ensure_currency_balance(&transaction.player_id).await.is_none() {
+            return Err(CurrencyTransactionError::Failed);
+        };
+        for _ in 0..CURRENCY_TRANSACTION_ATTEMPTS {
+            match self.try_record_currency_transaction(&transaction).await {
+                Ok(Some(balance)) => return Ok(RecordedCurrencyTransaction { transaction, created: true, balance }),
+                Ok(None) => return Err(CurrencyTransactionError::InsufficientFunds),
+                // write conflicts with a concurrent transaction on the same balance
+                Err(e) if e.contains_label(TRANSIENT_TRANSACTION_ERROR) => continue,
+                Err(e) => {
+                    warn!("Could not record currency transaction for {}: {}", transaction.player_id, e);
+                    break
+                }
+            };
+        }
+
+        // most likely a concurrent request with the same key, nothing was written
+        let existing = self.find_currency_transaction_by_key(&transaction).await.ok_or(CurrencyTransactionError::Failed)?;
+        let balance = self.get_currency_balance(&existing.player_id).await;
+        Ok(RecordedCurrencyTransaction { transaction: existing, created: false, balance })
+    }
+
+    // None when the balance is too low for a spend, dropping the session aborts anything uncommitted
+    async fn try_record_currency_transaction(&self, transaction: &CurrencyTransaction) -> mongodb::error::Result<Option<i64>> {
+        let mut session = self.client.start_session(None).await?;
+        session.start_transaction(None).await?;
+        let filter = if transaction.amount < 0 {
+            doc! { "_id": &transaction.player_id, "balance": { "$gte": -transaction.amount } }
+        } else {
+            doc! { "_id": &transaction.player_id }
+        };
+        let opts = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        let balance = match self.currency_balances.find_one_and_update_with_session(
+            filter, doc! { "$inc": { "balance": transaction.amount } }, opts, &mut session
+        ).await? {
+            Some(updated) => updated.balance,
+            None => {
+                session.abort_transaction().await?;
+                return Ok(None);
+            }
+        };
+        self.currency_transactions.insert_one_with_session(transaction, None, &mut session).await?;
+        session.commit_transaction().await?;
+        Ok(Some(balance))
+    }
+
+    pub async fn find_currency_transaction_by_key(&self, transaction: &CurrencyTransaction) -> Option<CurrencyTransaction> {
+        let key = transaction.idempotency_key.as_ref()?;
+        self.currency_transactions.find_one(doc! { "idempotencyKey": key }, None).await.unwrap_or(None)
+    }
+
+    pub async fn get_currency_balance(&self, player_id: &str) -> i64 {
+        self.ensure_currency_balance(player_id).await.unwrap_or(0)
+    }
+
+    // players without a balance yet get one summed from their ledger, all
+    // transactions go through here first so the sum cannot miss one
+    async fn ensure_currency_balance(&self, player_id: &str) -> Option<i64> {
+        if let Ok(Some(existing)) = self.currency_balances.find_one(doc! { "_id": player_id }, None).await {
+            return Some(existing.balance);
+        };
+        let balance = self.sum_currency_transactions(player_id).await?;
+        // losing to a concurrent backfill is fine, it summed the same ledger
+        let _ = self.currency_balances.insert_one(CurrencyBalance { player_id: player_id.to_owned(), balance }, None).await;
+        self.currency_balances.find_one(doc! { "_id": player_id }, None).await.ok().flatten().map(|existing| existing.balance)
+    }
+
+    async fn sum_currency_transactions(&self, player_id: &str) -> Option<i64> {
+        let pipeline = vec![
+            doc! { "$match": { "playerId": player_id } },
+            doc! { "$group": { "_id": null, "balance": { "$sum": "$amount" } } }
+        ];
+        let mut cursor = self.currency_transactions.aggregate(pipeline, None).await.ok()?;
+        match cursor.next().await {
+            Some(Ok(result)) => Some(result.get_i64("balance").or_else(|_| result.get_i32("balance").map(i64::from)).unwrap_or(0)),
+            Some(Err(_)) => None,
+            None => Some(0)
+        }
+    }
+
+    // earnings from one kill or objective share a timestamp, so the cursor also carries the id
+    pub async fn get_currency_transactions(&self, player_id: &str, before: Option<&PageCursor>, limit: i64) -> Vec<CurrencyTransaction> {
+        let mut filter = doc! { "playerId": player_id };
+        if let Some(before) = before {
+            before.apply(&mut filter, "createdAt");
+        };
+        let opts = FindOptions::builder().sort(PageCursor::sort("createdAt")).limit(limit).build();
+        let cursor = self.currency_transactions.find(filter, Some(opts)).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    // punishments of this type that still count towards the escalation ladder, oldest first
+    pub async fn get_prior_offences(&self, player_id: &str, punishment_type: &PunishmentType, default_decay_days: u64) -> Vec<Punishment> {
+        let mut filter = doc! { "target.id": player_id, "reason.name": &punishment_type.name, "reversion": null };
+        let decay_days = punishment_type.offence_decay_days.unwrap_or(default_decay_days);
+        if decay_days > 0 {
+            let cutoff = get_u64_time_millis().saturating_sub(decay_days * 86_400_000);
+            filter.insert("issuedAt", doc! { "$gte": cutoff as f64 });
+        };
+        let opts = FindOptions::builder().sort(doc! { "issuedAt": 1 }).build();
+        let cursor = self.punishments.find(filter, Some(opts)).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    // bans (including ip bans) still in effect against any of the given players
+    pub async fn get_active_bans_for_players(&self, player_ids: &[String]) -> Vec<Punishment> {
+        if player_ids.is_empty() {
+            return Vec::new();
+        };
+        let cursor = self.punishments.find(doc! {
+            "target.id": { "$in": player_ids },
+            "action.kind": { "$in": [PunishmentKind::Ban.to_string(), PunishmentKind::IpBan.to_string()] },
+            "reversion": null
+        }, None).await.ok();
+        let mut bans = Self::consume_cursor_into_owning_vec_option(cursor).await;
+        bans.retain(|p| p.is_active());
+        bans
+    }
+
+    pub async fn get_shared_ip_list(&self, hasher: &IpHasher) -> Arc<SharedIpList> {
+        let now = get_u64_time_millis();
+        if let Some((loaded_at, list)) = self.shared_ip_list.read().await.as_ref() {
+            if now.saturating_sub(*loaded_at) < SHARED_IP_LIST_LIFETIME_MILLIS {
+                return list.clone();
+            };
+        };
+        let cursor = self.shared_ips.find(doc! {}, None).await.ok();
+        let list = Arc::new(SharedIpList::from_entries(&Self::consume_cursor_into_owning_vec_option(cursor).await, hasher));
+        *self.shared_ip_list.write().await = Some((now, list.clone()));
+        list
+    }
+
+    pub async fn invalidate_shared_ip_list(&self) {
+        *self.shared_ip_list.write().await = None;
+    }
+
+    // punishments never carry shared ips so an ip ban cannot spread to everyone behind them
+    pub async fn without_shared_ips(&self, hasher: &IpHasher, mut ips: Vec<String>) -> Vec<String> {
+        let shared_ips = self.get_shared_ip_list(hasher).await;
+        ips.retain(|ip| !shared_ips.is_shared_key(ip));
+        ips
+    }
+
+    pub async fn get_active_appeal(&self, punishment_id: &str) -> Option<Appeal> {
+        self.appeals.find_one(doc! {
+            "punishmentId": punishment_id, "status": { "$in": AppealStatus::active_values() }
+        }, None).await.ok().flatten()
+    }
+
+    // adds the submission to an open report on the target made since then, in one update so concurrent reports all count
+    pub async fn merge_into_report(&self, target_id: &str, since: u64, submission: &ReportSubmission, message_ids: &[String]) -> Option<Report> {
+        let reported_at = submission.created_at as i64;
+        let submission = mongodb::bson::to_bson(submission).ok()?;
+        let opts = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        self.reports.find_one_and_update(doc! {
+            "target.id": target_id, "status": { "$in": ReportStatus::active_values() }, "lastReportedAt": { "$gte": since as i64 }
+        }, doc! {
+            "$inc": { "count": 1 },
+            "$max": { "lastReportedAt": reported_at },
+            "$push": { "submissions": submission },
+            "$addToSet": { "messageIds": { "$each": message_ids } }
+        }, opts).await.ok().flatten()
+    }
+
+    // writes the editable fields and appends the edit, as long as the punishment has not been reverted
+    pub async fn apply_punishment_edit(&self, punishment: &Punishment, edit: &PunishmentEdit) -> Option<Punishment> {
+        let mut set = doc! {
+            "reason": mongodb::bson::to_bson(&punishment.reason).ok()?,
+            "offence": punishment.offence as i64,
+            "action": mongodb::bson::to_bson(&punishment.action).ok()?,
+            "note": &punishment.note,
+            "silent": punishment.silent
+        };
+        // a lengthened punishment is picked up by the expiry job again
+        if punishment.is_active() {
+            set.insert("expiredAt", mongodb::bson::Bson::Null);
+        };
+        let opts = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        self.punishments.find_one_and_update(
+            doc! { "_id": &punishment.id, "reversion": null },
+            doc! { "$set": set, "$push": { "edits": mongodb::bson::to_bson(edit).ok()? } },
+            opts
+        ).await.ok().flatten()
+    }
+
+    pub async fn revert_punishment(&self, punishment_id: &str, reversion: &PunishmentReversion) -> Option<Punishment> {
+        let reversion = mongodb::bson::to_bson(reversion).ok()?;
+        let opts = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        self.punishments.find_one_and_update(
+            doc! { "_id": punishment_id, "reversion": null }, doc! { "$set": { "reversion": reversion } }, opts
+        ).await.ok().flatten()
+    }
+
+    // only lands while the appeal is still active and no comment has taken the id meanwhile
+    pub async fn push_appeal_comment(&self, appeal_id: &str, comment: &AppealComment) -> Option<Appeal> {
+        let created_at = comment.created_at as i64;
+        let comment_id = comment.id;
+        let comment = mongodb::bson::to_bson(comment).ok()?;
+        let opts = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        self.appeals.find_one_and_update(doc! {
+            "_id": appeal_id, "status": { "$in": AppealStatus::active_values() }, "comments.id": { "$ne": comment_id }
+        }, doc! {
+            "$push": { "comments": comment },
+            "$set": { "updatedAt": created_at }
+        }, opts).await.ok().flatten()
+    }
+
+    // moves the appeal on only if its status is still the one the caller checked the transition against
+    pub async fn transition_appeal(&self, appeal: &Appeal, status: &AppealStatus, reviewer: &SimplePlayer, now: u64) -> Option<Appeal> {
+        let mut update = doc! {
+            "status": status.to_string(),
+            "reviewer": mongodb::bson::to_bson(reviewer).ok()?,
+            "updatedAt": now as i64
+        };
+        if !status.is_active() {
+            update.insert("closedAt", now as i64);
+        };
+        let opts = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        self.appeals.find_one_and_update(
+            doc! { "_id": &appeal.id, "status": appeal.status.to_string() }, doc! { "$set": update }, opts
+        ).await.ok().flatten()
+    }
+
+    pub async fn get_chat_messages_by_ids(&self, ids: &[String]) -> Vec<ChatMessage> {
+        if ids.is_empty() {
+            return Vec::new();
+        };
+        let opts = FindOptions::builder().sort(doc! { "createdAt": 1 }).build();
+        let cursor = self.chat_messages.find(doc! { "_id": { "$in": ids } }, Some(opts)).await.ok();
+        Self::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
+    pub async fn find_clan(&self, text: &str) -> Option<Clan> {
+        self.clans.find_one(doc! {
+            "$or": [{ "_id": text }, { "nameLower": text.to_lowercase() }, { "tagLower": text.to_lowercase() }]
+        }, None).await.unwrap_or(None)
+    }
+
     pub async fn get_players_by_rank(&self, rank: &Rank) -> Vec<SimplePlayer> {
         let cursor = self.players.find(doc! { "rankIds": rank.id.clone() }, None).await.ok();
         let players = Self::consume_cursor_into_owning_vec_option(cursor).await;
@@ -273,10 +830,27 @@ pub async fn connect(db_url: &String, min_pool_size: Option<u32>, max_pool_size:
     let levels = db.collection::<Level>(Level::get_collection_name());
     let deaths = db.collection::<Death>(Death::get_collection_name());
     let ip_identities = db.collection::<IpIdentity>(IpIdentity::get_collection_name());
+    let stat_snapshots = db.collection::<PlayerStatSnapshot>(PlayerStatSnapshot::get_collection_name());
+    let friendships = db.collection::<Friendship>(Friendship::get_collection_name());
+    let clans = db.collection::<Clan>(Clan::get_collection_name());
+    let player_preferences = db.collection::<PlayerPreferences>(PlayerPreferences::get_collection_name());
+    let cosmetic_ownerships = db.collection::<CosmeticOwnership>(CosmeticOwnership::get_collection_name());
+    let currency_transactions = db.collection::<CurrencyTransaction>(CurrencyTransaction::get_collection_name());
+    let currency_balances = db.collection::<CurrencyBalance>(CurrencyBalance::get_collection_name());
+    let chat_messages = db.collection::<ChatMessage>(ChatMessage::get_collection_name());
+    let chat_filter_hits = db.collection::<ChatFilterHit>(ChatFilterHit::get_collection_name());
+    let appeals = db.collection::<Appeal>(Appeal::get_collection_name());
+    let reports = db.collection::<Report>(Report::get_collection_name());
+    let audit_entries = db.collection::<AuditEntry>(AuditEntry::get_collection_name());
+    let shared_ips = db.collection::<SharedIp>(SharedIp::get_collection_name());
+    let gate_policies = db.collection::<GatePolicies>(GatePolicies::get_collection_name());
 
     info!("Connected to database successfully.");
-    Ok(Database { 
-        mongo: db, tags, achievements, players, sessions, 
-        punishments, ranks, matches, levels, deaths, ip_identities
-    })
+    let database = Database { 
+        client, mongo: db, tags, achievements, players, sessions, 
+        punishments, ranks, matches, levels, deaths, ip_identities, stat_snapshots, friendships, clans, player_preferences, cosmetic_ownerships, currency_transactions, currency_balances, chat_messages, chat_filter_hits, appeals, reports, audit_entries, shared_ips, gate_policies,
+        shared_ip_list: RwLock::new(None)
+    };
+    database.ensure_indexes().await;
+    Ok(database)
 }
@@ -0,0 +1,86 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::database::CollectionOwner;
+
+use super::player::SimplePlayer;
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Appeal {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub punishment_id: String,
+    pub player: SimplePlayer,
+    pub statement: String,
+    pub status: AppealStatus,
+    #[serde(default)]
+    pub comments: Vec<AppealComment>,
+    // the staff member who last moved the appeal along
+    #[serde(default)]
+    pub reviewer: Option<SimplePlayer>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    #[serde(default)]
+    pub closed_at: Option<u64>
+}
+
+impl Appeal {
+    pub fn is_active(&self) -> bool {
+        self.status.is_active()
+    }
+}
+
+impl CollectionOwner<Appeal> for Appeal {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<Appeal> {
+        &database.appeals
+    }
+
+    fn get_collection_name() -> &'static str {
+        "appeal"
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Display)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum AppealStatus {
+    Open,
+    UnderReview,
+    Accepted,
+    Denied
+}
+
+impl AppealStatus {
+    pub fn is_active(&self) -> bool {
+        matches!(self, AppealStatus::Open | AppealStatus::UnderReview)
+    }
+
+    // closed appeals are final, a new appeal has to be submitted instead
+    pub fn can_transition_to(&self, next: &AppealStatus) -> bool {
+        matches!(
+            (self, next),
+            (AppealStatus::Open, AppealStatus::UnderReview) 
+                | (AppealStatus::Open | AppealStatus::UnderReview, AppealStatus::Accepted | AppealStatus::Denied)
+        )
+    }
+
+    pub fn active_values() -> Vec<String> {
+        vec![AppealStatus::Open.to_string(), AppealStatus::UnderReview.to_string()]
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AppealComment {
+    pub id: u32,
+    pub author: SimplePlayer,
+    pub content: String,
+    // staff-only comments are hidden from the appealing player
+    #[serde(default)]
+    pub internal: bool,
+    pub created_at: u64
+}
@@ -0,0 +1,106 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use mongodb::bson::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::database::CollectionOwner;
+
+use super::player::SimplePlayer;
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub actor: AuditActor,
+    pub action: AuditAction,
+    pub target: AuditTarget,
+    // only the fields that changed, both are missing for creations and deletions respectively
+    #[serde(default)]
+    pub before: Option<Document>,
+    #[serde(default)]
+    pub after: Option<Document>,
+    pub created_at: u64
+}
+
+impl CollectionOwner<AuditEntry> for AuditEntry {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<AuditEntry> {
+        &database.audit_entries
+    }
+
+    fn get_collection_name() -> &'static str {
+        "audit_entry"
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditActor {
+    // none when the server console or the API itself made the change
+    pub player: Option<SimplePlayer>,
+    pub server_id: Option<String>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditTarget {
+    pub kind: AuditTargetKind,
+    pub id: String,
+    pub name: Option<String>
+}
+
+impl AuditTarget {
+    pub fn new(kind: AuditTargetKind, id: &str, name: &str) -> Self {
+        AuditTarget { kind, id: id.to_owned(), name: Some(name.to_owned()) }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditTargetKind {
+    Rank,
+    Tag,
+    Achievement,
+    Player,
+    Punishment,
+    SharedIp,
+    GatePolicy,
+    Appeal,
+    Report,
+    Server
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditAction {
+    RankCreate,
+    RankUpdate,
+    RankDelete,
+    TagCreate,
+    TagUpdate,
+    TagDelete,
+    AchievementCreate,
+    AchievementDelete,
+    PlayerRankAdd,
+    PlayerRankRemove,
+    PlayerTagAdd,
+    PlayerTagRemove,
+    NoteAdd,
+    NoteDelete,
+    PunishmentIssue,
+    PunishmentEdit,
+    PunishmentRevert,
+    SharedIpAdd,
+    SharedIpRemove,
+    GatePolicyUpdate,
+    CosmeticGrant,
+    CosmeticRevoke,
+    CurrencyAdjust,
+    CurrencyRefund,
+    PlayerErase,
+    AppealStatusUpdate,
+    ReportAssign,
+    ReportStatusUpdate,
+    XpMultiplierUpdate
+}
@@ -0,0 +1,20 @@
+use serde::{Serialize, Deserialize};
+
+use crate::socket::player::player_events::ChatChannel;
+
+// loaded from chat_channels.yml, channels without an entry stay on their own server
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatChannelSettings {
+    pub channel: ChatChannel,
+    #[serde(default)]
+    pub relay: bool,
+    // receiving servers only show relayed messages to players holding this permission
+    pub permission: Option<String>
+}
+
+impl ChatChannelSettings {
+    pub fn defaults() -> Vec<ChatChannelSettings> {
+        vec![ChatChannelSettings { channel: ChatChannel::Staff, relay: true, permission: Some(String::from("mars.staff")) }]
+    }
+}
@@ -0,0 +1,74 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+
+use crate::{database::CollectionOwner, socket::player::player_events::ChatChannel};
+
+use super::player::SimplePlayer;
+
+// loaded from chat_filters.yml
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatFilterRule {
+    pub id: String,
+    pub matcher: ChatFilterMatcher,
+    pub action: ChatFilterAction,
+    // name of the punishment type issued by PUNISH rules
+    #[serde(default)]
+    pub punishment_type: Option<String>,
+    // empty applies the rule to every channel
+    #[serde(default)]
+    pub channels: Vec<ChatChannel>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ChatFilterMatcher {
+    Regex { pattern: String },
+    Wordlist { words: Vec<String> },
+    #[serde(rename_all = "camelCase")]
+    Links { #[serde(default)] allowed_domains: Vec<String> },
+    Advertising,
+    // more than `max_messages` within the window
+    #[serde(rename_all = "camelCase")]
+    Spam { max_messages: u64, window_seconds: u64 },
+    // the same message more than `max_repeats` times in a row within the window
+    #[serde(rename_all = "camelCase")]
+    Repeat { max_repeats: u32, window_seconds: u64 }
+}
+
+// ordered by severity
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ChatFilterAction {
+    Flag,
+    Cancel,
+    Punish
+}
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatFilterHit {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub rule_id: String,
+    pub action: ChatFilterAction,
+    pub player: SimplePlayer,
+    pub server_id: String,
+    pub message: String,
+    pub message_id: Option<String>,
+    #[serde(default)]
+    pub punishment_id: Option<String>,
+    pub created_at: u64
+}
+
+impl CollectionOwner<ChatFilterHit> for ChatFilterHit {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<ChatFilterHit> {
+        &database.chat_filter_hits
+    }
+
+    fn get_collection_name() -> &'static str {
+        "chat_filter_hit"
+    }
+}
@@ -0,0 +1,35 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{database::CollectionOwner, socket::player::player_events::ChatChannel};
+
+use super::player::SimplePlayer;
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatMessage {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub player: SimplePlayer,
+    pub server_id: String,
+    pub match_id: Option<String>,
+    pub channel: ChatChannel,
+    pub message: String,
+    pub created_at: u64,
+    // removed by the TTL index once passed, absent when retention is disabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime>
+}
+
+impl CollectionOwner<ChatMessage> for ChatMessage {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<ChatMessage> {
+        &database.chat_messages
+    }
+
+    fn get_collection_name() -> &'static str {
+        "chat_message"
+    }
+}
@@ -0,0 +1,95 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+
+use crate::database::CollectionOwner;
+
+use super::player::SimplePlayer;
+
+#[derive(Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Clan {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    pub name_lower: String,
+    pub tag: String,
+    pub tag_lower: String,
+    pub members: Vec<ClanMember>,
+    #[serde(default)]
+    pub invites: Vec<ClanInvite>,
+    pub created_at: u64,
+    #[serde(default)]
+    pub last_renamed_at: Option<u64>,
+    #[serde(default)]
+    pub stats: ClanStats
+}
+
+impl Clan {
+    pub fn get_member(&self, player_id: &String) -> Option<&ClanMember> {
+        self.members.iter().find(|member| &member.player.id == player_id)
+    }
+
+    pub fn get_role(&self, player_id: &String) -> Option<ClanRole> {
+        self.get_member(player_id).map(|member| member.role.clone())
+    }
+
+    pub fn owner(&self) -> Option<&ClanMember> {
+        self.members.iter().find(|member| member.role == ClanRole::Owner)
+    }
+
+    pub fn has_invite(&self, player_id: &String) -> bool {
+        self.invites.iter().any(|invite| &invite.player.id == player_id)
+    }
+
+    pub fn id_name(&self) -> String {
+        format!("{}/{}", self.id, self.name)
+    }
+}
+
+impl CollectionOwner<Clan> for Clan {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<Clan> {
+        &database.clans
+    }
+
+    fn get_collection_name() -> &'static str {
+        "clan"
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClanMember {
+    pub player: SimplePlayer,
+    pub role: ClanRole,
+    pub joined_at: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ClanRole {
+    Member,
+    Officer,
+    Owner
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClanInvite {
+    pub player: SimplePlayer,
+    pub invited_by: SimplePlayer,
+    pub created_at: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClanStats {
+    pub kills: u32,
+    pub deaths: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub ties: u32,
+    pub matches_played: u32,
+    pub game_playtime: u64
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+
+use crate::database::CollectionOwner;
+
+use super::{join_sound::JoinSound, player::{Player, SimplePlayer}, rank::Rank};
+
+// loaded from cosmetics.yml, join sounds are merged in as JOIN_SOUND items
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Cosmetic {
+    pub id: String,
+    pub name: String,
+    pub kind: CosmeticKind,
+    #[serde(default)]
+    pub description: Vec<String>,
+    #[serde(default)]
+    pub permission: Option<String>,
+    // empty means every player owns the item
+    #[serde(default)]
+    pub unlocks: Vec<CosmeticUnlock>,
+    #[serde(default)]
+    pub properties: HashMap<String, String>
+}
+
+impl Cosmetic {
+    pub fn is_free(&self) -> bool {
+        self.unlocks.is_empty()
+    }
+
+    pub fn purchase_price(&self) -> Option<u64> {
+        self.unlocks.iter().find_map(|unlock| match unlock {
+            CosmeticUnlock::Purchase { price } => Some(*price),
+            _ => None
+        })
+    }
+
+    // unlocks derived from the player's profile, ownership records are checked separately
+    pub fn get_derived_unlock(&self, player: &Player, level: u32) -> Option<CosmeticSource> {
+        if self.is_free() {
+            return Some(CosmeticSource::Default);
+        };
+        self.unlocks.iter().find_map(|unlock| match unlock {
+            CosmeticUnlock::Rank { rank_id } if player.rank_ids.contains(rank_id) => Some(CosmeticSource::Rank),
+            CosmeticUnlock::Achievement { achievement_id } if player.stats.achievements.contains_key(achievement_id) => Some(CosmeticSource::Achievement),
+            CosmeticUnlock::Level { level: required } if level >= *required => Some(CosmeticSource::Level),
+            _ => None
+        })
+    }
+
+    pub fn is_permitted(&self, ranks: &[Rank]) -> bool {
+        match &self.permission {
+            Some(permission) => ranks.iter().any(|rank| rank.permissions.contains(permission)),
+            None => true
+        }
+    }
+}
+
+impl From<&JoinSound> for Cosmetic {
+    fn from(join_sound: &JoinSound) -> Self {
+        Cosmetic {
+            id: join_sound.id.clone(),
+            name: join_sound.name.clone(),
+            kind: CosmeticKind::JoinSound,
+            description: join_sound.description.clone(),
+            permission: Some(join_sound.permission.clone()),
+            unlocks: Vec::new(),
+            properties: HashMap::from([
+                (String::from("sound"), join_sound.sound.clone()),
+                (String::from("volume"), join_sound.volume.to_string()),
+                (String::from("pitch"), join_sound.pitch.to_string())
+            ])
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CosmeticKind {
+    JoinSound,
+    KillEffect,
+    ProjectileTrail,
+    ChatColor
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "source", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CosmeticUnlock {
+    #[serde(rename_all = "camelCase")]
+    Rank { rank_id: String },
+    #[serde(rename_all = "camelCase")]
+    Achievement { achievement_id: String },
+    Level { level: u32 },
+    Purchase { price: u64 }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CosmeticSource {
+    Default,
+    Rank,
+    Achievement,
+    Level,
+    Grant,
+    Purchase
+}
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CosmeticOwnership {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub player_id: String,
+    pub cosmetic_id: String,
+    pub source: CosmeticSource,
+    #[serde(default)]
+    pub granted_by: Option<SimplePlayer>,
+    pub created_at: u64
+}
+
+impl CosmeticOwnership {
+    pub fn make_id(player_id: &str, cosmetic_id: &str) -> String {
+        format!("{}:{}", player_id, cosmetic_id)
+    }
+}
+
+impl CollectionOwner<CosmeticOwnership> for CosmeticOwnership {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<CosmeticOwnership> {
+        &database.cosmetic_ownerships
+    }
+
+    fn get_collection_name() -> &'static str {
+        "cosmetic_ownership"
+    }
+}
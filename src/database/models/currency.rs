@@ -0,0 +1,98 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{database::CollectionOwner, util::time::get_u64_time_millis};
+
+use super::player::SimplePlayer;
+
+// append-only, a player's balance is the sum of their transaction amounts and is
+// kept alongside as a CurrencyBalance so spends can be checked atomically
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrencyTransaction {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub player_id: String,
+    pub amount: i64,
+    pub kind: CurrencyTransactionKind,
+    pub reason: String,
+    // retried requests carrying the same key resolve to the original transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    // what the transaction concerns, e.g. the purchased cosmetic or the refunded transaction
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub actor: Option<SimplePlayer>,
+    pub created_at: u64
+}
+
+impl CurrencyTransaction {
+    pub fn new(player_id: &str, amount: i64, kind: CurrencyTransactionKind, reason: &str) -> Self {
+        CurrencyTransaction {
+            id: Uuid::new_v4().to_string(),
+            player_id: player_id.to_owned(),
+            amount,
+            kind,
+            reason: reason.to_owned(),
+            idempotency_key: None,
+            reference: None,
+            actor: None,
+            created_at: get_u64_time_millis()
+        }
+    }
+}
+
+impl CollectionOwner<CurrencyTransaction> for CurrencyTransaction {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<CurrencyTransaction> {
+        &database.currency_transactions
+    }
+
+    fn get_collection_name() -> &'static str {
+        "currency_transaction"
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrencyBalance {
+    #[id]
+    #[serde(rename = "_id")]
+    pub player_id: String,
+    pub balance: i64
+}
+
+impl CollectionOwner<CurrencyBalance> for CurrencyBalance {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<CurrencyBalance> {
+        &database.currency_balances
+    }
+
+    fn get_collection_name() -> &'static str {
+        "currency_balance"
+    }
+}
+
+pub struct RecordedCurrencyTransaction {
+    pub transaction: CurrencyTransaction,
+    // false when the idempotency key resolved to an earlier transaction
+    pub created: bool,
+    pub balance: i64
+}
+
+#[derive(Debug)]
+pub enum CurrencyTransactionError {
+    InsufficientFunds,
+    Failed
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CurrencyTransactionKind {
+    Earn,
+    Spend,
+    Adjust,
+    Refund
+}
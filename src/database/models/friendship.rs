@@ -0,0 +1,55 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+
+use crate::database::CollectionOwner;
+
+use super::player::SimplePlayer;
+
+#[derive(Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Friendship {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub requester: SimplePlayer,
+    pub recipient: SimplePlayer,
+    pub status: FriendshipStatus,
+    pub created_at: u64,
+    #[serde(default)]
+    pub accepted_at: Option<u64>,
+    // both ids in sorted order, unique so a pair can only ever have one friendship
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pair_key: Option<String>
+}
+
+impl Friendship {
+    pub fn pair_key(player_id: &str, other_id: &str) -> String {
+        if player_id < other_id { format!("{}:{}", player_id, other_id) } else { format!("{}:{}", other_id, player_id) }
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        self.status == FriendshipStatus::Accepted
+    }
+
+    pub fn other(&self, player_id: &String) -> &SimplePlayer {
+        if &self.requester.id == player_id { &self.recipient } else { &self.requester }
+    }
+}
+
+impl CollectionOwner<Friendship> for Friendship {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<Friendship> {
+        &database.friendships
+    }
+
+    fn get_collection_name() -> &'static str {
+        "friendship"
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FriendshipStatus {
+    Pending,
+    Accepted
+}
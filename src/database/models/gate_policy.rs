@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::CollectionOwner;
+
+pub const GATE_POLICIES_ID : &str = "prelogin";
+
+// admission policies evaluated at prelogin, a single document so every instance sees toggles at once
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GatePolicies {
+    pub maintenance: MaintenancePolicy,
+    pub allowlist: AllowlistPolicy,
+    pub reserved_slots: ReservedSlotsPolicy,
+    pub ip_limit: IpLimitPolicy
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MaintenancePolicy {
+    pub enabled: bool,
+    // holders of this permission through any of their ranks may still join
+    pub bypass_permission: Option<String>,
+    pub bypass_rank_ids: Vec<String>,
+    pub kick_message: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AllowlistPolicy {
+    pub enabled: bool,
+    pub player_ids: Vec<String>,
+    pub kick_message: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReservedSlotsPolicy {
+    pub enabled: bool,
+    // players online across the network
+    pub capacity: u32,
+    // the last slots below capacity, only ranked players may take them
+    pub reserved: u32,
+    // any rank counts when empty
+    pub rank_ids: Vec<String>,
+    pub kick_message: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IpLimitPolicy {
+    pub enabled: bool,
+    // distinct accounts online at once from one ip, shared ips are exempt
+    pub max_accounts: u32,
+    pub kick_message: Option<String>
+}
+
+impl CollectionOwner<GatePolicies> for GatePolicies {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<GatePolicies> {
+        &database.gate_policies
+    }
+
+    fn get_collection_name() -> &'static str {
+        "gate_policy"
+    }
+}
@@ -2,32 +2,143 @@ use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use mars_api_rs_derive::IdentifiableDocument;
 use mars_api_rs_macro::IdentifiableDocument;
-use mongodb::Collection;
+use mongodb::{bson::doc, options::UpdateOptions, results::UpdateResult, Collection};
 use rocket::serde::{Deserialize, Serialize};
 use crate::database::{CollectionOwner, Database};
 use crate::database::models::player::Player;
+use crate::util::time::get_u64_time_millis;
 
 #[derive(Deserialize, Serialize, IdentifiableDocument, Clone)]
 pub struct IpIdentity {
     #[id]
     #[serde(rename = "_id")]
     pub ip: String,
-    pub players: Vec<String>
+    pub players: Vec<String>,
+    // identities created before sightings were tracked have none
+    #[serde(default)]
+    pub sightings: Vec<IpSighting>
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IpSighting {
+    pub player_id: String,
+    pub first_seen_at: u64,
+    pub last_seen_at: u64
+}
+
+// limits on how far an alt lookup may spread
+pub struct AltGraphBounds {
+    pub depth: u32,
+    // ips shared by more accounts than this (e.g. schools, proxies) are not followed past the first hop
+    pub max_ip_fanout: usize,
+    pub max_accounts: usize
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AltGraph {
+    pub accounts: Vec<AltAccount>,
+    pub skipped_ips: Vec<String>,
+    // set when a bound stopped the traversal early
+    pub truncated: bool
+}
+
+impl AltGraph {
+    // an ip shared by only two accounts is strong evidence, one shared by many is weak
+    const LINK_WEIGHT : f64 = 0.6;
+    // each hop away from the origin halves the confidence
+    const DEPTH_DECAY : f64 = 0.5;
+
+    pub fn score_links(links: &[AltLink], depth: u32) -> f64 {
+        let unlinked = links.iter().fold(1.0, |acc, link| {
+            let others = link.shared_by.saturating_sub(1).max(1) as f64;
+            acc * (1.0 - Self::LINK_WEIGHT / others)
+        });
+        let score = (1.0 - unlinked) * Self::DEPTH_DECAY.powi(depth as i32 - 1);
+        (score * 100.0).round() / 100.0
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AltAccount {
+    pub player: Player,
+    pub depth: u32,
+    pub links: Vec<AltLink>,
+    pub confidence: f64
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AltLink {
+    pub ip: String,
+    // the account one step closer to the origin that shares this ip
+    pub via: String,
+    pub shared_by: usize,
+    pub first_seen_at: Option<u64>,
+    pub last_seen_at: Option<u64>
 }
 
 impl IpIdentity {
-    pub async fn add_player_ip(database: &Database, ip: &String, player: &String) {
+    // max_players caps how many accounts a shared ip keeps collecting; both steps are single
+    // atomic updates so concurrent logins from one ip do not overwrite each other
+    pub async fn add_player_ip(database: &Database, ip: &String, player: &String, max_players: Option<usize>) {
         let collection = &database.ip_identities;
-        let ip_identity = match Self::get_ip_identity_by_ip(collection, ip).await {
-            Some(mut ip_identity) => {
-                if !ip_identity.players.contains(&player) {
-                    ip_identity.players.push(player.clone());
-                }
-                ip_identity
-            },
-            None => IpIdentity { ip: ip.clone(), players: vec![player.clone()] }
+        let now = get_u64_time_millis() as i64;
+        let seen = collection.update_one(
+            doc! { "_id": ip, "sightings.playerId": player },
+            doc! { "$set": { "sightings.$.lastSeenAt": now }, "$addToSet": { "players": player } },
+            None
+        ).await;
+        if matches!(seen, Ok(UpdateResult { matched_count: 1.., .. })) {
+            return;
+        };
+
+        let mut filter = doc! { "_id": ip, "sightings.playerId": { "$ne": player } };
+        if let Some(max_players) = max_players {
+            filter.insert("$or", vec![
+                doc! { "players": player },
+                doc! { format!("players.{}", max_players.saturating_sub(1)): { "$exists": false } }
+            ]);
+        };
+        // a full identity fails the upsert on its _id, which leaves it untouched
+        let opts = UpdateOptions::builder().upsert(true).build();
+        let _ = collection.update_one(filter, doc! {
+            "$addToSet": { "players": player },
+            "$push": { "sightings": { "playerId": player, "firstSeenAt": now, "lastSeenAt": now } }
+        }, opts).await;
+    }
+
+    // folds the identity stored under an older hash into the one under the current hash
+    pub async fn rekey(database: &Database, old_ip: &String, new_ip: &String) {
+        let collection = &database.ip_identities;
+        let old_identity = match Self::get_ip_identity_by_ip(collection, old_ip).await {
+            Some(old_identity) => old_identity,
+            None => return
         };
+        let mut ip_identity = Self::get_ip_identity_by_ip(collection, new_ip).await
+            .unwrap_or(IpIdentity { ip: new_ip.clone(), players: Vec::new(), sightings: Vec::new() });
+        for player in old_identity.players.into_iter() {
+            if !ip_identity.players.contains(&player) {
+                ip_identity.players.push(player);
+            };
+        }
+        for old_sighting in old_identity.sightings.into_iter() {
+            match ip_identity.sightings.iter_mut().find(|sighting| sighting.player_id == old_sighting.player_id) {
+                Some(sighting) => {
+                    sighting.first_seen_at = sighting.first_seen_at.min(old_sighting.first_seen_at);
+                    sighting.last_seen_at = sighting.last_seen_at.max(old_sighting.last_seen_at);
+                },
+                None => ip_identity.sightings.push(old_sighting)
+            };
+        }
         database.save(&ip_identity).await;
+        let _ = collection.delete_one(doc! { "_id": old_ip }, None).await;
+    }
+
+    pub fn get_sighting(&self, player_id: &str) -> Option<&IpSighting> {
+        self.sightings.iter().find(|sighting| sighting.player_id == player_id)
     }
 
     pub async fn find_players_for_ip(database: &Database, ip: &String) -> Vec<Player> {
@@ -49,6 +160,11 @@ impl IpIdentity {
         }
     }
 
+    pub async fn get_ip_identities_by_ips(database: &Database, ips: &[String]) -> Vec<IpIdentity> {
+        let cursor = database.ip_identities.find(doc! { "_id": { "$in": ips } }, None).await.ok();
+        Database::consume_cursor_into_owning_vec_option(cursor).await
+    }
+
     pub async fn get_ip_identity_by_ip(collection: &Collection<IpIdentity>, ip: &String) -> Option<IpIdentity> {
         Database::find_by_id(collection, ip.as_str()).await
     }
@@ -79,7 +79,7 @@ pub struct LevelContributor {
     contribution: Option<String>
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, strum_macros::EnumProperty, Hash, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, strum_macros::EnumProperty, strum_macros::EnumString, Hash, PartialEq, Eq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 pub enum LevelGamemode {
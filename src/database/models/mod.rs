@@ -12,4 +12,18 @@ pub mod death;
 pub mod join_sound;
 pub mod server;
 pub mod achievement;
-pub mod ip_identity;
\ No newline at end of file
+pub mod ip_identity;
+pub mod stat_snapshot;
+pub mod friendship;
+pub mod clan;
+pub mod preference;
+pub mod cosmetic;
+pub mod currency;
+pub mod chat_channel;
+pub mod chat_message;
+pub mod chat_filter;
+pub mod appeal;
+pub mod report;
+pub mod audit_entry;
+pub mod shared_ip;
+pub mod gate_policy;
@@ -7,10 +7,10 @@ use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use num_traits::ToPrimitive;
 
-use crate::{database::CollectionOwner, socket::{leaderboard::ScoreType, player::{player_xp_listener::PlayerXPListener, player_events::PlayerXPGainData}, server::server_context::ServerContext, event_type::EventType}};
+use crate::{database::CollectionOwner, util::{string::name_grams, time::get_u64_time_millis}, socket::{leaderboard::ScoreType, player::{player_xp_listener::PlayerXPListener, player_events::PlayerXPGainData}, server::server_context::ServerContext, event_type::EventType, update::player_update_listener::{PlayerUpdate, PlayerUpdateData, PlayerUpdateReason}}};
 use crate::database::models::server::{ServerEvents, XPMultiplier};
 
-use super::{punishment::StaffNote, level::LevelGamemode, r#match::Match};
+use super::{punishment::StaffNote, level::LevelGamemode, r#match::Match, cosmetic::CosmeticKind, currency::{CurrencyTransaction, CurrencyTransactionKind}};
 
 #[derive(Debug, Serialize, Deserialize, Clone, IdentifiableDocument)]
 #[serde(rename_all = "camelCase")]
@@ -30,7 +30,18 @@ pub struct Player {
     pub active_tag_id: Option<String>,
     pub stats: PlayerStats,
     pub gamemode_stats: HashMap<LevelGamemode, GamemodeStats>,
-    pub active_join_sound_id: Option<String>
+    pub active_join_sound_id: Option<String>,
+    #[serde(default)]
+    pub past_names: Vec<PastPlayerName>,
+    #[serde(default)]
+    pub clan_id: Option<String>,
+    #[serde(default)]
+    pub active_cosmetics: HashMap<CosmeticKind, String>,
+    #[serde(default)]
+    pub ignored_player_ids: Vec<String>,
+    // see util::string::name_grams, kept in step with the name
+    #[serde(default)]
+    pub name_grams: Vec<String>
 }
 
 impl Player {
@@ -42,11 +53,29 @@ impl Player {
         format!("{}/{}", self.id, self.name)
     }
 
+    pub fn record_name_change(&mut self, new_name: &String) {
+        if &self.name == new_name {
+            return;
+        };
+        let old_name_lower = self.name_lower.clone();
+        self.past_names.retain(|past| past.name_lower != old_name_lower);
+        self.past_names.push(PastPlayerName {
+            name: self.name.clone(),
+            name_lower: old_name_lower,
+            changed_at: get_u64_time_millis()
+        });
+        self.name = new_name.clone();
+        self.name_lower = new_name.to_lowercase();
+        self.name_grams = name_grams(&self.name_lower);
+    }
+
     pub fn sanitized_copy(&self) -> Player {
         let mut clone = self.clone();
         clone.ips = Vec::new();
         clone.notes = Vec::new();
         clone.last_session_id = None;
+        clone.ignored_player_ids = Vec::new();
+        clone.name_grams = Vec::new();
         clone
     }
 
@@ -92,6 +121,25 @@ impl Player {
             }).await;
 
         server_context.api_state.leaderboards.xp.increment(&self.id_name(), Some(target_xp_increment)).await;
+        self.earn_currency(server_context, reason).await;
+    }
+
+    async fn earn_currency(&self, server_context: &mut ServerContext, reason: &String) {
+        let rate = server_context.api_state.config.data.currency_rates.get(reason).copied().unwrap_or(0);
+        if rate == 0 {
+            return;
+        };
+        let database = &server_context.api_state.database;
+        let earned = CurrencyTransaction::new(&self.id, rate as i64, CurrencyTransactionKind::Earn, reason);
+        let balance = match database.record_currency_transaction(earned).await {
+            Ok(recorded) => recorded.balance,
+            Err(_) => return
+        };
+        server_context.call(&EventType::PlayerUpdate, PlayerUpdate {
+            updated: self.clone(),
+            data: PlayerUpdateData::CurrencyUpdateData { balance, change: rate as i64 },
+            reason: PlayerUpdateReason::Currency
+        }).await;
     }
 }
 
@@ -361,6 +409,33 @@ pub struct FirstBloodRecord {
     pub time: u64
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PastPlayerName {
+    pub name: String,
+    pub name_lower: String,
+    pub changed_at: u64
+}
+
+// slim view of a player document used by name search
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerNameProjection {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    pub name_lower: String,
+    pub last_joined_at: f64,
+    #[serde(default)]
+    pub past_names: Vec<PastPlayerName>
+}
+
+impl PlayerNameProjection {
+    pub fn to_simple(&self) -> SimplePlayer {
+        SimplePlayer { name: self.name.clone(), id: self.id.clone() }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialEq, Eq)]
 pub struct SimplePlayer {
     pub name: String,
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+
+use crate::database::CollectionOwner;
+
+// loaded from preferences.yml
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreferenceDefinition {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub kind: PreferenceKind,
+    pub default: PreferenceValue,
+    #[serde(default)]
+    pub description: Option<String>,
+    // allowed values for ENUM preferences
+    #[serde(default)]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub min: Option<i64>,
+    #[serde(default)]
+    pub max: Option<i64>
+}
+
+impl PreferenceDefinition {
+    pub fn accepts(&self, value: &PreferenceValue) -> bool {
+        match (&self.kind, value) {
+            (PreferenceKind::Boolean, PreferenceValue::Boolean(_)) => true,
+            (PreferenceKind::Integer, PreferenceValue::Integer(number)) => {
+                self.min.map(|min| *number >= min).unwrap_or(true) && self.max.map(|max| *number <= max).unwrap_or(true)
+            },
+            (PreferenceKind::String, PreferenceValue::Text(text)) => {
+                self.max.map(|max| text.chars().count() as i64 <= max).unwrap_or(true)
+            },
+            (PreferenceKind::Enum, PreferenceValue::Text(text)) => self.options.contains(text),
+            _ => false
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PreferenceKind {
+    Boolean,
+    Integer,
+    String,
+    Enum
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PreferenceValue {
+    Boolean(bool),
+    Integer(i64),
+    Text(String)
+}
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPreferences {
+    #[id]
+    #[serde(rename = "_id")]
+    pub player_id: String,
+    pub values: HashMap<String, PreferenceValue>,
+    pub updated_at: u64
+}
+
+impl PlayerPreferences {
+    // defaults overlaid with stored values; keys no longer defined or no longer valid fall back to the default
+    pub fn resolve(definitions: &[PreferenceDefinition], stored: Option<&PlayerPreferences>) -> HashMap<String, PreferenceValue> {
+        definitions.iter().map(|definition| {
+            let value = stored
+                .and_then(|stored| stored.values.get(&definition.key))
+                .filter(|value| definition.accepts(value))
+                .unwrap_or(&definition.default)
+                .clone();
+            (definition.key.clone(), value)
+        }).collect()
+    }
+}
+
+impl CollectionOwner<PlayerPreferences> for PlayerPreferences {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<PlayerPreferences> {
+        &database.player_preferences
+    }
+
+    fn get_collection_name() -> &'static str {
+        "player_preferences"
+    }
+}
@@ -26,7 +26,15 @@ pub struct Punishment {
     #[serde(default)]
     pub reversion: Option<PunishmentReversion>,
     #[serde(default)]
-    pub server_id: Option<String>
+    pub server_id: Option<String>,
+    // chat messages the punishment was issued over
+    #[serde(default)]
+    pub message_ids: Vec<String>,
+    #[serde(default)]
+    pub edits: Vec<PunishmentEdit>,
+    // set by the expiry job once a timed punishment has run out
+    #[serde(default)]
+    pub expired_at: Option<u64>
 }
 
 impl Punishment {
@@ -69,15 +77,25 @@ pub struct PunishmentType {
     #[serde(default)]
     pub tip: Option<String>,
     #[serde(default = "default_required_permission")]
-    pub required_permission: String
+    pub required_permission: String,
+    // overrides punishments.offence-decay-days for this type
+    #[serde(default)]
+    pub offence_decay_days: Option<u64>
 }
 
+impl PunishmentType {
+    // offences past the last configured action repeat it
+    pub fn get_action_for_offence(&self, offence: u32) -> Option<&PunishmentAction> {
+        let index = (offence.max(1) as usize - 1).min(self.actions.len().checked_sub(1)?);
+        self.actions.get(index)
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PunishmentAction {
     pub kind: PunishmentKind,
     #[serde(default = "default_punishment_length")]
-    length: i64
+    pub length: i64
 }
 
 impl PunishmentAction {
@@ -113,6 +131,48 @@ pub struct PunishmentReason {
     short: String
 }
 
+impl From<&PunishmentType> for PunishmentReason {
+    fn from(punishment_type: &PunishmentType) -> Self {
+        PunishmentReason { name: punishment_type.name.clone(), message: punishment_type.message.clone(), short: punishment_type.short.clone() }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PunishmentEdit {
+    pub editor: SimplePlayer,
+    pub edited_at: u64,
+    pub justification: String,
+    // the editable fields as they were before this edit
+    pub previous: PunishmentEditableValues
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PunishmentEditableValues {
+    pub reason: PunishmentReason,
+    pub offence: u32,
+    // changes along with the reason, edits recorded before that have none
+    #[serde(default)]
+    pub kind: Option<PunishmentKind>,
+    pub length: i64,
+    pub note: Option<String>,
+    pub silent: bool
+}
+
+impl From<&Punishment> for PunishmentEditableValues {
+    fn from(punishment: &Punishment) -> Self {
+        PunishmentEditableValues {
+            reason: punishment.reason.clone(),
+            offence: punishment.offence,
+            kind: Some(punishment.action.kind.clone()),
+            length: punishment.action.length,
+            note: punishment.note.clone(),
+            silent: punishment.silent
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PunishmentReversion {
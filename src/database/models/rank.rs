@@ -5,6 +5,8 @@ use serde::{Serialize, Deserialize};
 
 use crate::database::{CollectionOwner, Database};
 
+use super::player::Player;
+
 #[derive(Deserialize, Serialize, Debug, IdentifiableDocument)]
 #[serde(rename_all = "camelCase")]
 pub struct Rank {
@@ -44,4 +46,12 @@ impl Rank {
         };
         Database::consume_cursor_into_owning_vec(cursor).await
     }
+
+    // ranks the player holds, including those applied to everyone on join
+    pub async fn find_for_player(database: &Database, player: &Player) -> Vec<Rank> {
+        let cursor = Rank::get_collection(database).find(doc! {
+            "$or": [{ "_id": { "$in": &player.rank_ids } }, { "applyOnJoin": true }]
+        }, None).await.ok();
+        Database::consume_cursor_into_owning_vec_option(cursor).await
+    }
 }
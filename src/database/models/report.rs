@@ -0,0 +1,78 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::database::CollectionOwner;
+
+use super::player::SimplePlayer;
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub target: SimplePlayer,
+    // the first reporter, later duplicates are kept in `submissions`
+    pub reporter: SimplePlayer,
+    pub reason: String,
+    pub server_id: String,
+    #[serde(default)]
+    pub match_id: Option<String>,
+    pub online_staff: Vec<SimplePlayer>,
+    #[serde(default)]
+    pub message_ids: Vec<String>,
+    pub status: ReportStatus,
+    #[serde(default)]
+    pub assignee: Option<SimplePlayer>,
+    #[serde(default)]
+    pub punishment_id: Option<String>,
+    // how many reports were merged into this one, including the first
+    pub count: u32,
+    #[serde(default)]
+    pub submissions: Vec<ReportSubmission>,
+    pub created_at: u64,
+    pub last_reported_at: u64,
+    #[serde(default)]
+    pub closed_at: Option<u64>
+}
+
+impl CollectionOwner<Report> for Report {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<Report> {
+        &database.reports
+    }
+
+    fn get_collection_name() -> &'static str {
+        "report"
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Display)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReportStatus {
+    Open,
+    Claimed,
+    Resolved,
+    Dismissed
+}
+
+impl ReportStatus {
+    pub fn is_active(&self) -> bool {
+        matches!(self, ReportStatus::Open | ReportStatus::Claimed)
+    }
+
+    pub fn active_values() -> Vec<String> {
+        vec![ReportStatus::Open.to_string(), ReportStatus::Claimed.to_string()]
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportSubmission {
+    pub reporter: SimplePlayer,
+    pub reason: String,
+    pub server_id: String,
+    pub created_at: u64
+}
@@ -2,13 +2,13 @@ use serde::{Serialize, Deserialize};
 
 use super::player::SimplePlayer;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerEvents {
     pub xp_multiplier: Option<XPMultiplier>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct XPMultiplier {
     pub value: f32,
@@ -0,0 +1,120 @@
+use std::{collections::HashSet, net::IpAddr};
+
+use ipnet::IpNet;
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::{database::CollectionOwner, util::ip_hash::IpHasher};
+
+use super::player::SimplePlayer;
+
+// an ip or range used by many unrelated players (schools, VPN exits, cafés) that
+// should not link accounts together
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedIp {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub value: String,
+    pub kind: SharedIpKind,
+    // what ip identities are stored under, None for ranges and hashes (the value already is one)
+    #[serde(default)]
+    pub key: Option<String>,
+    pub reason: String,
+    pub added_by: SimplePlayer,
+    pub created_at: u64
+}
+
+impl CollectionOwner<SharedIp> for SharedIp {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<SharedIp> {
+        &database.shared_ips
+    }
+
+    fn get_collection_name() -> &'static str {
+        "shared_ip"
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Display)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum SharedIpKind {
+    Ip,
+    Cidr,
+    // an already hashed ip (as stored), for when hashing is on and the raw address is unknown
+    Hash
+}
+
+impl SharedIpKind {
+    pub fn detect(value: &str) -> Option<SharedIpKind> {
+        if value.parse::<IpAddr>().is_ok() {
+            Some(SharedIpKind::Ip)
+        } else if value.parse::<IpNet>().is_ok() {
+            Some(SharedIpKind::Cidr)
+        } else if Self::is_hash(value) {
+            Some(SharedIpKind::Hash)
+        } else {
+            None
+        }
+    }
+
+    // unkeyed SHA-256 or a versioned HMAC, see IpHasher
+    fn is_hash(value: &str) -> bool {
+        let digest = match IpHasher::parse_version(value) {
+            Some(_) => value.split_once(':').map(|(_, digest)| digest).unwrap_or(value),
+            None => value
+        };
+        digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())
+    }
+}
+
+// ranges can only be matched against raw ips, so with hashing on they take
+// effect at join time but not when walking stored ip identities
+#[derive(Default)]
+pub struct SharedIpList {
+    keys: HashSet<String>,
+    ranges: Vec<IpNet>
+}
+
+impl SharedIpList {
+    // keys stored under an older hash key are carried forward to the current one
+    pub fn from_entries(entries: &[SharedIp], hasher: &IpHasher) -> Self {
+        let mut list = SharedIpList::default();
+        for entry in entries.iter() {
+            match entry.kind {
+                SharedIpKind::Cidr => {
+                    if let Ok(range) = entry.value.parse::<IpNet>() {
+                        list.ranges.push(range);
+                    };
+                },
+                SharedIpKind::Ip | SharedIpKind::Hash => {
+                    list.keys.insert(entry.value.clone());
+                }
+            };
+            if let Some(key) = &entry.key {
+                list.keys.insert(key.clone());
+            };
+        }
+        let rekeyed : Vec<String> = list.keys.iter().filter_map(|key| hasher.rekey(key)).collect();
+        list.keys.extend(rekeyed);
+        list
+    }
+
+    // for ips as stored on players, punishments and ip identities
+    pub fn is_shared_key(&self, key: &str) -> bool {
+        self.keys.contains(key) || key.parse::<IpAddr>().map(|ip| self.in_range(&ip)).unwrap_or(false)
+    }
+
+    // keys are every value the ip may be stored under
+    pub fn is_shared(&self, raw_ip: &str, keys: &[String]) -> bool {
+        keys.iter().any(|key| self.is_shared_key(key)) 
+            || raw_ip.parse::<IpAddr>().map(|ip| self.keys.contains(raw_ip) || self.in_range(&ip)).unwrap_or(false)
+    }
+
+    fn in_range(&self, ip: &IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Deserialize, Serialize};
+
+use crate::database::CollectionOwner;
+
+use super::{level::LevelGamemode, player::PlayerStats};
+
+// one document per player per (UTC) day the player was online
+#[derive(Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStatSnapshot {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub player_id: String,
+    pub date: u64,
+    // first snapshot of a player has nothing to diff against, so its delta is empty
+    #[serde(default)]
+    pub baseline: bool,
+    pub totals: StatSnapshotValues,
+    pub delta: StatSnapshotValues,
+    #[serde(default)]
+    pub gamemode_totals: HashMap<LevelGamemode, StatSnapshotValues>,
+    #[serde(default)]
+    pub gamemode_deltas: HashMap<LevelGamemode, StatSnapshotValues>
+}
+
+impl PlayerStatSnapshot {
+    pub fn create_id(player_id: &String, date: u64) -> String {
+        format!("{}:{}", player_id, date)
+    }
+}
+
+impl CollectionOwner<PlayerStatSnapshot> for PlayerStatSnapshot {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<PlayerStatSnapshot> {
+        &database.stat_snapshots
+    }
+
+    fn get_collection_name() -> &'static str {
+        "player_stat_snapshot"
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StatSnapshotValues {
+    pub kills: u32,
+    pub deaths: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub xp: u32,
+    pub server_playtime: i64,
+    pub game_playtime: u64
+}
+
+impl StatSnapshotValues {
+    pub fn of(stats: &PlayerStats) -> Self {
+        Self {
+            kills: stats.kills,
+            deaths: stats.deaths,
+            wins: stats.wins,
+            losses: stats.losses,
+            xp: stats.xp,
+            server_playtime: stats.server_playtime,
+            game_playtime: stats.game_playtime
+        }
+    }
+
+    // stats only grow, but saturate anyway in case a player was reset between snapshots
+    pub fn since(&self, previous: &StatSnapshotValues) -> Self {
+        Self {
+            kills: self.kills.saturating_sub(previous.kills),
+            deaths: self.deaths.saturating_sub(previous.deaths),
+            wins: self.wins.saturating_sub(previous.wins),
+            losses: self.losses.saturating_sub(previous.losses),
+            xp: self.xp.saturating_sub(previous.xp),
+            server_playtime: i64::max(0, self.server_playtime - previous.server_playtime),
+            game_playtime: self.game_playtime.saturating_sub(previous.game_playtime)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
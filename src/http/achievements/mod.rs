@@ -6,6 +6,8 @@ use rocket::{State, Rocket, Build, serde::json::Json};
 use uuid::Uuid;
 
 use crate::database::Database;
+use crate::database::models::audit_entry::{AuditAction, AuditTarget, AuditTargetKind};
+use crate::util::audit::AuditContext;
 use crate::util::error::ApiErrorResponder;
 use crate::util::r#macro::unwrap_helper;
 use crate::util::responder::JsonResponder;
@@ -21,7 +23,8 @@ mod payload;
 async fn add_achievement(
     state: &State<MarsAPIState>,
     achievement_create_req: Json<AchievementCreateRequest>,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<Achievement>, ApiErrorResponder> {
     match state.database.find_by_id_or_name::<Achievement>(&achievement_create_req.name).await {
         Some(_tag) => return Err(ApiErrorResponder::achievement_conflict()),
@@ -43,6 +46,7 @@ async fn add_achievement(
         first_completion: None
     };
     state.database.save::<Achievement>(&new_achievement).await;
+    audit.record_created(state, AuditAction::AchievementCreate, AuditTarget::new(AuditTargetKind::Achievement, &new_achievement.id, &new_achievement.name), &new_achievement).await;
     return Ok(JsonResponder::from(new_achievement, Status::Ok));
 }
 
@@ -50,8 +54,10 @@ async fn add_achievement(
 async fn delete_achievement(
     state: &State<MarsAPIState>,
     achievement_id: &str,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<(), ApiErrorResponder> {
+    let achievement = unwrap_helper::return_default!(Database::find_by_id(&state.database.achievements, achievement_id).await, Err(ApiErrorResponder::achievement_missing()));
     match state.database.delete_by_id::<Achievement>(achievement_id).await {
         Some(DeleteResult { deleted_count: 0, .. }) | None => {
             return Err(ApiErrorResponder::achievement_missing());
@@ -75,6 +81,7 @@ async fn delete_achievement(
         };
         join_all(remove_from_cache_futures).await;
     }
+    audit.record_deleted(state, AuditAction::AchievementDelete, AuditTarget::new(AuditTargetKind::Achievement, &achievement.id, &achievement.name), &achievement).await;
     Ok(())
 }
 
@@ -0,0 +1,185 @@
+mod payload;
+
+use mongodb::{bson::{doc, Document}, options::FindOptions};
+use rocket::{http::Status, serde::json::Json, Build, Rocket, State};
+use uuid::Uuid;
+
+use crate::{database::{models::{appeal::{Appeal, AppealComment, AppealStatus}, audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, punishment::Punishment}, Database}, util::{audit::AuditContext, auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, responder::JsonResponder, time::get_u64_time_millis}, MarsAPIState};
+
+use self::payload::{AppealCommentRequest, AppealCreateRequest, AppealStatusRequest};
+
+use super::punishment::revert_punishment;
+
+const STATEMENT_MAX_LENGTH : usize = 2000;
+const COMMENT_ATTEMPTS : usize = 3;
+
+#[post("/", format = "json", data = "<create_req>")]
+pub async fn create_appeal(
+    state: &State<MarsAPIState>,
+    create_req: Json<AppealCreateRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Appeal>, ApiErrorResponder> {
+    let data = create_req.0;
+    let statement = data.statement.trim();
+    if statement.is_empty() || statement.len() > STATEMENT_MAX_LENGTH {
+        return Err(ApiErrorResponder::validation_error_with_message("The statement must not be empty or too long"));
+    };
+    let punishment = unwrap_helper::return_default!(Database::find_by_id(&state.database.punishments, &data.punishment_id).await, Err(ApiErrorResponder::missing_punishment()));
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, &data.player_id).await, Err(ApiErrorResponder::missing_player()));
+    if punishment.target.id != player.id {
+        return Err(ApiErrorResponder::create_anonymous_error(Status::Forbidden, "Only the punished player can appeal"));
+    };
+    if punishment.reversion.is_some() {
+        return Err(ApiErrorResponder::validation_error_with_message("The punishment has already been reverted"));
+    };
+    if state.database.get_active_appeal(&punishment.id).await.is_some() {
+        return Err(ApiErrorResponder::appeal_conflict());
+    };
+
+    let now = get_u64_time_millis();
+    let appeal = Appeal {
+        id: Uuid::new_v4().to_string(),
+        punishment_id: punishment.id.clone(),
+        player: player.to_simple(),
+        statement: statement.to_owned(),
+        status: AppealStatus::Open,
+        comments: Vec::new(),
+        reviewer: None,
+        created_at: now,
+        updated_at: now,
+        closed_at: None
+    };
+    // the unique index catches an appeal created concurrently
+    if state.database.appeals.insert_one(&appeal, None).await.is_err() {
+        return Err(ApiErrorResponder::appeal_conflict());
+    };
+    notify_appeal_webhook(state, &appeal, punishment);
+    Ok(JsonResponder::created(appeal))
+}
+
+#[get("/?<status>&<player>&<before>&<limit>")]
+pub async fn get_appeals(
+    state: &State<MarsAPIState>,
+    status: Option<&str>,
+    player: Option<&str>,
+    before: Option<u64>,
+    limit: Option<i64>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Vec<Appeal>>, ApiErrorResponder> {
+    let mut filter = Document::new();
+    if let Some(status) = status {
+        filter.insert("status", status.to_uppercase());
+    };
+    if let Some(player) = player {
+        let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player).await, Err(ApiErrorResponder::missing_player()));
+        filter.insert("player.id", player.id);
+    };
+    if let Some(before) = before {
+        filter.insert("createdAt", doc! { "$lt": before as i64 });
+    };
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+    let opts = FindOptions::builder().sort(doc! { "createdAt": -1 }).limit(limit).build();
+    let cursor = state.database.appeals.find(filter, Some(opts)).await.ok();
+    Ok(JsonResponder::ok(Database::consume_cursor_into_owning_vec_option(cursor).await))
+}
+
+#[get("/punishment/<punishment_id>")]
+pub async fn get_punishment_appeals(
+    state: &State<MarsAPIState>,
+    punishment_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Vec<Appeal>>, ApiErrorResponder> {
+    let opts = FindOptions::builder().sort(doc! { "createdAt": -1 }).build();
+    let cursor = state.database.appeals.find(doc! { "punishmentId": punishment_id }, Some(opts)).await.ok();
+    Ok(JsonResponder::ok(Database::consume_cursor_into_owning_vec_option(cursor).await))
+}
+
+#[get("/<appeal_id>")]
+pub async fn get_appeal(
+    state: &State<MarsAPIState>,
+    appeal_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Appeal>, ApiErrorResponder> {
+    let appeal = unwrap_helper::return_default!(Database::find_by_id(&state.database.appeals, appeal_id).await, Err(ApiErrorResponder::appeal_missing()));
+    Ok(JsonResponder::ok(appeal))
+}
+
+#[post("/<appeal_id>/comments", format = "json", data = "<comment_req>")]
+pub async fn add_appeal_comment(
+    state: &State<MarsAPIState>,
+    appeal_id: &str,
+    comment_req: Json<AppealCommentRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Appeal>, ApiErrorResponder> {
+    let data = comment_req.0;
+    if data.content.trim().is_empty() {
+        return Err(ApiErrorResponder::validation_error_with_message("The comment must not be empty"));
+    };
+    let content = data.content.trim().to_owned();
+    // retried when a concurrent comment takes the id first
+    for _ in 0..COMMENT_ATTEMPTS {
+        let appeal = unwrap_helper::return_default!(Database::find_by_id(&state.database.appeals, appeal_id).await, Err(ApiErrorResponder::appeal_missing()));
+        if !appeal.is_active() {
+            return Err(ApiErrorResponder::appeal_closed());
+        };
+        let id = appeal.comments.iter().max_by_key(|comment| comment.id).map(|comment| comment.id).unwrap_or(0) + 1;
+        let comment = AppealComment { id, author: data.author.clone(), content: content.clone(), internal: data.internal, created_at: get_u64_time_millis() };
+        if let Some(appeal) = state.database.push_appeal_comment(&appeal.id, &comment).await {
+            return Ok(JsonResponder::ok(appeal));
+        };
+    }
+    Err(ApiErrorResponder::create_anonymous_error(Status::Conflict, "The appeal was changed concurrently, try again"))
+}
+
+#[post("/<appeal_id>/status", format = "json", data = "<status_req>")]
+pub async fn update_appeal_status(
+    state: &State<MarsAPIState>,
+    appeal_id: &str,
+    status_req: Json<AppealStatusRequest>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<Appeal>, ApiErrorResponder> {
+    let data = status_req.0;
+    let before = unwrap_helper::return_default!(Database::find_by_id(&state.database.appeals, appeal_id).await, Err(ApiErrorResponder::appeal_missing()));
+    if !before.status.can_transition_to(&data.status) {
+        return Err(ApiErrorResponder::appeal_closed());
+    };
+    let mut punishment = unwrap_helper::return_default!(Database::find_by_id(&state.database.punishments, &before.punishment_id).await, Err(ApiErrorResponder::missing_punishment()));
+
+    // only the request that actually moves the appeal on goes on to revert the punishment
+    let appeal = unwrap_helper::return_default!(
+        state.database.transition_appeal(&before, &data.status, &data.reviewer, get_u64_time_millis()).await,
+        Err(ApiErrorResponder::appeal_closed())
+    );
+    audit.acting_as(Some(&data.reviewer)).record_changed(
+        state, AuditAction::AppealStatusUpdate, AuditTarget::new(AuditTargetKind::Appeal, &appeal.id, &appeal.player.name), &before, &appeal
+    ).await;
+
+    // a punishment reverted by other means stays as it is
+    if appeal.status == AppealStatus::Accepted && punishment.reversion.is_none() {
+        let reason = data.reason.unwrap_or_else(|| String::from("Appeal accepted"));
+        punishment = revert_punishment(state, &audit, punishment, data.reviewer, reason).await;
+    };
+    notify_appeal_webhook(state, &appeal, punishment);
+    Ok(JsonResponder::ok(appeal))
+}
+
+fn notify_appeal_webhook(state: &MarsAPIState, appeal: &Appeal, punishment: Punishment) {
+    // take ownership for the spawned task
+    let appeal_clone = appeal.clone();
+    let state_clone = state.config.clone();
+    tokio::spawn(async move {
+        state_clone.webhooks.send_appeal_webhook(&appeal_clone, &punishment).await;
+    });
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/appeals", routes![
+        create_appeal,
+        get_appeals,
+        get_punishment_appeals,
+        get_appeal,
+        add_appeal_comment,
+        update_appeal_status
+    ])
+}
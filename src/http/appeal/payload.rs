@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::models::{appeal::AppealStatus, player::SimplePlayer};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppealCreateRequest {
+    pub punishment_id: String,
+    pub player_id: String,
+    pub statement: String
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppealCommentRequest {
+    pub author: SimplePlayer,
+    pub content: String,
+    #[serde(default)]
+    pub internal: bool
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppealStatusRequest {
+    pub status: AppealStatus,
+    pub reviewer: SimplePlayer,
+    // used as the reversion reason when the appeal is accepted
+    #[serde(default)]
+    pub reason: Option<String>
+}
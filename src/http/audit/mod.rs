@@ -0,0 +1,61 @@
+mod payload;
+
+use mongodb::{bson::Document, options::FindOptions};
+use rocket::{Build, Rocket, State};
+
+use crate::{database::{models::audit_entry::AuditEntry, Database}, util::{auth::AuthorizationToken, error::ApiErrorResponder, pagination::PageCursor, r#macro::unwrap_helper, responder::JsonResponder}, MarsAPIState};
+
+use self::payload::{AuditQuery, AuditResponse};
+
+const AUDIT_LIMIT_MAX : i64 = 200;
+
+#[get("/?<query..>")]
+pub async fn get_audit_entries(
+    state: &State<MarsAPIState>,
+    query: AuditQuery<'_>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<AuditResponse>, ApiErrorResponder> {
+    let AuditQuery { actor, server, action, target, from, to, before, limit } = query;
+    let mut filter = Document::new();
+    if let Some(actor) = actor {
+        // staff who have since been renamed or erased can still be looked up by ID
+        let actor_id = state.player_cache.get(&state.database, actor).await.map(|player| player.id).unwrap_or_else(|| actor.to_owned());
+        filter.insert("actor.player.id", actor_id);
+    };
+    if let Some(server) = server {
+        filter.insert("actor.serverId", server);
+    };
+    if let Some(action) = action {
+        filter.insert("action", action.to_uppercase());
+    };
+    if let Some(target) = target {
+        filter.insert("target.id", target);
+    };
+
+    let mut created_at = Document::new();
+    if let Some(from) = from {
+        created_at.insert("$gte", from as i64);
+    };
+    if let Some(to) = to {
+        created_at.insert("$lte", to as i64);
+    };
+    if !created_at.is_empty() {
+        filter.insert("createdAt", created_at);
+    };
+    // a change and its follow-ups (e.g. a punishment and its note) land in the same millisecond
+    if let Some(before) = before {
+        let before = unwrap_helper::return_default!(PageCursor::parse(before), Err(ApiErrorResponder::validation_error_with_message("Invalid cursor")));
+        before.apply(&mut filter, "createdAt");
+    };
+
+    let limit = limit.unwrap_or(50).clamp(1, AUDIT_LIMIT_MAX);
+    let opts = FindOptions::builder().sort(PageCursor::sort("createdAt")).limit(limit).build();
+    let cursor = state.database.audit_entries.find(filter, Some(opts)).await.ok();
+    let entries : Vec<AuditEntry> = Database::consume_cursor_into_owning_vec_option(cursor).await;
+    let next_cursor = PageCursor::next(&entries, limit, |entry| (entry.created_at, &entry.id));
+    Ok(JsonResponder::ok(AuditResponse { entries, next_cursor }))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/audit", routes![get_audit_entries])
+}
@@ -0,0 +1,26 @@
+use rocket::FromForm;
+use serde::Serialize;
+
+use crate::database::models::audit_entry::AuditEntry;
+
+#[derive(FromForm)]
+pub struct AuditQuery<'r> {
+    // name or ID of the staff member
+    pub actor: Option<&'r str>,
+    pub server: Option<&'r str>,
+    pub action: Option<&'r str>,
+    pub target: Option<&'r str>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    // cursor from a previous page
+    pub before: Option<&'r str>,
+    pub limit: Option<i64>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditResponse {
+    pub entries: Vec<AuditEntry>,
+    // pass as `before` to fetch the next page
+    pub next_cursor: Option<String>
+}
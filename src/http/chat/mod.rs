@@ -0,0 +1,137 @@
+mod payload;
+
+use std::str::FromStr;
+
+use mongodb::{bson::{doc, Document}, options::FindOptions};
+use rocket::{Build, Rocket, State};
+
+use crate::{database::{models::{chat_filter::ChatFilterHit, chat_message::ChatMessage}, Database}, socket::player::player_events::ChatChannel, util::{auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, pagination::PageCursor, responder::JsonResponder, string::escape_regex}, MarsAPIState};
+
+use self::payload::{ChatContextResponse, ChatFilterHitsResponse, ChatSearchQuery, ChatSearchResponse};
+
+const SEARCH_LIMIT_MAX : i64 = 200;
+const CONTEXT_RADIUS_MAX : i64 = 50;
+
+#[get("/search?<query..>")]
+pub async fn search_chat(
+    state: &State<MarsAPIState>,
+    query: ChatSearchQuery<'_>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<ChatSearchResponse>, ApiErrorResponder> {
+    let ChatSearchQuery { player, server, channel, q, from, to, before, limit } = query;
+    let mut filter = Document::new();
+    if let Some(player) = player {
+        let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player).await, Err(ApiErrorResponder::missing_player()));
+        filter.insert("player.id", player.id);
+    };
+    if let Some(server) = server {
+        filter.insert("serverId", server);
+    };
+    if let Some(channel) = channel {
+        let channel = unwrap_helper::result_return_default!(
+            ChatChannel::from_str(&channel.to_uppercase()),
+            Err(ApiErrorResponder::validation_error_with_message("Unknown chat channel"))
+        );
+        filter.insert("channel", channel.to_string());
+    };
+    if let Some(q) = q.map(|q| q.trim()).filter(|q| !q.is_empty()) {
+        // an unanchored regex cannot use an index, so it only runs over one player's or server's messages
+        if player.is_none() && server.is_none() {
+            return Err(ApiErrorResponder::validation_error_with_message("Searching message text requires a player or server filter"));
+        };
+        filter.insert("message", doc! { "$regex": escape_regex(q), "$options": "i" });
+    };
+
+    let mut created_at = Document::new();
+    if let Some(from) = from {
+        created_at.insert("$gte", from as i64);
+    };
+    if let Some(to) = to {
+        created_at.insert("$lte", to as i64);
+    };
+    if !created_at.is_empty() {
+        filter.insert("createdAt", created_at);
+    };
+    if filter.is_empty() {
+        return Err(ApiErrorResponder::validation_error_with_message("At least one filter is required"));
+    };
+    if let Some(before) = before {
+        let before = unwrap_helper::return_default!(PageCursor::parse(before), Err(ApiErrorResponder::validation_error_with_message("Invalid cursor")));
+        before.apply(&mut filter, "createdAt");
+    };
+
+    let limit = limit.unwrap_or(50).clamp(1, SEARCH_LIMIT_MAX);
+    let opts = FindOptions::builder().sort(PageCursor::sort("createdAt")).limit(limit).build();
+    let cursor = state.database.chat_messages.find(filter, Some(opts)).await.ok();
+    let messages : Vec<ChatMessage> = Database::consume_cursor_into_owning_vec_option(cursor).await;
+    let next_cursor = PageCursor::next(&messages, limit, |message| (message.created_at, &message.id));
+    Ok(JsonResponder::ok(ChatSearchResponse { messages, next_cursor }))
+}
+
+#[get("/<message_id>/context?<radius>")]
+pub async fn get_chat_context(
+    state: &State<MarsAPIState>,
+    message_id: &str,
+    radius: Option<i64>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<ChatContextResponse>, ApiErrorResponder> {
+    let message = unwrap_helper::return_default!(
+        Database::find_by_id(&state.database.chat_messages, message_id).await,
+        Err(ApiErrorResponder::create_anonymous_error(rocket::http::Status::NotFound, "The chat message does not exist"))
+    );
+    let radius = radius.unwrap_or(10).clamp(1, CONTEXT_RADIUS_MAX);
+
+    let before_opts = FindOptions::builder().sort(doc! { "createdAt": -1 }).limit(radius).build();
+    let cursor = state.database.chat_messages.find(doc! {
+        "serverId": &message.server_id, "createdAt": { "$lte": message.created_at as i64 }, "_id": { "$ne": &message.id }
+    }, Some(before_opts)).await.ok();
+    let mut before : Vec<ChatMessage> = Database::consume_cursor_into_owning_vec_option(cursor).await;
+    before.reverse();
+
+    let after_opts = FindOptions::builder().sort(doc! { "createdAt": 1 }).limit(radius).build();
+    let cursor = state.database.chat_messages.find(doc! {
+        "serverId": &message.server_id, "createdAt": { "$gt": message.created_at as i64 }
+    }, Some(after_opts)).await.ok();
+    let after = Database::consume_cursor_into_owning_vec_option(cursor).await;
+
+    Ok(JsonResponder::ok(ChatContextResponse { message, before, after }))
+}
+
+// for tuning filter rules against what they actually caught
+#[get("/filter/hits?<rule>&<player>&<before>&<limit>")]
+pub async fn get_chat_filter_hits(
+    state: &State<MarsAPIState>,
+    rule: Option<&str>,
+    player: Option<&str>,
+    before: Option<&str>,
+    limit: Option<i64>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<ChatFilterHitsResponse>, ApiErrorResponder> {
+    let mut filter = Document::new();
+    if let Some(rule) = rule {
+        filter.insert("ruleId", rule);
+    };
+    if let Some(player) = player {
+        let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player).await, Err(ApiErrorResponder::missing_player()));
+        filter.insert("player.id", player.id);
+    };
+    // hits from one message share a timestamp, so the id breaks ties
+    if let Some(before) = before {
+        let before = unwrap_helper::return_default!(PageCursor::parse(before), Err(ApiErrorResponder::validation_error_with_message("Invalid cursor")));
+        before.apply(&mut filter, "createdAt");
+    };
+    let limit = limit.unwrap_or(50).clamp(1, SEARCH_LIMIT_MAX);
+    let opts = FindOptions::builder().sort(PageCursor::sort("createdAt")).limit(limit).build();
+    let cursor = state.database.chat_filter_hits.find(filter, Some(opts)).await.ok();
+    let hits : Vec<ChatFilterHit> = Database::consume_cursor_into_owning_vec_option(cursor).await;
+    let next_cursor = PageCursor::next(&hits, limit, |hit| (hit.created_at, &hit.id));
+    Ok(JsonResponder::ok(ChatFilterHitsResponse { hits, next_cursor }))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/chat", routes![
+        search_chat,
+        get_chat_context,
+        get_chat_filter_hits
+    ])
+}
@@ -0,0 +1,41 @@
+use rocket::FromForm;
+use serde::Serialize;
+
+use crate::database::models::{chat_filter::ChatFilterHit, chat_message::ChatMessage};
+
+#[derive(FromForm)]
+pub struct ChatSearchQuery<'r> {
+    pub player: Option<&'r str>,
+    pub server: Option<&'r str>,
+    pub channel: Option<&'r str>,
+    pub q: Option<&'r str>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    // cursor from a previous page
+    pub before: Option<&'r str>,
+    pub limit: Option<i64>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatSearchResponse {
+    pub messages: Vec<ChatMessage>,
+    // pass as `before` to fetch the next page
+    pub next_cursor: Option<String>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatFilterHitsResponse {
+    pub hits: Vec<ChatFilterHit>,
+    // pass as `before` to fetch the next page
+    pub next_cursor: Option<String>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatContextResponse {
+    pub message: ChatMessage,
+    pub before: Vec<ChatMessage>,
+    pub after: Vec<ChatMessage>
+}
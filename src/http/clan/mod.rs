@@ -0,0 +1,411 @@
+mod payload;
+
+use std::str::FromStr;
+
+use mongodb::{bson::{doc, to_bson, Bson, Document}, options::{FindOneAndUpdateOptions, ReturnDocument}, results::UpdateResult};
+use rocket::{serde::json::Json, Build, Rocket, State};
+use uuid::Uuid;
+
+use crate::{database::models::{clan::{Clan, ClanInvite, ClanMember, ClanRole, ClanStats}, player::Player}, socket::{leaderboard::{LeaderboardEntry, LeaderboardPeriod, ScoreType}, player::player_context::send_message_to_online_players}, util::{auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, responder::{EmptyResponse, JsonResponder}, string::enumify, time::get_u64_time_millis}, MarsAPIState};
+
+use self::payload::{ClanCreateRequest, ClanInviteRequest, ClanRoleUpdateRequest, ClanUpdateRequest};
+
+macro_rules! extract_clan {
+    ( $e:expr, $s:expr ) => {
+        if let Some(clan) = ($s).database.find_clan($e).await { clan }
+        else { return Err(ApiErrorResponder::clan_missing()) }
+    }
+}
+
+macro_rules! extract_player {
+    ( $e:expr, $s:expr ) => {
+        if let Some(player) = ($s).player_cache.get(&($s).database, $e).await { player }
+        else { return Err(ApiErrorResponder::missing_player()) }
+    }
+}
+
+#[post("/", format = "json", data = "<create_req>")]
+pub async fn create_clan(
+    state: &State<MarsAPIState>,
+    create_req: Json<ClanCreateRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Clan>, ApiErrorResponder> {
+    let data = create_req.0;
+    let mut owner = extract_player!(&data.owner, state);
+    if owner.clan_id.is_some() {
+        return Err(ApiErrorResponder::clan_membership_conflict());
+    };
+    validate_name_and_tag(Some(&data.name), Some(&data.tag))?;
+    ensure_name_and_tag_available(state, None, Some(&data.name), Some(&data.tag)).await?;
+
+    let now = get_u64_time_millis();
+    let clan = Clan {
+        id: Uuid::new_v4().to_string(),
+        name: data.name.clone(),
+        name_lower: data.name.to_lowercase(),
+        tag: data.tag.clone(),
+        tag_lower: data.tag.to_lowercase(),
+        members: vec![ClanMember { player: owner.to_simple(), role: ClanRole::Owner, joined_at: now }],
+        invites: Vec::new(),
+        created_at: now,
+        last_renamed_at: None,
+        stats: ClanStats::default()
+    };
+    if !claim_player_for_clan(state, &mut owner, &clan.id).await {
+        return Err(ApiErrorResponder::clan_membership_conflict());
+    };
+    // the unique indexes on name and tag settle races between concurrent creates
+    if state.database.clans.insert_one(&clan, None).await.is_err() {
+        release_player_from_clan(state, owner, &clan.id).await;
+        return Err(ApiErrorResponder::clan_conflict());
+    };
+    Ok(JsonResponder::created(clan))
+}
+
+#[get("/<clan_id>")]
+pub async fn get_clan(
+    state: &State<MarsAPIState>,
+    clan_id: &str
+) -> Result<JsonResponder<Clan>, ApiErrorResponder> {
+    Ok(JsonResponder::ok(extract_clan!(clan_id, state)))
+}
+
+#[put("/<clan_id>", format = "json", data = "<update_req>")]
+pub async fn update_clan(
+    state: &State<MarsAPIState>,
+    clan_id: &str,
+    update_req: Json<ClanUpdateRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Clan>, ApiErrorResponder> {
+    let data = update_req.0;
+    let clan = extract_clan!(clan_id, state);
+    ensure_role(&clan, &data.actor, ClanRole::Owner)?;
+    if data.name.is_none() && data.tag.is_none() {
+        return Ok(JsonResponder::ok(clan));
+    };
+
+    let now = get_u64_time_millis();
+    let cooldown = state.config.options.clan_rename_cooldown_hours * 3_600_000;
+    if clan.last_renamed_at.map(|renamed_at| renamed_at + cooldown > now).unwrap_or(false) {
+        return Err(ApiErrorResponder::clan_rename_cooldown());
+    };
+    validate_name_and_tag(data.name.as_ref(), data.tag.as_ref())?;
+    ensure_name_and_tag_available(state, Some(&clan.id), data.name.as_ref(), data.tag.as_ref()).await?;
+
+    let previous_id_name = clan.id_name();
+    let mut update = doc! { "lastRenamedAt": now as i64 };
+    if let Some(name) = data.name {
+        update.insert("name", &name);
+        update.insert("nameLower", name.to_lowercase());
+    };
+    if let Some(tag) = data.tag {
+        update.insert("tag", &tag);
+        update.insert("tagLower", tag.to_lowercase());
+    };
+    // the unique indexes on name and tag settle races with a concurrent rename or create
+    let clan = match state.database.clans.find_one_and_update(
+        doc! { "_id": &clan.id }, doc! { "$set": update }, FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build()
+    ).await {
+        Ok(Some(clan)) => clan,
+        Ok(None) => return Err(ApiErrorResponder::clan_missing()),
+        Err(_) => return Err(ApiErrorResponder::clan_conflict())
+    };
+    if previous_id_name != clan.id_name() {
+        for leaderboard in state.leaderboards.clans.all() {
+            leaderboard.rename_member(&previous_id_name, &clan.id_name()).await;
+        };
+    };
+    Ok(JsonResponder::ok(clan))
+}
+
+#[delete("/<clan_id>?<actor>")]
+pub async fn disband_clan(
+    state: &State<MarsAPIState>,
+    clan_id: &str,
+    actor: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<EmptyResponse>, ApiErrorResponder> {
+    let clan = extract_clan!(clan_id, state);
+    ensure_role(&clan, &actor.to_owned(), ClanRole::Owner)?;
+    let _ = state.database.clans.delete_one(doc! { "_id": &clan.id }, None).await;
+    for member in clan.members.iter() {
+        if let Some(player) = state.player_cache.get(&state.database, &member.player.id).await {
+            release_player_from_clan(state, player, &clan.id).await;
+        };
+    };
+    for leaderboard in state.leaderboards.clans.all() {
+        leaderboard.remove_player(&clan.id).await;
+    };
+    Ok(JsonResponder::ok(EmptyResponse {}))
+}
+
+#[post("/<clan_id>/invites", format = "json", data = "<invite_req>")]
+pub async fn invite_player(
+    state: &State<MarsAPIState>,
+    clan_id: &str,
+    invite_req: Json<ClanInviteRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Clan>, ApiErrorResponder> {
+    let data = invite_req.0;
+    let clan = extract_clan!(clan_id, state);
+    let inviter = ensure_role(&clan, &data.actor, ClanRole::Officer)?.player.clone();
+    let target = extract_player!(&data.target, state);
+    if target.clan_id.is_some() {
+        return Err(ApiErrorResponder::clan_membership_conflict());
+    };
+    let invite = ClanInvite { player: target.to_simple(), invited_by: inviter.clone(), created_at: get_u64_time_millis() };
+    let clan = match update_clan_if(state, doc! {
+        "_id": &clan.id,
+        "members": { "$elemMatch": { "player.id": &inviter.id, "role": { "$in": [role_value(&ClanRole::Officer), role_value(&ClanRole::Owner)] } } },
+        "invites.player.id": { "$ne": &target.id }
+    }, doc! { "$push": { "invites": to_bson(&invite).unwrap_or(Bson::Null) } }, None).await {
+        Some(clan) => clan,
+        // already invited, or the inviter lost their role meanwhile
+        None => {
+            let clan = extract_clan!(&clan.id, state);
+            ensure_role(&clan, &data.actor, ClanRole::Officer)?;
+            return Ok(JsonResponder::ok(clan));
+        }
+    };
+    send_message_to_online_players(
+        state,
+        std::slice::from_ref(&target.id),
+        &format!("{} invited you to join the clan {} [{}]", inviter.name, clan.name, clan.tag),
+        None
+    ).await;
+    Ok(JsonResponder::created(clan))
+}
+
+#[post("/<clan_id>/invites/<player_id>/accept")]
+pub async fn accept_invite(
+    state: &State<MarsAPIState>,
+    clan_id: &str,
+    player_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Clan>, ApiErrorResponder> {
+    let clan = extract_clan!(clan_id, state);
+    let mut player = extract_player!(player_id, state);
+    if !clan.has_invite(&player.id) {
+        return Err(ApiErrorResponder::validation_error_with_message("The player has not been invited to this clan"));
+    };
+    let max_members = state.config.options.clan_max_members as i64;
+    if clan.members.len() as i64 >= max_members {
+        return Err(ApiErrorResponder::clan_full());
+    };
+    // the player is claimed first, so accepting two clans at once can only join one of them
+    if !claim_player_for_clan(state, &mut player, &clan.id).await {
+        return Err(ApiErrorResponder::clan_membership_conflict());
+    };
+    let member = ClanMember { player: player.to_simple(), role: ClanRole::Member, joined_at: get_u64_time_millis() };
+    let joined = update_clan_if(state, doc! {
+        "_id": &clan.id,
+        "invites.player.id": &player.id,
+        "members.player.id": { "$ne": &player.id },
+        "$expr": { "$lt": [{ "$size": "$members" }, max_members] }
+    }, doc! {
+        "$pull": { "invites": { "player.id": &player.id } },
+        "$push": { "members": to_bson(&member).unwrap_or(Bson::Null) }
+    }, None).await;
+    match joined {
+        Some(clan) => Ok(JsonResponder::ok(clan)),
+        None => {
+            release_player_from_clan(state, player, &clan.id).await;
+            // either a concurrent accept filled the last slot or the invite was revoked meanwhile
+            let clan = extract_clan!(&clan.id, state);
+            if !clan.has_invite(&player_id.to_owned()) {
+                return Err(ApiErrorResponder::validation_error_with_message("The player has not been invited to this clan"));
+            };
+            Err(ApiErrorResponder::clan_full())
+        }
+    }
+}
+
+// used both by the invitee to decline and by officers to revoke
+#[post("/<clan_id>/invites/<player_id>/decline?<actor>")]
+pub async fn decline_invite(
+    state: &State<MarsAPIState>,
+    clan_id: &str,
+    player_id: &str,
+    actor: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Clan>, ApiErrorResponder> {
+    let clan = extract_clan!(clan_id, state);
+    if actor != player_id {
+        ensure_role(&clan, &actor.to_owned(), ClanRole::Officer)?;
+    };
+    let clan = unwrap_helper::return_default!(
+        update_clan_if(state, doc! { "_id": &clan.id, "invites.player.id": player_id }, doc! { "$pull": { "invites": { "player.id": player_id } } }, None).await,
+        Err(ApiErrorResponder::validation_error_with_message("The player has not been invited to this clan"))
+    );
+    Ok(JsonResponder::ok(clan))
+}
+
+// a player removing themselves leaves the clan, anyone else is a kick
+#[delete("/<clan_id>/members/<player_id>?<actor>")]
+pub async fn remove_member(
+    state: &State<MarsAPIState>,
+    clan_id: &str,
+    player_id: &str,
+    actor: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Clan>, ApiErrorResponder> {
+    let clan = extract_clan!(clan_id, state);
+    let player = extract_player!(player_id, state);
+    let target_role = unwrap_helper::return_default!(clan.get_role(&player.id), Err(ApiErrorResponder::validation_error_with_message("The player is not a member of this clan")));
+    if actor == player.id {
+        if target_role == ClanRole::Owner {
+            return Err(ApiErrorResponder::validation_error_with_message("The owner must transfer ownership before leaving"));
+        };
+    } else {
+        let actor_role = ensure_role(&clan, &actor.to_owned(), ClanRole::Officer)?.role.clone();
+        if actor_role <= target_role {
+            return Err(ApiErrorResponder::clan_insufficient_role());
+        };
+    };
+    // the role checked above must still hold, a concurrent promotion could otherwise be kicked by an equal
+    let clan = unwrap_helper::return_default!(update_clan_if(state, doc! {
+        "_id": &clan.id,
+        "members": { "$elemMatch": { "player.id": &player.id, "role": role_value(&target_role) } }
+    }, doc! { "$pull": { "members": { "player.id": &player.id } } }, None).await, Err(ApiErrorResponder::validation_error_with_message("The player is not a member of this clan")));
+    release_player_from_clan(state, player, &clan.id).await;
+    Ok(JsonResponder::ok(clan))
+}
+
+#[put("/<clan_id>/members/<player_id>/role", format = "json", data = "<role_req>")]
+pub async fn update_member_role(
+    state: &State<MarsAPIState>,
+    clan_id: &str,
+    player_id: &str,
+    role_req: Json<ClanRoleUpdateRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Clan>, ApiErrorResponder> {
+    let data = role_req.0;
+    let clan = extract_clan!(clan_id, state);
+    ensure_role(&clan, &data.actor, ClanRole::Owner)?;
+    if clan.get_member(&player_id.to_owned()).is_none() {
+        return Err(ApiErrorResponder::validation_error_with_message("The player is not a member of this clan"));
+    };
+    if data.actor == player_id {
+        return Err(ApiErrorResponder::validation_error_with_message("The owner cannot change their own role"));
+    };
+    let mut update = doc! { "members.$[target].role": role_value(&data.role) };
+    let mut array_filters = vec![doc! { "target.player.id": player_id }];
+    if data.role == ClanRole::Owner {
+        // there is only ever one owner, so promoting someone else transfers ownership
+        update.insert("members.$[actor].role", role_value(&ClanRole::Officer));
+        array_filters.push(doc! { "actor.player.id": &data.actor });
+    };
+    let clan = unwrap_helper::return_default!(update_clan_if(state, doc! {
+        "_id": &clan.id,
+        "members": { "$elemMatch": { "player.id": &data.actor, "role": role_value(&ClanRole::Owner) } },
+        "members.player.id": player_id
+    }, doc! { "$set": update }, Some(array_filters)).await, Err(ApiErrorResponder::clan_insufficient_role()));
+    Ok(JsonResponder::ok(clan))
+}
+
+#[get("/leaderboards/<score_type>/<period>?<limit>")]
+pub async fn get_clan_leaderboard(
+    state: &State<MarsAPIState>,
+    score_type: &str,
+    period: &str,
+    limit: Option<u32>
+) -> Result<JsonResponder<Vec<LeaderboardEntry>>, ApiErrorResponder> {
+    let score_type = unwrap_helper::return_default!(ScoreType::from_str(enumify(score_type).as_str()).ok(), Err(ApiErrorResponder::validation_error()));
+    let leaderboard = unwrap_helper::return_default!(state.leaderboards.clans.by_score_type(&score_type), Err(ApiErrorResponder::validation_error()));
+    let period = unwrap_helper::return_default!(LeaderboardPeriod::from_str(enumify(period).as_str()).ok(), Err(ApiErrorResponder::validation_error()));
+    let limit = limit.unwrap_or(10);
+    Ok(JsonResponder::ok(leaderboard.fetch_top(&period, if limit > 50 { 50 } else { limit }).await))
+}
+
+fn ensure_role<'a>(clan: &'a Clan, player_id: &String, minimum: ClanRole) -> Result<&'a ClanMember, ApiErrorResponder> {
+    match clan.get_member(player_id) {
+        Some(member) if member.role >= minimum => Ok(member),
+        _ => Err(ApiErrorResponder::clan_insufficient_role())
+    }
+}
+
+fn validate_name_and_tag(name: Option<&String>, tag: Option<&String>) -> Result<(), ApiErrorResponder> {
+    if let Some(name) = name {
+        let valid = (3..=24).contains(&name.len())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '_')
+            && name.trim() == name;
+        if !valid {
+            return Err(ApiErrorResponder::validation_error_with_message("Clan names must be 3-24 letters, digits, spaces or underscores"));
+        };
+    };
+    if let Some(tag) = tag {
+        let valid = (2..=6).contains(&tag.len()) && tag.chars().all(|c| c.is_ascii_alphanumeric());
+        if !valid {
+            return Err(ApiErrorResponder::validation_error_with_message("Clan tags must be 2-6 letters or digits"));
+        };
+    };
+    Ok(())
+}
+
+async fn ensure_name_and_tag_available(
+    state: &MarsAPIState,
+    own_id: Option<&String>,
+    name: Option<&String>,
+    tag: Option<&String>
+) -> Result<(), ApiErrorResponder> {
+    let mut conditions = Vec::new();
+    if let Some(name) = name {
+        conditions.push(doc! { "nameLower": name.to_lowercase() });
+    };
+    if let Some(tag) = tag {
+        conditions.push(doc! { "tagLower": tag.to_lowercase() });
+    };
+    if conditions.is_empty() {
+        return Ok(());
+    };
+    let existing = state.database.clans.find_one(doc! { "$or": conditions }, None).await.unwrap_or(None);
+    match existing {
+        Some(existing) if Some(&existing.id) != own_id => Err(ApiErrorResponder::clan_conflict()),
+        _ => Ok(())
+    }
+}
+
+// sets the player's clan only while they are in none, so they can never end up in two
+async fn claim_player_for_clan(state: &MarsAPIState, player: &mut Player, clan_id: &String) -> bool {
+    let result = state.database.players.update_one(doc! { "_id": &player.id, "clanId": null }, doc! { "$set": { "clanId": clan_id } }, None).await;
+    if !matches!(result, Ok(UpdateResult { matched_count: 1, .. })) {
+        return false;
+    };
+    player.clan_id = Some(clan_id.clone());
+    state.player_cache.set(&state.database, &player.name, player, false).await;
+    true
+}
+
+// clears the player's clan only if it is still this one
+async fn release_player_from_clan(state: &MarsAPIState, mut player: Player, clan_id: &String) {
+    let _ = state.database.players.update_one(doc! { "_id": &player.id, "clanId": clan_id }, doc! { "$set": { "clanId": null } }, None).await;
+    if player.clan_id.as_ref() == Some(clan_id) {
+        player.clan_id = None;
+        state.player_cache.set(&state.database, &player.name, &player, false).await;
+    };
+}
+
+// membership changes are applied as conditional updates so concurrent ones cannot overwrite each other
+async fn update_clan_if(state: &MarsAPIState, filter: Document, update: Document, array_filters: Option<Vec<Document>>) -> Option<Clan> {
+    let opts = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).array_filters(array_filters).build();
+    state.database.clans.find_one_and_update(filter, update, opts).await.ok().flatten()
+}
+
+fn role_value(role: &ClanRole) -> Bson {
+    to_bson(role).unwrap_or(Bson::Null)
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/clans", routes![
+        create_clan,
+        get_clan,
+        update_clan,
+        disband_clan,
+        invite_player,
+        accept_invite,
+        decline_invite,
+        remove_member,
+        update_member_role,
+        get_clan_leaderboard
+    ])
+}
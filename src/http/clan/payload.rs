@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::models::clan::ClanRole;
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClanCreateRequest {
+    pub name: String,
+    pub tag: String,
+    // id of the founding player
+    pub owner: String
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClanUpdateRequest {
+    pub actor: String,
+    pub name: Option<String>,
+    pub tag: Option<String>
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClanInviteRequest {
+    pub actor: String,
+    // id or name of the invited player
+    pub target: String
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClanRoleUpdateRequest {
+    pub actor: String,
+    pub role: ClanRole
+}
@@ -0,0 +1,221 @@
+mod payload;
+
+use mongodb::bson::doc;
+use rocket::{http::Status, serde::json::Json, Build, Rocket, State};
+
+use crate::{database::{models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, cosmetic::{CosmeticOwnership, CosmeticSource}, currency::{CurrencyTransaction, CurrencyTransactionError, CurrencyTransactionKind, RecordedCurrencyTransaction}, player::Player}, Database}, socket::{player::player_context::send_player_update_to_online_player, update::player_update_listener::{PlayerUpdateData, PlayerUpdateReason}}, util::{audit::AuditContext, auth::AuthorizationToken, error::ApiErrorResponder, pagination::PageCursor, r#macro::unwrap_helper, responder::JsonResponder}, MarsAPIState};
+
+use self::payload::{CurrencyAdjustRequest, CurrencyBalanceResponse, CurrencyRefundRequest, CurrencyTransactionResponse, CurrencyTransactionsResponse};
+
+#[get("/<player_id>")]
+pub async fn get_balance(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<CurrencyBalanceResponse>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let balance = state.database.get_currency_balance(&player.id).await;
+    Ok(JsonResponder::ok(CurrencyBalanceResponse { player_id: player.id, balance }))
+}
+
+#[get("/<player_id>/transactions?<before>&<limit>")]
+pub async fn get_transactions(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    before: Option<&str>,
+    limit: Option<i64>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<CurrencyTransactionsResponse>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let before = match before {
+        Some(before) => Some(unwrap_helper::return_default!(PageCursor::parse(before), Err(ApiErrorResponder::validation_error_with_message("Invalid cursor")))),
+        None => None
+    };
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    let transactions = state.database.get_currency_transactions(&player.id, before.as_ref(), limit).await;
+    let next_cursor = PageCursor::next(&transactions, limit, |transaction| (transaction.created_at, &transaction.id));
+    Ok(JsonResponder::ok(CurrencyTransactionsResponse { transactions, next_cursor }))
+}
+
+#[post("/<player_id>/adjust", format = "json", data = "<adjust_req>")]
+pub async fn adjust_balance(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    adjust_req: Json<CurrencyAdjustRequest>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<CurrencyTransactionResponse>, ApiErrorResponder> {
+    let data = adjust_req.0;
+    if data.amount == 0 {
+        return Err(ApiErrorResponder::validation_error_with_message("Adjustment amount must not be zero"));
+    };
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+
+    let mut transaction = CurrencyTransaction::new(&player.id, data.amount, CurrencyTransactionKind::Adjust, &data.reason);
+    transaction.idempotency_key = data.idempotency_key;
+    transaction.actor = data.actor;
+    let RecordedCurrencyTransaction { transaction, created, balance } = state.database.record_currency_transaction(transaction).await
+        .map_err(transaction_error)?;
+    if created {
+        audit.acting_as(transaction.actor.as_ref()).record_created(
+            state, AuditAction::CurrencyAdjust, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &transaction
+        ).await;
+        notify_balance_change(state, player, balance, transaction.amount).await;
+    };
+    Ok(JsonResponder::from(CurrencyTransactionResponse { transaction, balance }, if created { Status::Created } else { Status::Ok }))
+}
+
+#[post("/transactions/<transaction_id>/refund", format = "json", data = "<refund_req>")]
+pub async fn refund_transaction(
+    state: &State<MarsAPIState>,
+    transaction_id: &str,
+    refund_req: Json<CurrencyRefundRequest>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<CurrencyTransactionResponse>, ApiErrorResponder> {
+    let data = refund_req.0;
+    let original = unwrap_helper::return_default!(
+        Database::find_by_id(&state.database.currency_transactions, transaction_id).await,
+        Err(ApiErrorResponder::transaction_missing())
+    );
+    if original.kind == CurrencyTransactionKind::Refund {
+        return Err(ApiErrorResponder::transaction_conflict());
+    };
+
+    let reason = data.reason.unwrap_or_else(|| format!("Refund of {}", original.reason));
+    let mut refund = CurrencyTransaction::new(&original.player_id, -original.amount, CurrencyTransactionKind::Refund, &reason);
+    // one refund per transaction
+    refund.idempotency_key = Some(format!("refund:{}", original.id));
+    refund.reference = Some(original.id.clone());
+    refund.actor = data.actor;
+    // refunding earned currency takes it back, which is refused if it has already been spent
+    let RecordedCurrencyTransaction { transaction: refund, created, balance } = state.database.record_currency_transaction(refund).await
+        .map_err(transaction_error)?;
+    if !created {
+        return Err(ApiErrorResponder::transaction_conflict());
+    };
+
+    let player = Database::find_by_id(&state.database.players, &original.player_id).await;
+    let target = match &player {
+        Some(player) => AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name),
+        None => AuditTarget { kind: AuditTargetKind::Player, id: original.player_id.clone(), name: None }
+    };
+    audit.acting_as(refund.actor.as_ref()).record_created(state, AuditAction::CurrencyRefund, target, &refund).await;
+    // refunded purchases take the item back
+    if let (CurrencyTransactionKind::Spend, Some(cosmetic_id)) = (&original.kind, &original.reference) {
+        let _ = state.database.cosmetic_ownerships.delete_one(doc! {
+            "_id": CosmeticOwnership::make_id(&original.player_id, cosmetic_id),
+            "source": "PURCHASE"
+        }, None).await;
+        if let Some(player) = &player {
+            unequip_cosmetic(state, player, cosmetic_id).await;
+        };
+    };
+
+    if let Some(player) = player {
+        notify_balance_change(state, player, balance, refund.amount).await;
+    };
+    Ok(JsonResponder::created(CurrencyTransactionResponse { transaction: refund, balance }))
+}
+
+// the cached copy is preferred, it may hold progress not yet persisted
+async fn unequip_cosmetic(state: &MarsAPIState, player: &Player, cosmetic_id: &str) {
+    let mut player = state.player_cache.get(&state.database, &player.name).await.unwrap_or_else(|| player.clone());
+    let equipped = player.active_cosmetics.len();
+    player.active_cosmetics.retain(|_, equipped_id| equipped_id != cosmetic_id);
+    let join_sound = player.active_join_sound_id.as_deref() == Some(cosmetic_id);
+    if join_sound {
+        player.active_join_sound_id = None;
+    };
+    if join_sound || player.active_cosmetics.len() != equipped {
+        state.player_cache.set(&state.database, &player.name, &player, true).await;
+    };
+}
+
+fn transaction_error(error: CurrencyTransactionError) -> ApiErrorResponder {
+    match error {
+        CurrencyTransactionError::InsufficientFunds => ApiErrorResponder::insufficient_funds(),
+        CurrencyTransactionError::Failed => ApiErrorResponder::create_anonymous_error(Status::InternalServerError, "Could not record transaction")
+    }
+}
+
+pub async fn notify_balance_change(state: &MarsAPIState, player: Player, balance: i64, change: i64) {
+    send_player_update_to_online_player(
+        state,
+        player,
+        PlayerUpdateData::CurrencyUpdateData { balance, change },
+        PlayerUpdateReason::Currency
+    ).await;
+}
+
+// spends the price and records a PURCHASE ownership, retries with the same key resolve to the first purchase
+pub async fn purchase_cosmetic(
+    state: &MarsAPIState,
+    player: Player,
+    cosmetic_id: &str,
+    price: u64,
+    idempotency_key: Option<String>
+) -> Result<(CosmeticOwnership, i64), ApiErrorResponder> {
+    let ownership_id = CosmeticOwnership::make_id(&player.id, cosmetic_id);
+    let mut spend = CurrencyTransaction::new(&player.id, -(price as i64), CurrencyTransactionKind::Spend, &format!("Purchased {}", cosmetic_id));
+    spend.idempotency_key = idempotency_key;
+    spend.reference = Some(cosmetic_id.to_owned());
+
+    if let Some(previous) = state.database.find_currency_transaction_by_key(&spend).await {
+        return resolve_purchase_retry(state, &previous, &spend, &ownership_id).await;
+    };
+    if Database::find_by_id(&state.database.cosmetic_ownerships, &ownership_id).await.is_some() {
+        return Err(ApiErrorResponder::cosmetic_already_owned());
+    };
+    let recorded = state.database.record_currency_transaction(spend.clone()).await.map_err(transaction_error)?;
+    if !recorded.created {
+        return resolve_purchase_retry(state, &recorded.transaction, &spend, &ownership_id).await;
+    };
+    let RecordedCurrencyTransaction { transaction: spend, balance, .. } = recorded;
+
+    let ownership = CosmeticOwnership {
+        id: ownership_id,
+        player_id: player.id.clone(),
+        cosmetic_id: cosmetic_id.to_owned(),
+        source: CosmeticSource::Purchase,
+        granted_by: None,
+        created_at: spend.created_at
+    };
+    // a concurrent purchase under another key got there first, give the currency back
+    if state.database.cosmetic_ownerships.insert_one(&ownership, None).await.is_err() {
+        let mut refund = CurrencyTransaction::new(&player.id, -spend.amount, CurrencyTransactionKind::Refund, &format!("Refund of {}", spend.reason));
+        refund.idempotency_key = Some(format!("refund:{}", spend.id));
+        refund.reference = Some(spend.id.clone());
+        let _ = state.database.record_currency_transaction(refund).await;
+        return Err(ApiErrorResponder::cosmetic_already_owned());
+    };
+    notify_balance_change(state, player, balance, spend.amount).await;
+    Ok((ownership, balance))
+}
+
+// a key reused for another player or cosmetic is not a retry, nor is one whose purchase was refunded since
+async fn resolve_purchase_retry(
+    state: &MarsAPIState,
+    previous: &CurrencyTransaction,
+    spend: &CurrencyTransaction,
+    ownership_id: &str
+) -> Result<(CosmeticOwnership, i64), ApiErrorResponder> {
+    if previous.kind != CurrencyTransactionKind::Spend || previous.player_id != spend.player_id || previous.reference != spend.reference {
+        return Err(ApiErrorResponder::transaction_conflict());
+    };
+    let ownership = unwrap_helper::return_default!(
+        Database::find_by_id(&state.database.cosmetic_ownerships, ownership_id).await,
+        Err(ApiErrorResponder::transaction_conflict())
+    );
+    let balance = state.database.get_currency_balance(&spend.player_id).await;
+    Ok((ownership, balance))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/currency", routes![
+        get_balance,
+        get_transactions,
+        adjust_balance,
+        refund_transaction
+    ])
+}
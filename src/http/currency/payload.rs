@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::models::{currency::CurrencyTransaction, player::SimplePlayer};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrencyBalanceResponse {
+    pub player_id: String,
+    pub balance: i64
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrencyAdjustRequest {
+    pub amount: i64,
+    pub reason: String,
+    pub actor: Option<SimplePlayer>,
+    pub idempotency_key: Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrencyRefundRequest {
+    pub reason: Option<String>,
+    pub actor: Option<SimplePlayer>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrencyTransactionResponse {
+    pub transaction: CurrencyTransaction,
+    pub balance: i64
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrencyTransactionsResponse {
+    pub transactions: Vec<CurrencyTransaction>,
+    // pass as `before` to fetch the next page
+    pub next_cursor: Option<String>
+}
@@ -0,0 +1,206 @@
+mod payload;
+
+use std::collections::HashMap;
+
+use mongodb::bson::doc;
+use rocket::{serde::json::Json, Build, Rocket, State};
+use uuid::Uuid;
+
+use crate::{database::models::{friendship::{Friendship, FriendshipStatus}, player::Player}, socket::player::player_context::send_message_to_online_players, util::{auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, responder::{EmptyResponse, JsonResponder}, time::get_u64_time_millis}, MarsAPIState};
+
+use self::payload::{FriendListResponse, FriendRequestCreateRequest, FriendRequestResponse, FriendResponse};
+
+#[get("/<player_id>")]
+pub async fn get_friends(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<FriendListResponse>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let friendships = state.database.get_friendships(&player.id).await;
+
+    let friend_ids : Vec<String> = friendships.iter()
+        .filter(|friendship| friendship.is_accepted())
+        .map(|friendship| friendship.other(&player.id).id.clone())
+        .collect();
+    let online : HashMap<String, String> = state.database.get_active_sessions_for_players(&friend_ids).await
+        .into_iter()
+        .map(|session| (session.player.id, session.server_id))
+        .collect();
+
+    let mut friends = Vec::new();
+    let mut incoming_requests = Vec::new();
+    let mut outgoing_requests = Vec::new();
+    for friendship in friendships.iter() {
+        let other = friendship.other(&player.id).clone();
+        match friendship.status {
+            FriendshipStatus::Accepted => {
+                let server_id = online.get(&other.id).cloned();
+                friends.push(FriendResponse {
+                    player: other,
+                    since: friendship.accepted_at.unwrap_or(friendship.created_at),
+                    online: server_id.is_some(),
+                    server_id
+                });
+            },
+            FriendshipStatus::Pending => {
+                let request = FriendRequestResponse { player: other, created_at: friendship.created_at };
+                if friendship.recipient.id == player.id { incoming_requests.push(request) } else { outgoing_requests.push(request) }
+            }
+        }
+    };
+    Ok(JsonResponder::ok(FriendListResponse { friends, incoming_requests, outgoing_requests }))
+}
+
+#[post("/<player_id>/requests", format = "json", data = "<request>")]
+pub async fn send_friend_request(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    request: Json<FriendRequestCreateRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Friendship>, ApiErrorResponder> {
+    let data = request.0;
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let target = unwrap_helper::return_default!(state.player_cache.get(&state.database, &data.target).await, Err(ApiErrorResponder::missing_player()));
+    if player.id == target.id {
+        return Err(ApiErrorResponder::validation_error_with_message("Players cannot befriend themselves"));
+    };
+
+    if let Some(existing) = state.database.find_friendship(&player.id, &target.id).await {
+        // sending a request to someone who already asked us is treated as accepting theirs
+        if existing.is_accepted() || existing.requester.id == player.id {
+            return Err(ApiErrorResponder::friendship_conflict());
+        };
+        let friendship = accept_request(state, existing, &player).await?;
+        return Ok(JsonResponder::ok(friendship));
+    };
+
+    ensure_below_friend_limit(state, &player.id, None).await?;
+    let friendship = Friendship {
+        id: Uuid::new_v4().to_string(),
+        requester: player.to_simple(),
+        recipient: target.to_simple(),
+        status: FriendshipStatus::Pending,
+        created_at: get_u64_time_millis(),
+        accepted_at: None,
+        pair_key: Some(Friendship::pair_key(&player.id, &target.id))
+    };
+    // the pair key is unique, so concurrent requests between the same players cannot both land
+    if state.database.friendships.insert_one(&friendship, None).await.is_err() {
+        return Err(ApiErrorResponder::friendship_conflict());
+    };
+    send_message_to_online_players(
+        state,
+        std::slice::from_ref(&target.id),
+        &format!("{} sent you a friend request", player.name),
+        None
+    ).await;
+    Ok(JsonResponder::created(friendship))
+}
+
+#[post("/<player_id>/requests/<other_id>/accept")]
+pub async fn accept_friend_request(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    other_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Friendship>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let friendship = unwrap_helper::return_default!(
+        state.database.find_friendship(&player.id, &other_id.to_owned()).await,
+        Err(ApiErrorResponder::friendship_missing())
+    );
+    if friendship.is_accepted() || friendship.recipient.id != player.id {
+        return Err(ApiErrorResponder::friendship_missing());
+    };
+    let friendship = accept_request(state, friendship, &player).await?;
+    Ok(JsonResponder::ok(friendship))
+}
+
+// declining an incoming request and cancelling an outgoing one both remove the pending request
+#[post("/<player_id>/requests/<other_id>/decline")]
+pub async fn decline_friend_request(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    other_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<EmptyResponse>, ApiErrorResponder> {
+    let friendship = unwrap_helper::return_default!(
+        state.database.find_friendship(&player_id.to_owned(), &other_id.to_owned()).await,
+        Err(ApiErrorResponder::friendship_missing())
+    );
+    if friendship.is_accepted() {
+        return Err(ApiErrorResponder::friendship_missing());
+    };
+    let _ = state.database.friendships.delete_one(doc! { "_id": &friendship.id }, None).await;
+    Ok(JsonResponder::ok(EmptyResponse {}))
+}
+
+#[delete("/<player_id>/<friend_id>")]
+pub async fn remove_friend(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    friend_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<EmptyResponse>, ApiErrorResponder> {
+    let friendship = unwrap_helper::return_default!(
+        state.database.find_friendship(&player_id.to_owned(), &friend_id.to_owned()).await,
+        Err(ApiErrorResponder::friendship_missing())
+    );
+    if !friendship.is_accepted() {
+        return Err(ApiErrorResponder::friendship_missing());
+    };
+    let _ = state.database.friendships.delete_one(doc! { "_id": &friendship.id }, None).await;
+    Ok(JsonResponder::ok(EmptyResponse {}))
+}
+
+// outgoing requests count towards the limit so they cannot be sent without bound,
+// the friendship being accepted (if any) is left out as it is already counted once
+async fn ensure_below_friend_limit(state: &MarsAPIState, player_id: &String, excluding: Option<&String>) -> Result<(), ApiErrorResponder> {
+    let count = state.database.friendships.count_documents(doc! {
+        "_id": { "$ne": excluding },
+        "$or": [
+            { "status": "ACCEPTED", "requester.id": player_id },
+            { "status": "ACCEPTED", "recipient.id": player_id },
+            { "status": "PENDING", "requester.id": player_id }
+        ]
+    }, None).await.unwrap_or(0);
+    if count >= state.config.options.max_friends as u64 {
+        return Err(ApiErrorResponder::friend_limit_reached());
+    };
+    Ok(())
+}
+
+async fn accept_request(state: &MarsAPIState, mut friendship: Friendship, accepter: &Player) -> Result<Friendship, ApiErrorResponder> {
+    ensure_below_friend_limit(state, &accepter.id, Some(&friendship.id)).await?;
+    ensure_below_friend_limit(state, &friendship.requester.id, Some(&friendship.id)).await?;
+    friendship.status = FriendshipStatus::Accepted;
+    friendship.accepted_at = Some(get_u64_time_millis());
+    friendship.pair_key = Some(Friendship::pair_key(&friendship.requester.id, &friendship.recipient.id));
+    state.database.save(&friendship).await;
+    send_message_to_online_players(
+        state,
+        &[friendship.requester.id.clone()],
+        &format!("{} accepted your friend request", accepter.name),
+        None
+    ).await;
+    Ok(friendship)
+}
+
+pub async fn notify_friends_of_login(state: &MarsAPIState, player: &Player) {
+    let friend_ids : Vec<String> = state.database.get_friendships(&player.id).await.iter()
+        .filter(|friendship| friendship.is_accepted())
+        .map(|friendship| friendship.other(&player.id).id.clone())
+        .collect();
+    send_message_to_online_players(state, &friend_ids, &format!("Your friend {} is now online", player.name), None).await;
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/friends", routes![
+        get_friends,
+        send_friend_request,
+        accept_friend_request,
+        decline_friend_request,
+        remove_friend
+    ])
+}
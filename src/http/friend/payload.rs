@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::models::player::SimplePlayer;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FriendRequestCreateRequest {
+    // id or name of the player to befriend
+    pub target: String
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FriendListResponse {
+    pub friends: Vec<FriendResponse>,
+    pub incoming_requests: Vec<FriendRequestResponse>,
+    pub outgoing_requests: Vec<FriendRequestResponse>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FriendResponse {
+    pub player: SimplePlayer,
+    pub since: u64,
+    pub online: bool,
+    pub server_id: Option<String>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FriendRequestResponse {
+    pub player: SimplePlayer,
+    pub created_at: u64
+}
@@ -0,0 +1,172 @@
+mod payload;
+
+use mongodb::{bson::{doc, to_bson, Document}, options::{FindOneAndUpdateOptions, ReturnDocument}};
+use rocket::{serde::json::Json, Build, Rocket, State};
+
+use crate::{database::models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, gate_policy::{AllowlistPolicy, GatePolicies, IpLimitPolicy, MaintenancePolicy, ReservedSlotsPolicy, GATE_POLICIES_ID}, player::Player, rank::Rank}, util::{audit::AuditContext, auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, responder::JsonResponder}, MarsAPIState};
+
+use self::payload::AllowlistPlayerRequest;
+
+
+const DEFAULT_MAINTENANCE_MESSAGE : &str = "The network is undergoing maintenance, please check back later";
+const DEFAULT_ALLOWLIST_MESSAGE : &str = "You are not on the allowlist for this event";
+const DEFAULT_RESERVED_SLOTS_MESSAGE : &str = "The network is full, the remaining slots are reserved for ranked players";
+const DEFAULT_IP_LIMIT_MESSAGE : &str = "Too many accounts are online from your connection";
+
+async fn get_gate_policies(state: &MarsAPIState) -> GatePolicies {
+    state.database.gate_policies.find_one(doc! { "_id": GATE_POLICIES_ID }, None).await.ok().flatten().unwrap_or_default()
+}
+
+// returns the kick message of the first policy that turns the player away
+pub async fn evaluate_gate(state: &MarsAPIState, player: &Player, ip_candidates: &[String], shared_ip: bool) -> Option<String> {
+    let policies = get_gate_policies(state).await;
+    let GatePolicies { maintenance, allowlist, reserved_slots, ip_limit } = policies;
+    if !(maintenance.enabled || allowlist.enabled || reserved_slots.enabled || ip_limit.enabled) {
+        return None;
+    };
+
+    if maintenance.enabled {
+        let bypass = player.rank_ids.iter().any(|rank_id| maintenance.bypass_rank_ids.contains(rank_id)) || match &maintenance.bypass_permission {
+            Some(permission) => Rank::find_for_player(&state.database, player).await.iter().any(|rank| rank.permissions.contains(permission)),
+            None => false
+        };
+        if !bypass {
+            return Some(maintenance.kick_message.unwrap_or(DEFAULT_MAINTENANCE_MESSAGE.to_owned()));
+        };
+    };
+    if allowlist.enabled && !allowlist.player_ids.contains(&player.id) {
+        return Some(allowlist.kick_message.unwrap_or(DEFAULT_ALLOWLIST_MESSAGE.to_owned()));
+    };
+
+    // capacity limits only apply to players who are not already online, e.g. switching servers
+    if !(reserved_slots.enabled || ip_limit.enabled)
+        || !state.database.get_active_sessions_for_players(std::slice::from_ref(&player.id)).await.is_empty() {
+        return None;
+    };
+    if reserved_slots.enabled {
+        let ranked = if reserved_slots.rank_ids.is_empty() { !player.rank_ids.is_empty() }
+            else { player.rank_ids.iter().any(|rank_id| reserved_slots.rank_ids.contains(rank_id)) };
+        let open_slots = reserved_slots.capacity.saturating_sub(reserved_slots.reserved) as u64;
+        if !ranked && state.database.count_online_players().await >= open_slots {
+            return Some(reserved_slots.kick_message.unwrap_or(DEFAULT_RESERVED_SLOTS_MESSAGE.to_owned()));
+        };
+    };
+    if ip_limit.enabled && !shared_ip {
+        let online = state.database.get_online_player_ids_for_ips(ip_candidates).await;
+        if online.iter().filter(|id| *id != &player.id).count() >= ip_limit.max_accounts as usize {
+            return Some(ip_limit.kick_message.unwrap_or(DEFAULT_IP_LIMIT_MESSAGE.to_owned()));
+        };
+    };
+    None
+}
+
+// each change is a single update of the stored document, so concurrent changes to different policies all apply
+async fn update_gate_policies(state: &MarsAPIState, audit: &AuditContext, update: Document) -> GatePolicies {
+    let opts = FindOneAndUpdateOptions::builder().upsert(true).return_document(ReturnDocument::Before).build();
+    let before = state.database.gate_policies.find_one_and_update(doc! { "_id": GATE_POLICIES_ID }, update, opts).await
+        .ok().flatten().unwrap_or_default();
+    let after = get_gate_policies(state).await;
+    audit.record_changed(
+        state, AuditAction::GatePolicyUpdate, AuditTarget::new(AuditTargetKind::GatePolicy, GATE_POLICIES_ID, "Prelogin gate"), &before, &after
+    ).await;
+    after
+}
+
+fn set_policy<T: serde::Serialize>(field: &str, policy: &T) -> Result<Document, ApiErrorResponder> {
+    let policy = to_bson(policy).map_err(|_| ApiErrorResponder::validation_error_with_message("Invalid gate policy"))?;
+    Ok(doc! { "$set": { field: policy } })
+}
+
+#[get("/")]
+pub async fn get_gate(
+    state: &State<MarsAPIState>,
+    _auth_guard: AuthorizationToken
+) -> JsonResponder<GatePolicies> {
+    JsonResponder::ok(get_gate_policies(state).await)
+}
+
+#[put("/maintenance", format = "json", data = "<policy>")]
+pub async fn set_maintenance(
+    state: &State<MarsAPIState>,
+    policy: Json<MaintenancePolicy>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<GatePolicies>, ApiErrorResponder> {
+    Ok(JsonResponder::ok(update_gate_policies(state, &audit, set_policy("maintenance", &policy.0)?).await))
+}
+
+// the player list is managed through its own routes
+#[put("/allowlist", format = "json", data = "<policy>")]
+pub async fn set_allowlist(
+    state: &State<MarsAPIState>,
+    policy: Json<AllowlistPolicy>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> JsonResponder<GatePolicies> {
+    let AllowlistPolicy { enabled, kick_message, .. } = policy.0;
+    JsonResponder::ok(update_gate_policies(state, &audit, doc! {
+        "$set": { "allowlist.enabled": enabled, "allowlist.kickMessage": kick_message }
+    }).await)
+}
+
+#[post("/allowlist/players", format = "json", data = "<allowlist_req>")]
+pub async fn add_allowlist_player(
+    state: &State<MarsAPIState>,
+    allowlist_req: Json<AllowlistPlayerRequest>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<GatePolicies>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(
+        state.player_cache.get(&state.database, &allowlist_req.player).await,
+        Err(ApiErrorResponder::missing_player())
+    );
+    Ok(JsonResponder::ok(update_gate_policies(state, &audit, doc! { "$addToSet": { "allowlist.playerIds": &player.id } }).await))
+}
+
+#[delete("/allowlist/players/<player_id>")]
+pub async fn remove_allowlist_player(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> JsonResponder<GatePolicies> {
+    JsonResponder::ok(update_gate_policies(state, &audit, doc! { "$pull": { "allowlist.playerIds": player_id } }).await)
+}
+
+#[put("/reserved-slots", format = "json", data = "<policy>")]
+pub async fn set_reserved_slots(
+    state: &State<MarsAPIState>,
+    policy: Json<ReservedSlotsPolicy>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<GatePolicies>, ApiErrorResponder> {
+    if policy.reserved > policy.capacity {
+        return Err(ApiErrorResponder::validation_error_with_message("Reserved slots cannot exceed the capacity"));
+    };
+    Ok(JsonResponder::ok(update_gate_policies(state, &audit, set_policy("reservedSlots", &policy.0)?).await))
+}
+
+#[put("/ip-limit", format = "json", data = "<policy>")]
+pub async fn set_ip_limit(
+    state: &State<MarsAPIState>,
+    policy: Json<IpLimitPolicy>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<GatePolicies>, ApiErrorResponder> {
+    if policy.enabled && policy.max_accounts == 0 {
+        return Err(ApiErrorResponder::validation_error_with_message("At least one account per IP must be allowed"));
+    };
+    Ok(JsonResponder::ok(update_gate_policies(state, &audit, set_policy("ipLimit", &policy.0)?).await))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/gate", routes![
+        get_gate,
+        set_maintenance,
+        set_allowlist,
+        add_allowlist_player,
+        remove_allowlist_player,
+        set_reserved_slots,
+        set_ip_limit
+    ])
+}
@@ -12,3 +12,12 @@ pub mod tag;
 pub mod perks;
 pub mod r#match;
 pub mod achievements;
+pub mod privacy;
+pub mod friend;
+pub mod clan;
+pub mod currency;
+pub mod chat;
+pub mod appeal;
+pub mod audit;
+pub mod shared_ip;
+pub mod gate;
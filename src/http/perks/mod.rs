@@ -1,8 +1,9 @@
+use mongodb::bson::doc;
 use rocket::{Rocket, State, Build, serde::json::Json};
 
-use crate::{MarsAPIState, database::models::{join_sound::JoinSound, player::Player}, util::{auth::AuthorizationToken, responder::JsonResponder, error::ApiErrorResponder}};
+use crate::{MarsAPIState, http::currency::purchase_cosmetic, database::{Database, models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, cosmetic::{Cosmetic, CosmeticKind, CosmeticOwnership, CosmeticSource}, join_sound::JoinSound, player::Player, rank::Rank}}, util::{audit::AuditContext, auth::AuthorizationToken, responder::{EmptyResponse, JsonResponder}, error::ApiErrorResponder, r#macro::unwrap_helper, time::get_u64_time_millis}};
 
-use self::payload::JoinSoundSetRequest;
+use self::payload::{CosmeticEquipRequest, CosmeticGrantRequest, CosmeticInventoryResponse, CosmeticPurchaseRequest, CosmeticPurchaseResponse, JoinSoundSetRequest, OwnedCosmetic};
 
 mod payload;
 
@@ -20,25 +21,191 @@ async fn update_join_sound(
     set_join_req: Json<JoinSoundSetRequest>,
     _auth_guard: AuthorizationToken
 ) -> Result<JsonResponder<Player>, ApiErrorResponder> {
-    match state.player_cache.get(&state.database, player_id).await {
-        Some(mut p) => {
-            let current_sound = set_join_req.0.active_join_sound_id;
-            if p.active_join_sound_id == current_sound {
-                return Ok(JsonResponder::ok(p));
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let player = equip_cosmetic(state, player, CosmeticKind::JoinSound, set_join_req.0.active_join_sound_id).await?;
+    Ok(JsonResponder::ok(player))
+}
+
+#[get("/cosmetics")]
+fn get_cosmetics(
+    state: &State<MarsAPIState>
+) -> Json<&Vec<Cosmetic>> {
+    Json(&state.config.data.cosmetics)
+}
+
+#[get("/cosmetics/<player_id>")]
+async fn get_inventory(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<CosmeticInventoryResponse>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let ownerships = state.database.get_cosmetic_ownerships(&player.id).await;
+    let ranks = Rank::find_for_player(&state.database, &player).await;
+    let owned = state.config.data.cosmetics.iter().filter_map(|cosmetic| {
+        let source = get_cosmetic_source(state, &player, cosmetic, &ownerships)?;
+        Some(OwnedCosmetic { cosmetic_id: cosmetic.id.clone(), source, permitted: cosmetic.is_permitted(&ranks) })
+    }).collect();
+    let mut equipped = player.active_cosmetics.clone();
+    if let Some(join_sound_id) = get_equipped(&player, &CosmeticKind::JoinSound) {
+        equipped.insert(CosmeticKind::JoinSound, join_sound_id);
+    };
+    Ok(JsonResponder::ok(CosmeticInventoryResponse { owned, equipped }))
+}
+
+#[post("/cosmetics/<player_id>/grant", format = "json", data = "<grant_req>")]
+async fn grant_cosmetic(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    grant_req: Json<CosmeticGrantRequest>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<CosmeticOwnership>, ApiErrorResponder> {
+    let data = grant_req.0;
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let cosmetic = unwrap_helper::return_default!(find_cosmetic(state, &data.cosmetic_id), Err(ApiErrorResponder::cosmetic_missing()));
+    // other sources are derived from the player's profile, and purchases need a spend in the ledger
+    if data.source.map(|source| source != CosmeticSource::Grant).unwrap_or(false) {
+        return Err(ApiErrorResponder::validation_error_with_message("Ownership can only be granted as GRANT"));
+    };
+    let ownership = CosmeticOwnership {
+        id: CosmeticOwnership::make_id(&player.id, &cosmetic.id),
+        player_id: player.id.clone(),
+        cosmetic_id: cosmetic.id.clone(),
+        source: CosmeticSource::Grant,
+        granted_by: data.granted_by,
+        created_at: get_u64_time_millis()
+    };
+    // the id is derived from the player and cosmetic, so an existing or concurrent grant fails the insert
+    if state.database.cosmetic_ownerships.insert_one(&ownership, None).await.is_err() {
+        return Err(ApiErrorResponder::cosmetic_already_owned());
+    };
+    audit.acting_as(ownership.granted_by.as_ref()).record_created(
+        state, AuditAction::CosmeticGrant, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &ownership
+    ).await;
+    Ok(JsonResponder::created(ownership))
+}
+
+#[post("/cosmetics/<player_id>/purchase", format = "json", data = "<purchase_req>")]
+async fn purchase(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    purchase_req: Json<CosmeticPurchaseRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<CosmeticPurchaseResponse>, ApiErrorResponder> {
+    let data = purchase_req.0;
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let cosmetic = unwrap_helper::return_default!(find_cosmetic(state, &data.cosmetic_id), Err(ApiErrorResponder::cosmetic_missing()));
+    let price = unwrap_helper::return_default!(
+        cosmetic.purchase_price(),
+        Err(ApiErrorResponder::validation_error_with_message("The cosmetic cannot be purchased"))
+    );
+    if get_cosmetic_source(state, &player, cosmetic, &[]).is_some() {
+        return Err(ApiErrorResponder::cosmetic_already_owned());
+    };
+    let (ownership, balance) = purchase_cosmetic(state, player, &cosmetic.id, price, data.idempotency_key).await?;
+    Ok(JsonResponder::created(CosmeticPurchaseResponse { ownership, balance }))
+}
+
+#[delete("/cosmetics/<player_id>/<cosmetic_id>")]
+async fn revoke_cosmetic(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    cosmetic_id: &str,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<EmptyResponse>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let id = CosmeticOwnership::make_id(&player.id, cosmetic_id);
+    let ownership = unwrap_helper::return_default!(
+        state.database.cosmetic_ownerships.find_one_and_delete(doc! { "_id": &id }, None).await.ok().flatten(),
+        Err(ApiErrorResponder::cosmetic_missing())
+    );
+    audit.record_deleted(
+        state, AuditAction::CosmeticRevoke, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &ownership
+    ).await;
+
+    // unequip unless the player still has the item through another source
+    if let Some(cosmetic) = find_cosmetic(state, cosmetic_id) {
+        let equipped = get_equipped(&player, &cosmetic.kind).as_ref() == Some(&cosmetic.id);
+        if equipped && get_cosmetic_source(state, &player, cosmetic, &[]).is_none() {
+            equip_cosmetic(state, player, cosmetic.kind.clone(), None).await?;
+        };
+    };
+    Ok(JsonResponder::ok(EmptyResponse {}))
+}
+
+#[put("/cosmetics/<player_id>/equip", format = "json", data = "<equip_req>")]
+async fn equip(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    equip_req: Json<CosmeticEquipRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Player>, ApiErrorResponder> {
+    let data = equip_req.0;
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let player = equip_cosmetic(state, player, data.kind, data.cosmetic_id).await?;
+    Ok(JsonResponder::ok(player))
+}
+
+fn find_cosmetic<'a>(state: &'a MarsAPIState, cosmetic_id: &str) -> Option<&'a Cosmetic> {
+    state.config.data.cosmetics.iter().find(|cosmetic| cosmetic.id == cosmetic_id)
+}
+
+fn get_cosmetic_source(state: &MarsAPIState, player: &Player, cosmetic: &Cosmetic, ownerships: &[CosmeticOwnership]) -> Option<CosmeticSource> {
+    if let Some(ownership) = ownerships.iter().find(|ownership| ownership.cosmetic_id == cosmetic.id) {
+        return Some(ownership.source.clone());
+    };
+    cosmetic.get_derived_unlock(player, player.stats.get_level(state.config.options.use_exponential_exp))
+}
+
+fn get_equipped(player: &Player, kind: &CosmeticKind) -> Option<String> {
+    // servers still read join sounds from the dedicated field
+    match kind {
+        CosmeticKind::JoinSound => player.active_join_sound_id.clone(),
+        _ => player.active_cosmetics.get(kind).cloned()
+    }
+}
+
+// passing no cosmetic clears the slot
+async fn equip_cosmetic(state: &MarsAPIState, mut player: Player, kind: CosmeticKind, cosmetic_id: Option<String>) -> Result<Player, ApiErrorResponder> {
+    if get_equipped(&player, &kind) == cosmetic_id {
+        return Ok(player);
+    };
+    match &cosmetic_id {
+        Some(cosmetic_id) => {
+            let cosmetic = unwrap_helper::return_default!(find_cosmetic(state, cosmetic_id), Err(ApiErrorResponder::cosmetic_missing()));
+            if cosmetic.kind != kind {
+                return Err(ApiErrorResponder::validation_error_with_message("Cosmetic cannot be equipped in that slot"));
+            };
+            let ownership = Database::find_by_id(&state.database.cosmetic_ownerships, &CosmeticOwnership::make_id(&player.id, &cosmetic.id)).await;
+            let ownerships : Vec<CosmeticOwnership> = ownership.into_iter().collect();
+            if get_cosmetic_source(state, &player, cosmetic, &ownerships).is_none() {
+                return Err(ApiErrorResponder::cosmetic_not_owned());
             };
-            p.active_join_sound_id = current_sound;
-            state.player_cache.set(&state.database, &p.name, &p, true).await;
-            Ok(JsonResponder::ok(p))
+            if !cosmetic.is_permitted(&Rank::find_for_player(&state.database, &player).await) {
+                return Err(ApiErrorResponder::cosmetic_not_permitted());
+            };
+            player.active_cosmetics.insert(kind.clone(), cosmetic.id.clone());
         },
-        None => {
-            Err(ApiErrorResponder::missing_player())
-        }
-    }
+        None => { player.active_cosmetics.remove(&kind); }
+    };
+    if kind == CosmeticKind::JoinSound {
+        player.active_join_sound_id = cosmetic_id;
+    };
+    state.player_cache.set(&state.database, &player.name, &player, true).await;
+    Ok(player)
 }
 
 pub fn mount(rocket_build: Rocket<Build>, state: &MarsAPIState) -> Rocket<Build> {
     rocket_build.mount("/mc/perks", routes![
         get_join_sounds,
-        update_join_sound
+        update_join_sound,
+        get_cosmetics,
+        get_inventory,
+        grant_cosmetic,
+        purchase,
+        revoke_cosmetic,
+        equip
     ])
 }
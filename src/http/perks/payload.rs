@@ -1,7 +1,56 @@
+use std::collections::HashMap;
+
 use serde::{Serialize, Deserialize};
 
+use crate::database::models::{cosmetic::{CosmeticKind, CosmeticOwnership, CosmeticSource}, player::SimplePlayer};
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JoinSoundSetRequest {
     pub active_join_sound_id: Option<String>
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CosmeticGrantRequest {
+    pub cosmetic_id: String,
+    // only GRANT is accepted, purchases go through the currency routes so they carry a ledger spend
+    pub source: Option<CosmeticSource>,
+    pub granted_by: Option<SimplePlayer>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CosmeticEquipRequest {
+    pub kind: CosmeticKind,
+    pub cosmetic_id: Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CosmeticPurchaseRequest {
+    pub cosmetic_id: String,
+    pub idempotency_key: Option<String>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CosmeticPurchaseResponse {
+    pub ownership: CosmeticOwnership,
+    pub balance: i64
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnedCosmetic {
+    pub cosmetic_id: String,
+    pub source: CosmeticSource,
+    pub permitted: bool
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CosmeticInventoryResponse {
+    pub owned: Vec<OwnedCosmetic>,
+    pub equipped: HashMap<CosmeticKind, String>
+}
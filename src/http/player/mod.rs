@@ -5,21 +5,20 @@ use mongodb::bson::doc;
 use payloads::PlayerPreLoginRequest;
 use rocket::{serde::json::Json, Build, Rocket, State, http::Status};
 use uuid::Uuid;
-use crate::{util::{auth::AuthorizationToken, error::{ApiError, ApiErrorResponder}, string::to_utf8_byte_array, responder::{JsonResponder, EmptyResponse}, time::get_u64_time_millis, r#macro::unwrap_helper}, MarsAPIState, database::{Database, models::{punishment::{Punishment, PunishmentKind, StaffNote}, player::{Player, PlayerStats, SessionRecord}, session::Session, rank::Rank, tag::Tag}}, http::player::payloads::{PlayerLoginRequest, PlayerLookupResponse, PlayerAddNoteRequest, PlayerSetActiveTagRequest}, socket::leaderboard::{Leaderboard, ScoreType, LeaderboardPeriod}};
-use sha2::{Sha256, Digest};
+use crate::{util::{audit::AuditContext, auth::AuthorizationToken, error::{ApiError, ApiErrorResponder}, string::{levenshtein_distance, is_valid_player_name_query, name_grams, query_name_grams}, responder::{JsonResponder, EmptyResponse}, time::get_u64_time_millis, r#macro::unwrap_helper}, MarsAPIState, database::{Database, models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, punishment::{Punishment, PunishmentKind, PunishmentReason, PunishmentType, StaffNote}, player::{Player, PlayerStats, SessionRecord}, session::Session, level::LevelGamemode, rank::Rank, tag::Tag, preference::{PlayerPreferences, PreferenceValue}}}, http::player::payloads::{PlayerLoginRequest, PlayerLookupResponse, PlayerAddNoteRequest, PlayerSetActiveTagRequest}, socket::{leaderboard::{Leaderboard, ScoreType, LeaderboardPeriod}, player::{ban_evasion::{check_ban_evasion, BAN_EVASION_KICK_MESSAGE}, player_context::send_player_update_to_online_player, presence::{clear_presence, get_presence, set_presence, PlayerPresence}, direct_message::deliver_offline_messages}, update::player_update_listener::{PlayerUpdateData, PlayerUpdateReason}}};
 
-use self::payloads::{PlayerPreLoginResponse, PlayerPreLoginResponder, PlayerLoginResponse, PlayerLogoutRequest, PlayerProfileResponder, PlayerProfileResponse, PlayerAltResponse};
-use std::{time::{SystemTime, UNIX_EPOCH}, collections::HashMap};
-use crate::database::models::ip_identity::IpIdentity;
+use self::payloads::{PlayerPreLoginResponse, PlayerPreLoginResponder, PlayerLoginResponse, PlayerLogoutRequest, PlayerProfileResponder, PlayerProfileResponse, PlayerAltResponse, PlayerSearchResult, PlayerBatchRequest, PlayerStatHistoryPoint, PlayerPreferencesUpdateRequest, PlayerPresenceResponse};
+use std::{time::{SystemTime, UNIX_EPOCH}, collections::HashMap, str::FromStr};
+use crate::database::models::ip_identity::{AltGraphBounds, IpIdentity};
 
-use super::punishment::payloads::PunishmentIssueRequest;
+use super::{friend::notify_friends_of_login, gate::evaluate_gate, punishment::payloads::{PunishmentIssueRequest, PunishmentPreviewResponse}};
 
 #[post("/<player_id>/prelogin", format = "json", data = "<prelogin_req>")]
 pub async fn prelogin(
     state: &State<MarsAPIState>, 
     prelogin_req: Json<PlayerPreLoginRequest>, 
     player_id: &str, 
-    _auth_guard: AuthorizationToken
+    auth_guard: AuthorizationToken
 ) -> Result<PlayerPreLoginResponder, ApiErrorResponder> {
     let data = prelogin_req.0;
 
@@ -28,10 +27,18 @@ pub async fn prelogin(
     };
 
     let ip = hash_ip(&state, &data.ip);
+    let ip_candidates = state.config.ip_hasher.candidates(&data.ip);
+    // forms of this ip stored under older hash keys, empty once dual-read is off
+    let stale_ips : Vec<&String> = ip_candidates.iter().filter(|candidate| *candidate != &ip).collect();
+    for stale_ip in stale_ips.iter() {
+        IpIdentity::rekey(&state.database, stale_ip, &ip).await;
+    }
+    let shared_ip = state.database.get_shared_ip_list(&state.config.ip_hasher).await.is_shared(&data.ip, &ip_candidates);
+    let identity_cap = if shared_ip { Some(state.config.options.shared_ip_max_identity_players) } else { None };
     let player_optional = Database::find_by_id(&state.database.players, &data.player.id).await;
     if let Some(mut returning_player) = player_optional {
-        returning_player.name = data.player.name.clone();
-        returning_player.name_lower = returning_player.name.to_lowercase();
+        returning_player.record_name_change(&data.player.name);
+        returning_player.ips.retain(|stored| !stale_ips.contains(&stored));
         let new_ip = !returning_player.ips.contains(&ip);
         if new_ip {
             returning_player.ips.push(ip.clone());
@@ -43,9 +50,12 @@ pub async fn prelogin(
             let is_player_banned = ban_pun_optional.is_some();
             if is_player_banned {
                 true
+            } else if shared_ip {
+                // everyone behind a shared ip would otherwise inherit its bans
+                false
             } else {
                 let mut ip_punishments : Vec<Punishment> = match state.database.punishments.find(doc! {
-                    "targetIps": &ip,
+                    "targetIps": { "$in": &ip_candidates },
                     "action.kind": PunishmentKind::IpBan.to_string()
                 }, None).await {
                     Ok(cursor) => Database::consume_cursor_into_owning_vec(cursor).await,
@@ -60,19 +70,25 @@ pub async fn prelogin(
                 is_ip_banned
             }
         };
+        // accounts sharing the ip may be serving a ban
+        let evading = !banned && !shared_ip && check_ban_evasion(state, &auth_guard.server_id, &data.player, &ip, false).await;
         state.player_cache.set(&state.database, &returning_player.name, &returning_player, true).await;
         state.database.ensure_player_name_uniqueness(&data.player.name, &data.player.id).await;
-        // denormalize ip player relationship
-        if new_ip {
-            IpIdentity::add_player_ip(&state.database, &ip,  &returning_player.id).await;
-        }
+        // denormalize ip player relationship, also refreshing when the ip was last seen
+        IpIdentity::add_player_ip(&state.database, &ip,  &returning_player.id, identity_cap).await;
 
+        let kick_message = if banned { None } 
+            else if evading { Some(BAN_EVASION_KICK_MESSAGE.to_owned()) } 
+            else { evaluate_gate(state, &returning_player, &ip_candidates, shared_ip).await };
+        let preferences = get_resolved_preferences(state, &returning_player.id).await;
         Ok(PlayerPreLoginResponder { 
             response: PlayerPreLoginResponse {
                 new: false, 
-                allowed: !banned,
+                allowed: !banned && kick_message.is_none(),
                 player: returning_player, 
-                active_punishments: puns 
+                active_punishments: puns,
+                preferences,
+                kick_message
             }
         })
     } else {
@@ -92,24 +108,149 @@ pub async fn prelogin(
             gamemode_stats: HashMap::new(),
             notes: Vec::new(),
             last_session_id: None,
-            active_join_sound_id: None
+            active_join_sound_id: None,
+            past_names: Vec::new(),
+            clan_id: None,
+            active_cosmetics: HashMap::new(),
+            ignored_player_ids: Vec::new(),
+            name_grams: name_grams(&data.player.name.to_lowercase())
         };
 
+        let evading = !shared_ip && check_ban_evasion(state, &auth_guard.server_id, &data.player, &ip, true).await;
         state.player_cache.set(&state.database, &player.name, &player, true).await;
         state.database.ensure_player_name_uniqueness(&data.player.name, &data.player.id).await;
-        IpIdentity::add_player_ip(&state.database, &ip, &data.player.id).await;
+        IpIdentity::add_player_ip(&state.database, &ip, &data.player.id, identity_cap).await;
 
+        let kick_message = if evading { Some(BAN_EVASION_KICK_MESSAGE.to_owned()) } 
+            else { evaluate_gate(state, &player, &ip_candidates, shared_ip).await };
         Ok(PlayerPreLoginResponder {
             response: PlayerPreLoginResponse {
                 new: true,
-                allowed: true,
+                allowed: kick_message.is_none(),
                 player,
-                active_punishments: Vec::new()
+                active_punishments: Vec::new(),
+                preferences: PlayerPreferences::resolve(&state.config.data.preferences, None),
+                kick_message
             }
         })
     }
 }
 
+const SEARCH_CANDIDATE_LIMIT : i64 = 250;
+
+#[get("/search?<q>&<limit>")]
+pub async fn search_players(
+    state: &State<MarsAPIState>,
+    q: &str,
+    limit: Option<usize>
+) -> Result<JsonResponder<Vec<PlayerSearchResult>>, ApiErrorResponder> {
+    let query = q.trim().to_lowercase();
+    if !is_valid_player_name_query(&query) {
+        return Err(ApiErrorResponder::validation_error_with_message("Search query must be a valid player name prefix"));
+    };
+    let limit = limit.unwrap_or(10).min(50);
+
+    // (tier, distance, result) where lower tiers rank first: exact, prefix, fuzzy
+    let mut ranked : Vec<(u8, usize, PlayerSearchResult)> = Vec::new();
+    // exact matches are looked up on their own, the prefix window may not reach them
+    let mut candidates = state.database.find_players_by_exact_name(&query).await;
+    for candidate in state.database.find_players_by_name_prefix(&query, SEARCH_CANDIDATE_LIMIT).await {
+        if !candidates.iter().any(|existing| existing.id == candidate.id) {
+            candidates.push(candidate);
+        };
+    }
+    for candidate in candidates {
+        let past_match = candidate.past_names.iter()
+            .find(|past| past.name_lower.starts_with(&query))
+            .map(|past| past.name.clone());
+        let tier = if candidate.name_lower == query { 0 } else { 1 };
+        let matched_name = if candidate.name_lower.starts_with(&query) { None } else { past_match };
+        ranked.push((tier, 0, PlayerSearchResult {
+            player: candidate.to_simple(),
+            last_seen_at: candidate.last_joined_at,
+            matched_name
+        }));
+    }
+
+    // fall back to typo-tolerant matching when the prefix search comes up short, candidates come
+    // from the name gram index: each edit breaks at most two grams, so a match within the allowed
+    // distance shares at least all but that many with the query
+    if ranked.len() < limit && query.len() >= 3 {
+        let max_distance = if query.len() <= 5 { 1 } else { 2 };
+        let grams = query_name_grams(&query);
+        let min_shared = grams.len().saturating_sub(max_distance * 2).max(1);
+        let fuzzy_candidates = state.database.find_players_by_name_grams(&grams, min_shared, SEARCH_CANDIDATE_LIMIT).await;
+        for candidate in fuzzy_candidates {
+            if ranked.iter().any(|(_, _, result)| result.player.id == candidate.id) {
+                continue;
+            };
+            let truncated : String = candidate.name_lower.chars().take(query.len()).collect();
+            let distance = levenshtein_distance(&query, &truncated).min(levenshtein_distance(&query, &candidate.name_lower));
+            if distance > max_distance {
+                continue;
+            };
+            ranked.push((2, distance, PlayerSearchResult {
+                player: candidate.to_simple(),
+                last_seen_at: candidate.last_joined_at,
+                matched_name: None
+            }));
+        }
+    };
+
+    ranked.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(a.1.cmp(&b.1))
+            .then(b.2.last_seen_at.partial_cmp(&a.2.last_seen_at).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    let results = ranked.into_iter().take(limit).map(|(_, _, result)| result).collect();
+    Ok(JsonResponder::ok(results))
+}
+
+const BATCH_FETCH_LIMIT : usize = 500;
+
+#[post("/batch", format = "json", data = "<batch_req>")]
+pub async fn batch_players(
+    state: &State<MarsAPIState>,
+    batch_req: Json<PlayerBatchRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<HashMap<String, Player>>, ApiErrorResponder> {
+    let mut keys : Vec<String> = Vec::new();
+    for key in batch_req.0.players.into_iter() {
+        if !keys.iter().any(|existing| existing.eq_ignore_ascii_case(&key)) {
+            keys.push(key);
+        };
+    }
+    if keys.len() > BATCH_FETCH_LIMIT {
+        return Err(ApiErrorResponder::validation_error_with_message(&format!("At most {} players can be fetched at once", BATCH_FETCH_LIMIT)));
+    };
+
+    let mut profiles : HashMap<String, Player> = HashMap::new();
+    let mut misses : Vec<String> = Vec::new();
+    for (key, cached) in keys.iter().zip(state.player_cache.query_many(&keys).await) {
+        match cached {
+            Some(player) => { profiles.insert(player.id.clone(), player.sanitized_copy()); },
+            None => misses.push(key.clone())
+        };
+    }
+
+    // misses are mostly ids, while the cache is keyed by name, so look again by name
+    // in case the player is online with progress that has not been persisted yet
+    let fetched = state.database.find_players_by_ids_or_names(&misses).await;
+    let names : Vec<String> = fetched.iter().map(|player| player.name.clone()).collect();
+    let mut backfill : Vec<(String, &Player)> = Vec::new();
+    for (player, cached) in fetched.iter().zip(state.player_cache.query_many(&names).await) {
+        match cached {
+            Some(cached) => { profiles.insert(cached.id.clone(), cached.sanitized_copy()); },
+            None => {
+                backfill.push((player.name.clone(), player));
+                profiles.insert(player.id.clone(), player.sanitized_copy());
+            }
+        };
+    }
+    state.player_cache.set_many_if_absent(&backfill).await;
+    Ok(JsonResponder::ok(profiles))
+}
+
 macro_rules! extract_player_from_url {
     ( $e:expr, $s:expr ) => {
         if let Some(player) = ($s).player_cache.get(&($s).database, ($e)).await { player } 
@@ -167,8 +308,20 @@ pub async fn login(
     player.last_session_id = Some(active_session.id.clone());
 
     state.player_cache.set(&state.database, &player.name, &player, true).await;
+    set_presence(state, &player.id, &PlayerPresence::from(&active_session)).await;
+    {
+        // take ownership for the spawned task
+        let state_clone = state.inner().clone();
+        let player_clone = player.clone();
+        let server_id = active_session.server_id.clone();
+        tokio::spawn(async move {
+            notify_friends_of_login(&state_clone, &player_clone).await;
+            deliver_offline_messages(&state_clone, &player_clone.id, &server_id).await;
+        });
+    }
 
-    Ok(JsonResponder::from(PlayerLoginResponse { active_session }, Status::Created))
+    let preferences = get_resolved_preferences(state, &player.id).await;
+    Ok(JsonResponder::from(PlayerLoginResponse { active_session, preferences }, Status::Created))
 }
 
 
@@ -206,6 +359,7 @@ pub async fn logout(
 
     state.database.save(&session).await;
     state.player_cache.set(&state.database, &player.name, &player, true).await;
+    clear_presence(state, &player.id, &session.id).await;
 
     Ok(JsonResponder::ok(EmptyResponse {}))
 }
@@ -264,9 +418,14 @@ pub async fn profile(
     join_all(lb_position_tasks).await.into_iter().filter(|pos_opt| pos_opt.1.is_some()).for_each(|pos| {
         positions.insert(pos.0, pos.1.unwrap());
     });
+    let clan_tag = match &player.clan_id {
+        Some(clan_id) => Database::find_by_id(&state.database.clans, clan_id).await.map(|clan| clan.tag),
+        None => None
+    };
     Ok(PlayerProfileResponder::ProfileWithLeaderboardPositions(PlayerProfileResponse {
         player: profile,
-        leaderboard_positions: positions
+        leaderboard_positions: positions,
+        clan_tag
     }))
 }
 
@@ -277,27 +436,48 @@ pub async fn issue_punishment(
     state: &State<MarsAPIState>, 
     pun_issue_req: Json<PunishmentIssueRequest>,
     _player_id: &str,
-    auth_guard: AuthorizationToken
+    auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<Punishment>, ApiErrorResponder> {
     let data = pun_issue_req.0;
     let punishment_id = Uuid::new_v4().to_string();
     let time_millis : u64 = u64::try_from(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()).unwrap_or(u64::MAX);
     let target_player : Player = async_extract_player_from_url_v2!(&data.target_name, state);
+    // servers can disagree about prior offences, so the ladder is walked here instead
+    let (reason, offence, action) = match state.config.data.punishment_types.iter().find(|punishment_type| punishment_type.name == data.reason.name) {
+        Some(punishment_type) => {
+            let preview = unwrap_helper::return_default!(
+                preview_punishment_type(state, &target_player, punishment_type).await, 
+                Err(ApiErrorResponder::validation_error_with_message("The punishment type has no actions"))
+            );
+            (preview.reason, preview.offence, preview.action)
+        },
+        None => match (data.offence, data.action) {
+            (Some(offence), Some(action)) => (data.reason, offence, action),
+            _ => return Err(ApiErrorResponder::validation_error_with_message("An offence and action are required for unknown punishment types"))
+        }
+    };
     let punishment = Punishment { 
         id: punishment_id, 
-        reason: data.reason, 
+        reason, 
         issued_at: time_millis as f64, 
         silent: data.silent, 
-        offence: data.offence, 
-        action: data.action, 
+        offence, 
+        action, 
         note: data.note, 
         punisher: data.punisher, 
         target: target_player.to_simple(), 
-        target_ips: data.target_ips, 
+        target_ips: state.database.without_shared_ips(&state.config.ip_hasher, data.target_ips).await, 
         reversion: None, 
-        server_id: Some(auth_guard.server_id)
+        server_id: Some(auth_guard.server_id),
+        message_ids: data.message_ids,
+        edits: Vec::new(),
+        expired_at: None
     };
     state.database.insert_one(&punishment).await;
+    audit.acting_as(punishment.punisher.as_ref()).record_created(
+        state, AuditAction::PunishmentIssue, AuditTarget::new(AuditTargetKind::Punishment, &punishment.id, &punishment.target.name), &punishment
+    ).await;
     {
         // take ownership for the spawned task
         let pun_clone = punishment.clone();
@@ -310,6 +490,32 @@ pub async fn issue_punishment(
 }
 
 
+#[get("/<player_id>/punishments/preview?<reason>")]
+pub async fn preview_punishment(
+    state: &State<MarsAPIState>, 
+    player_id: &str,
+    reason: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<PunishmentPreviewResponse>, ApiErrorResponder> {
+    let player : Player = async_extract_player_from_url_v2!(&player_id, state);
+    let punishment_type = unwrap_helper::return_default!(
+        state.config.data.punishment_types.iter().find(|punishment_type| punishment_type.name == reason), 
+        Err(ApiErrorResponder::validation_error_with_message("Unknown punishment type"))
+    );
+    let preview = unwrap_helper::return_default!(
+        preview_punishment_type(state, &player, punishment_type).await, 
+        Err(ApiErrorResponder::validation_error_with_message("The punishment type has no actions"))
+    );
+    Ok(JsonResponder::ok(preview))
+}
+
+async fn preview_punishment_type(state: &MarsAPIState, player: &Player, punishment_type: &PunishmentType) -> Option<PunishmentPreviewResponse> {
+    let prior_offences = state.database.get_prior_offences(&player.id, punishment_type, state.config.options.punishment_offence_decay_days).await;
+    let offence = prior_offences.len() as u32 + 1;
+    let action = punishment_type.get_action_for_offence(offence)?.clone();
+    Some(PunishmentPreviewResponse { reason: PunishmentReason::from(punishment_type), offence, action, prior_offences })
+}
+
 #[get("/<player_id>/punishments")]
 pub async fn get_punishments(
     state: &State<MarsAPIState>, 
@@ -320,47 +526,200 @@ pub async fn get_punishments(
     Ok(JsonResponder::created(state.database.get_player_punishments(&player).await))
 }
 
-pub fn hash_ip(state: &MarsAPIState, digest: &String) -> String {
-    if state.config.options.enable_ip_hashing { sha256_hash_formatted(digest) } 
-    else { digest.clone() }
+// thirty days
+const STAT_HISTORY_DEFAULT_RANGE : u64 = 2_592_000_000;
+
+#[get("/<player_id>/stats/history?<from>&<to>&<gamemode>")]
+pub async fn get_stat_history(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    from: Option<u64>,
+    to: Option<u64>,
+    gamemode: Option<String>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Vec<PlayerStatHistoryPoint>>, ApiErrorResponder> {
+    let gamemode = match gamemode {
+        Some(gamemode) => Some(unwrap_helper::result_return_default!(
+            LevelGamemode::from_str(&gamemode.to_uppercase()), 
+            Err(ApiErrorResponder::validation_error_with_message("Unknown gamemode"))
+        )),
+        None => None
+    };
+    let to = to.unwrap_or_else(get_u64_time_millis);
+    let from = from.unwrap_or(to.saturating_sub(STAT_HISTORY_DEFAULT_RANGE));
+    if from > to {
+        return Err(ApiErrorResponder::validation_error_with_message("'from' must not be after 'to'"));
+    };
+    let player : Player = async_extract_player_from_url_v2!(&player_id, state);
+    let snapshots = state.database.get_player_stat_history(&player.id, from, to).await;
+    let points = snapshots.into_iter().filter_map(|mut snapshot| {
+        let (totals, delta) = match &gamemode {
+            Some(gamemode) => (
+                snapshot.gamemode_totals.remove(gamemode)?, 
+                snapshot.gamemode_deltas.remove(gamemode).unwrap_or_default()
+            ),
+            None => (snapshot.totals, snapshot.delta)
+        };
+        Some(PlayerStatHistoryPoint { date: snapshot.date, baseline: snapshot.baseline, totals, delta })
+    }).collect();
+    Ok(JsonResponder::ok(points))
 }
 
-pub fn sha256_hash_formatted(digest: &String) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(to_utf8_byte_array(digest));
-    hasher.finalize().iter().fold(String::from(""), |mut hex_str, elem| {
-        let formatted = format!("{:02x}", elem);
-        hex_str.push_str(&formatted);
-        hex_str
-    })
+#[put("/<player_id>/preferences", format = "json", data = "<preferences_req>")]
+pub async fn update_preferences(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    preferences_req: Json<PlayerPreferencesUpdateRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<HashMap<String, PreferenceValue>>, ApiErrorResponder> {
+    let player : Player = async_extract_player_from_url_v2!(&player_id, state);
+    let definitions = &state.config.data.preferences;
+    let mut stored = state.database.get_player_preferences(&player.id).await.unwrap_or(PlayerPreferences {
+        player_id: player.id.clone(),
+        values: HashMap::new(),
+        updated_at: 0
+    });
+    for (key, value) in preferences_req.0.preferences.into_iter() {
+        let definition = unwrap_helper::return_default!(
+            definitions.iter().find(|definition| definition.key == key),
+            Err(ApiErrorResponder::validation_error_with_message(&format!("Unknown preference '{}'", key)))
+        );
+        match value {
+            Some(value) => {
+                if !definition.accepts(&value) {
+                    return Err(ApiErrorResponder::validation_error_with_message(&format!("Invalid value for preference '{}'", key)));
+                };
+                stored.values.insert(key, value);
+            },
+            None => { stored.values.remove(&key); }
+        };
+    }
+    stored.updated_at = get_u64_time_millis();
+    state.database.save(&stored).await;
+
+    let preferences = PlayerPreferences::resolve(definitions, Some(&stored));
+    send_player_update_to_online_player(
+        state,
+        player,
+        PlayerUpdateData::PreferencesUpdateData { preferences: preferences.clone() },
+        PlayerUpdateReason::Preferences
+    ).await;
+    Ok(JsonResponder::ok(preferences))
 }
 
-#[get("/<player_id>/lookup?<alts>")]
+#[get("/<player_id>/presence")]
+pub async fn get_player_presence(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<PlayerPresenceResponse>, ApiErrorResponder> {
+    let player : Player = async_extract_player_from_url_v2!(&player_id, state);
+    let presence = get_presence(state, &player.id).await;
+    Ok(JsonResponder::ok(PlayerPresenceResponse {
+        player: player.to_simple(),
+        online: presence.is_some(),
+        server_id: presence.as_ref().map(|presence| presence.server_id.clone()),
+        since: presence.map(|presence| presence.since)
+    }))
+}
+
+const MAX_IGNORED_PLAYERS : usize = 500;
+
+#[put("/<player_id>/ignores/<target_id>")]
+pub async fn ignore_player(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    target_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Vec<String>>, ApiErrorResponder> {
+    let mut player : Player = async_extract_player_from_url_v2!(&player_id, state);
+    let target : Player = async_extract_player_from_url_v2!(&target_id, state);
+    if player.id == target.id {
+        return Err(ApiErrorResponder::validation_error_with_message("Players cannot ignore themselves"));
+    };
+    if !player.ignored_player_ids.contains(&target.id) {
+        if player.ignored_player_ids.len() >= MAX_IGNORED_PLAYERS {
+            return Err(ApiErrorResponder::validation_error_with_message(&format!("At most {} players can be ignored", MAX_IGNORED_PLAYERS)));
+        };
+        player.ignored_player_ids.push(target.id);
+        state.player_cache.set(&state.database, &player.name, &player, true).await;
+    };
+    Ok(JsonResponder::ok(player.ignored_player_ids))
+}
+
+#[delete("/<player_id>/ignores/<target_id>")]
+pub async fn unignore_player(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    target_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Vec<String>>, ApiErrorResponder> {
+    let mut player : Player = async_extract_player_from_url_v2!(&player_id, state);
+    // the target may have been erased or renamed, so accept a raw id as well
+    let target_id = match state.player_cache.get(&state.database, target_id).await {
+        Some(target) => target.id,
+        None => target_id.to_owned()
+    };
+    if !player.ignored_player_ids.contains(&target_id) {
+        return Err(ApiErrorResponder::validation_error_with_message("The player is not ignored"));
+    };
+    player.ignored_player_ids.retain(|ignored_id| ignored_id != &target_id);
+    state.player_cache.set(&state.database, &player.name, &player, true).await;
+    Ok(JsonResponder::ok(player.ignored_player_ids))
+}
+
+async fn get_resolved_preferences(state: &MarsAPIState, player_id: &str) -> HashMap<String, PreferenceValue> {
+    let stored = state.database.get_player_preferences(player_id).await;
+    PlayerPreferences::resolve(&state.config.data.preferences, stored.as_ref())
+}
+
+pub fn hash_ip(state: &MarsAPIState, digest: &String) -> String {
+    state.config.ip_hasher.hash(digest)
+}
+
+#[get("/<player_id>/lookup?<alts>&<depth>")]
 pub async fn lookup_player(
     state: &State<MarsAPIState>, 
     player_id: &str,
     alts: bool,
+    depth: Option<u32>,
     _auth_guard: AuthorizationToken
 ) -> Result<JsonResponder<PlayerLookupResponse>, ApiErrorResponder> {
     let player : Player = async_extract_player_from_url_v2!(&player_id, state);
-    let player_alts : Vec<PlayerAltResponse> = {
+    let options = &state.config.options;
+    let (player_alts, skipped_ips, truncated) : (Vec<PlayerAltResponse>, Vec<String>, bool) = {
         let mut player_alts : Vec<PlayerAltResponse> = Vec::new();
+        let mut skipped_ips : Vec<String> = Vec::new();
+        let mut truncated = false;
         if alts {
+            let bounds = AltGraphBounds {
+                depth: depth.unwrap_or(1).clamp(1, options.alt_lookup_max_depth.max(1)),
+                max_ip_fanout: options.alt_lookup_max_ip_fanout,
+                max_accounts: options.alt_lookup_max_accounts
+            };
             let t1 = get_u64_time_millis();
-            let fetched_alts = state.database.get_alts_for_player(&player).await;
+            let graph = state.database.get_alt_graph(&player, &bounds, &state.config.ip_hasher).await;
             let t2 = get_u64_time_millis();
-            debug!("Alt lookup for {} took {}ms", &player.name, (t2 - t1));
-            let pun_tasks : Vec<_> = fetched_alts.iter().map(|alt| {
-                state.database.get_player_punishments(alt)
+            debug!("Alt lookup for {} (depth {}) took {}ms", &player.name, bounds.depth, (t2 - t1));
+            let pun_tasks : Vec<_> = graph.accounts.iter().map(|alt| {
+                state.database.get_player_punishments(&alt.player)
             }).collect();
             let alt_puns = join_all(pun_tasks).await;
-            for (alt, puns) in fetched_alts.into_iter().zip(alt_puns) {
-                player_alts.push(PlayerAltResponse { player: alt, punishments: puns });
+            for (alt, puns) in graph.accounts.into_iter().zip(alt_puns) {
+                player_alts.push(PlayerAltResponse { 
+                    player: alt.player, 
+                    punishments: puns, 
+                    depth: alt.depth, 
+                    links: alt.links, 
+                    confidence: alt.confidence 
+                });
             }
+            skipped_ips = graph.skipped_ips;
+            truncated = graph.truncated;
         };
-        player_alts
+        (player_alts, skipped_ips, truncated)
     };
-    Ok(JsonResponder::created(PlayerLookupResponse { player, alts: player_alts }))
+    Ok(JsonResponder::created(PlayerLookupResponse { player, alts: player_alts, skipped_ips, truncated }))
 }
 
 #[post("/<player_id>/notes", format = "json", data = "<add_note_req>")]
@@ -368,7 +727,8 @@ pub async fn add_player_note(
     state: &State<MarsAPIState>, 
     player_id: &str,
     add_note_req: Json<PlayerAddNoteRequest>,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<Player>, ApiErrorResponder> {
     let data = add_note_req.0;
     let mut player : Player = async_extract_player_from_url_v2!(&player_id, state);
@@ -377,6 +737,9 @@ pub async fn add_player_note(
     let note_clone = note.clone();
     player.notes.push(note);
     state.player_cache.set(&state.database, player_id, &player, true).await;
+    audit.acting_as(Some(&note_clone.author)).record_created(
+        state, AuditAction::NoteAdd, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &note_clone
+    ).await;
     {
         // take ownership for the spawned task
         let state_clone = state.config.clone();
@@ -393,13 +756,15 @@ pub async fn delete_player_note(
     state: &State<MarsAPIState>, 
     player_id: &str,
     note_id: u32,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<Player>, ApiErrorResponder> {
     let mut player : Player = async_extract_player_from_url_v2!(&player_id, state);
     let note_index = unwrap_helper::return_default!(player.notes.iter().position(|note| { note.id == note_id }), Err(ApiErrorResponder::note_missing()));
     let note_clone = player.notes[note_index].clone();
     player.notes.remove(note_index);
     state.player_cache.set(&state.database, player_id, &player, true).await;
+    audit.record_deleted(state, AuditAction::NoteDelete, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &note_clone).await;
     {
         // take ownership for the spawned task
         let state_clone = state.config.clone();
@@ -443,9 +808,11 @@ async fn add_tag_to_player(
     state: &State<MarsAPIState>, 
     player_id: &str, 
     tag_id: &str, 
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<Player>, ApiErrorResponder> {
     let mut player = async_extract_player_from_url_v2!(player_id, state);
+    let before = player.clone();
 
     let tag = match state.database.find_by_id_or_name::<Tag>(tag_id).await {
         Some(tag) => tag,
@@ -458,6 +825,7 @@ async fn add_tag_to_player(
 
     player.tag_ids.push(tag.id.clone());
     state.player_cache.set(&state.database, &player.name, &player, true).await;
+    audit.record_changed(state, AuditAction::PlayerTagAdd, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &before, &player).await;
     return Ok(JsonResponder::from(player, Status::Ok));
 }
 
@@ -467,9 +835,11 @@ async fn delete_player_tag(
     state: &State<MarsAPIState>,
     player_id: &str,
     tag_id: &str,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<Player>, ApiErrorResponder> {
     let mut player = async_extract_player_from_url_v2!(player_id, state);
+    let before = player.clone();
     let tag = match state.database.find_by_id_or_name::<Tag>(tag_id).await {
         Some(tag) => tag,
         None => return Err(ApiErrorResponder::tag_missing())
@@ -482,6 +852,7 @@ async fn delete_player_tag(
         player.active_tag_id = Option::None;
     }
     state.player_cache.set(&state.database, &player.name, &player, true).await;
+    audit.record_changed(state, AuditAction::PlayerTagRemove, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &before, &player).await;
     return Ok(JsonResponder::from(player, Status::Ok));
 
 }
@@ -491,15 +862,18 @@ async fn add_player_rank(
     state: &State<MarsAPIState>, 
     player_id: &str, 
     rank_id: &str, 
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<Json<Player>, ApiErrorResponder> {
     let mut player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
     let rank = unwrap_helper::return_default!(state.database.find_by_id_or_name::<Rank>(rank_id).await, Err(ApiErrorResponder::missing_rank()));
 
     if player.rank_ids.contains(&rank.id) { return Err(ApiErrorResponder::rank_already_present()); };
+    let before = player.clone();
     player.rank_ids.push(rank.id);
 
     state.player_cache.set(&state.database, &player.name, &player, true).await;
+    audit.record_changed(state, AuditAction::PlayerRankAdd, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &before, &player).await;
     Ok(Json(player))
 }
 
@@ -508,15 +882,18 @@ async fn delete_player_rank(
     state: &State<MarsAPIState>, 
     player_id: &str, 
     rank_id: &str, 
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<Json<Player>, ApiErrorResponder> {
     let mut player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
     let rank = unwrap_helper::return_default!(state.database.find_by_id_or_name::<Rank>(rank_id).await, Err(ApiErrorResponder::missing_rank()));
 
     if !player.rank_ids.contains(&rank.id) { return Err(ApiErrorResponder::rank_not_present()); };
+    let before = player.clone();
     player.rank_ids.retain(|rank_id| { rank_id != rank.id.as_str() });
 
     state.player_cache.set(&state.database, &player.name, &player, true).await;
+    audit.record_changed(state, AuditAction::PlayerRankRemove, AuditTarget::new(AuditTargetKind::Player, &player.id, &player.name), &before, &player).await;
     Ok(Json(player))
 }
 
@@ -524,10 +901,18 @@ pub fn mount(rocket_build: Rocket<Build>, state: &MarsAPIState) -> Rocket<Build>
     rocket_build.mount("/mc/players", routes![
         prelogin, 
         login, 
+        search_players,
+        batch_players,
         logout, 
         profile, 
         issue_punishment, 
+        preview_punishment,
         get_punishments,
+        get_stat_history,
+        update_preferences,
+        get_player_presence,
+        ignore_player,
+        unignore_player,
         lookup_player,
         add_player_note,
         delete_player_note,
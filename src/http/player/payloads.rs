@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use rocket::{response::{self, Response, Responder}, Request, http::{Status, ContentType}, serde::json::Json};
 
-use crate::{database::models::{player::{SimplePlayer, Player}, punishment::Punishment, session::Session}, socket::leaderboard::ScoreType};
+use crate::{database::models::{ip_identity::AltLink, player::{SimplePlayer, Player}, punishment::Punishment, session::Session, stat_snapshot::StatSnapshotValues, preference::PreferenceValue}, socket::leaderboard::ScoreType};
 
 #[derive(Deserialize, Serialize)]
 pub struct PlayerPreLoginRequest {
@@ -23,7 +23,10 @@ pub struct PlayerPreLoginResponse {
     pub new: bool,
     pub allowed: bool,
     pub player: Player,
-    pub active_punishments: Vec<Punishment>
+    pub active_punishments: Vec<Punishment>,
+    pub preferences: HashMap<String, PreferenceValue>,
+    // set when an admission policy rather than a punishment turned the player away
+    pub kick_message: Option<String>
 }
 
 impl<'r> Responder<'r, 'static> for PlayerPreLoginResponder {
@@ -44,7 +47,8 @@ pub struct PlayerLoginResponder {
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PlayerLoginResponse {
-    pub active_session: Session
+    pub active_session: Session,
+    pub preferences: HashMap<String, PreferenceValue>
 }
 
 impl<'r> Responder<'r, 'static> for PlayerLoginResponder {
@@ -69,14 +73,21 @@ pub struct PlayerLogoutRequest {
 #[serde(rename_all = "camelCase")]
 pub struct PlayerLookupResponse {
     pub player: Player,
-    pub alts: Vec<PlayerAltResponse>
+    pub alts: Vec<PlayerAltResponse>,
+    // shared too widely to follow
+    pub skipped_ips: Vec<String>,
+    // the alt lookup hit a fanout or account bound
+    pub truncated: bool
 }
 
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PlayerAltResponse {
     pub player: Player,
-    pub punishments: Vec<Punishment>
+    pub punishments: Vec<Punishment>,
+    pub depth: u32,
+    pub links: Vec<AltLink>,
+    pub confidence: f64
 }
 
 
@@ -84,7 +95,8 @@ pub struct PlayerAltResponse {
 #[serde(rename_all = "camelCase")]
 pub struct PlayerProfileResponse {
     pub player: Player,
-    pub leaderboard_positions: HashMap<ScoreType, u64>
+    pub leaderboard_positions: HashMap<ScoreType, u64>,
+    pub clan_tag: Option<String>
 }
 
 pub enum PlayerProfileResponder {
@@ -124,3 +136,43 @@ pub struct PlayerAddNoteRequest {
 pub struct PlayerSetActiveTagRequest {
     pub active_tag_id: Option<String>
 }
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSearchResult {
+    pub player: SimplePlayer,
+    pub last_seen_at: f64,
+    // set when the query matched one of the player's previous names
+    pub matched_name: Option<String>
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerBatchRequest {
+    pub players: Vec<String>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStatHistoryPoint {
+    pub date: u64,
+    pub baseline: bool,
+    pub totals: StatSnapshotValues,
+    pub delta: StatSnapshotValues
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPreferencesUpdateRequest {
+    // only the keys present are changed, a null value resets the key to its default
+    pub preferences: HashMap<String, Option<PreferenceValue>>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPresenceResponse {
+    pub player: SimplePlayer,
+    pub online: bool,
+    pub server_id: Option<String>,
+    pub since: Option<u64>
+}
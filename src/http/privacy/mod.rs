@@ -0,0 +1,304 @@
+mod payload;
+
+use std::collections::HashMap;
+
+use futures::StreamExt;
+use mongodb::{bson::{doc, Bson, Document}, options::{FindOptions, UpdateOptions}};
+use rocket::{http::Status, Build, Rocket, State};
+
+use crate::{database::{models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, player::Player, punishment::Punishment}, Database}, util::{audit::AuditContext, auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, responder::JsonResponder, string::{name_grams, sha256_hash_formatted}, time::get_u64_time_millis}, socket::player::{direct_message::clear_direct_message_state, presence::remove_presence}, MarsAPIState};
+
+use self::payload::{AuthoredNote, MatchParticipation, MatchParticipationProjection, PlayerDataExport, PlayerErasureResponse};
+
+#[get("/<player_id>/export")]
+pub async fn export_player_data(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<PlayerDataExport>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let id = player.id.clone();
+    let database = &state.database;
+
+    let sessions = Database::consume_cursor_into_owning_vec_option(
+        database.sessions.find(doc! { "player.id": &id }, None).await.ok()
+    ).await;
+    let punishments_received = database.get_player_punishments(&player).await;
+    let punishments_issued : Vec<Punishment> = Database::consume_cursor_into_owning_vec_option(
+        database.punishments.find(doc! { "punisher.id": &id }, None).await.ok()
+    ).await;
+    let notes_authored = {
+        let noted_players : Vec<Player> = Database::consume_cursor_into_owning_vec_option(
+            database.players.find(doc! { "notes.author.id": &id }, None).await.ok()
+        ).await;
+        noted_players.into_iter().flat_map(|noted| {
+            let subject = noted.to_simple();
+            let author_id = id.clone();
+            noted.notes.into_iter()
+                .filter(move |note| note.author.id == author_id)
+                .map(move |note| AuthoredNote { subject: subject.clone(), note })
+        }).collect::<Vec<_>>()
+    };
+    let deaths = Database::consume_cursor_into_owning_vec_option(
+        database.deaths.find(doc! { "$or": [{ "victim.id": &id }, { "attacker.id": &id }] }, None).await.ok()
+    ).await;
+    let match_participations = {
+        let participant_path = format!("participants.{}", id);
+        let opts = FindOptions::builder()
+            .projection(doc! { "_id": 1, "loadedAt": 1, "startedAt": 1, "endedAt": 1, "serverId": 1, &participant_path: 1 })
+            .sort(doc! { "loadedAt": -1 })
+            .build();
+        let projections : Vec<MatchParticipationProjection> = Database::consume_cursor_into_owning_vec_option(
+            database.matches.clone_with_type::<MatchParticipationProjection>()
+                .find(doc! { &participant_path: { "$exists": true } }, Some(opts)).await.ok()
+        ).await;
+        projections.into_iter().filter_map(|mut projection| {
+            let participant = projection.participants.remove(&id)?;
+            Some(MatchParticipation {
+                match_id: projection.id,
+                server_id: projection.server_id,
+                loaded_at: projection.loaded_at,
+                started_at: projection.started_at,
+                ended_at: projection.ended_at,
+                participant
+            })
+        }).collect::<Vec<_>>()
+    };
+    let ip_identities = Database::consume_cursor_into_owning_vec_option(
+        database.ip_identities.find(doc! { "players": &id }, None).await.ok()
+    ).await;
+    let preferences = database.get_player_preferences(&id).await;
+    let cosmetics = database.get_cosmetic_ownerships(&id).await;
+    let currency_transactions = Database::consume_cursor_into_owning_vec_option(
+        database.currency_transactions.find(doc! { "playerId": &id }, None).await.ok()
+    ).await;
+    let chat_messages = Database::consume_cursor_into_owning_vec_option(
+        database.chat_messages.find(doc! { "player.id": &id }, None).await.ok()
+    ).await;
+    let chat_filter_hits = Database::consume_cursor_into_owning_vec_option(
+        database.chat_filter_hits.find(doc! { "player.id": &id }, None).await.ok()
+    ).await;
+    let appeals = Database::consume_cursor_into_owning_vec_option(
+        database.appeals.find(doc! { "player.id": &id }, None).await.ok()
+    ).await;
+    let reports_filed = Database::consume_cursor_into_owning_vec_option(
+        database.reports.find(doc! { "submissions.reporter.id": &id }, None).await.ok()
+    ).await;
+
+    Ok(JsonResponder::ok(PlayerDataExport {
+        exported_at: get_u64_time_millis(),
+        player,
+        sessions,
+        punishments_received,
+        punishments_issued,
+        notes_authored,
+        deaths,
+        match_participations,
+        ip_identities,
+        preferences,
+        cosmetics,
+        currency_transactions,
+        chat_messages,
+        chat_filter_hits,
+        appeals,
+        reports_filed
+    }))
+}
+
+#[post("/<player_id>/erase")]
+pub async fn erase_player_data(
+    state: &State<MarsAPIState>,
+    player_id: &str,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<PlayerErasureResponse>, ApiErrorResponder> {
+    let player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    if state.database.get_active_player_session(&player).await.is_some() {
+        return Err(ApiErrorResponder::create_anonymous_error(Status::Conflict, "Player must be offline to be erased"));
+    };
+    let id = player.id.clone();
+    let anonymised_name = format!("Erased{}", &sha256_hash_formatted(&id)[..8]);
+    let mongo = &state.database.mongo;
+    let mut modified : HashMap<String, u64> = HashMap::new();
+
+    // ids are kept so that match, death and stat aggregates stay consistent; only identifying fields change
+    let result = state.database.players.update_one(doc! { "_id": &id }, doc! {
+        "$set": {
+            "name": &anonymised_name,
+            "nameLower": anonymised_name.to_lowercase(),
+            "nameGrams": name_grams(&anonymised_name.to_lowercase()),
+            "ips": [],
+            "notes": [],
+            "pastNames": [],
+            "lastSessionId": null
+        }
+    }, None).await;
+    modified.insert(String::from("player"), result.map(|res| res.modified_count).unwrap_or(0));
+
+    let participant_path = format!("participants.{}", id);
+    let embeds : Vec<(&str, String)> = vec![
+        ("session", String::from("player")),
+        ("punishment", String::from("target")),
+        ("punishment", String::from("punisher")),
+        ("punishment", String::from("reversion.reverter")),
+        ("death", String::from("victim")),
+        ("death", String::from("attacker")),
+        ("match", participant_path),
+        ("match", String::from("firstBlood.attacker")),
+        ("match", String::from("firstBlood.victim")),
+        ("level", String::from("records.highestKillstreak.player")),
+        ("level", String::from("records.longestProjectileKill.player")),
+        ("level", String::from("records.fastestWoolCapture.player")),
+        ("level", String::from("records.fastestFlagCapture.player")),
+        ("level", String::from("records.fastestFirstBlood.attacker")),
+        ("level", String::from("records.fastestFirstBlood.victim")),
+        ("level", String::from("records.killsInMatch.player")),
+        ("level", String::from("records.deathsInMatch.player")),
+        ("player", String::from("stats.records.fastestFirstBlood.victim")),
+        ("friendship", String::from("requester")),
+        ("friendship", String::from("recipient")),
+        ("chat_filter_hit", String::from("player")),
+        ("report", String::from("target")),
+        ("report", String::from("reporter")),
+        ("report", String::from("assignee")),
+        ("appeal", String::from("player")),
+        ("appeal", String::from("reviewer")),
+        ("audit_entry", String::from("actor.player")),
+        ("audit_entry", String::from("target"))
+    ];
+    for (collection, path) in embeds {
+        let result = mongo.collection::<Document>(collection).update_many(
+            doc! { format!("{}.id", path): &id },
+            doc! { "$set": { format!("{}.name", path): &anonymised_name } },
+            None
+        ).await;
+        *modified.entry(format!("{}.{}", collection, path)).or_insert(0) += result.map(|res| res.modified_count).unwrap_or(0);
+    };
+
+    let array_embeds : Vec<(&str, &str, &str, &str)> = vec![
+        ("player", "notes.author.id", "notes.$[embed].author", "embed.author.id"),
+        ("match", "level.goals.cores.contributors.id", "level.goals.cores.$[].contributors.$[embed]", "embed.id"),
+        ("match", "level.goals.destroyables.contributors.id", "level.goals.destroyables.$[].contributors.$[embed]", "embed.id"),
+        ("clan", "members.player.id", "members.$[embed].player", "embed.player.id"),
+        ("clan", "invites.player.id", "invites.$[embed].player", "embed.player.id"),
+        ("clan", "invites.invitedBy.id", "invites.$[embed].invitedBy", "embed.invitedBy.id"),
+        ("report", "submissions.reporter.id", "submissions.$[embed].reporter", "embed.reporter.id"),
+        ("report", "onlineStaff.id", "onlineStaff.$[embed]", "embed.id"),
+        ("appeal", "comments.author.id", "comments.$[embed].author", "embed.author.id")
+    ];
+    for (collection, query_path, path, filter_path) in array_embeds {
+        let opts = UpdateOptions::builder().array_filters(vec![doc! { filter_path: &id }]).build();
+        let result = mongo.collection::<Document>(collection).update_many(
+            doc! { query_path: &id },
+            doc! { "$set": { format!("{}.name", path): &anonymised_name } },
+            Some(opts)
+        ).await;
+        *modified.entry(format!("{}.{}", collection, path)).or_insert(0) += result.map(|res| res.modified_count).unwrap_or(0);
+    };
+
+    let result = state.database.sessions.update_many(doc! { "player.id": &id }, doc! { "$set": { "ip": "" } }, None).await;
+    modified.insert(String::from("session.ip"), result.map(|res| res.modified_count).unwrap_or(0));
+    let result = state.database.punishments.update_many(doc! { "target.id": &id }, doc! { "$set": { "targetIps": [] } }, None).await;
+    modified.insert(String::from("punishment.targetIps"), result.map(|res| res.modified_count).unwrap_or(0));
+    let result = state.database.ip_identities.update_many(doc! { "players": &id }, doc! { "$pull": { "players": &id, "sightings": { "playerId": &id } } }, None).await;
+    modified.insert(String::from("ip_identity.players"), result.map(|res| res.modified_count).unwrap_or(0));
+    let _ = state.database.ip_identities.delete_many(doc! { "players": { "$size": 0 } }, None).await;
+    let result = state.database.chat_messages.delete_many(doc! { "player.id": &id }, None).await;
+    modified.insert(String::from("chat_message"), result.map(|res| res.deleted_count).unwrap_or(0));
+    // hits stay as the record behind automatic punishments, without the message
+    let result = state.database.chat_filter_hits.update_many(doc! { "player.id": &id }, doc! { "$set": { "message": "" } }, None).await;
+    modified.insert(String::from("chat_filter_hit.message"), result.map(|res| res.modified_count).unwrap_or(0));
+    let result = state.database.appeals.update_many(doc! { "player.id": &id }, doc! { "$set": { "statement": "" } }, None).await;
+    modified.insert(String::from("appeal.statement"), result.map(|res| res.modified_count).unwrap_or(0));
+
+    // snapshots of the player and their records are dropped, elsewhere their names and ips are replaced
+    let mut subject_ids = vec![Bson::String(id.clone())];
+    for (collection, path) in [("punishment", "target.id"), ("appeal", "player.id"), ("report", "target.id")] {
+        if let Ok(ids) = mongo.collection::<Document>(collection).distinct("_id", doc! { path: &id }, None).await {
+            subject_ids.extend(ids);
+        };
+    };
+    let result = state.database.audit_entries.update_many(
+        doc! { "target.id": { "$in": &subject_ids } }, doc! { "$set": { "before": null, "after": null } }, None
+    ).await;
+    modified.insert(String::from("audit_entry.snapshots"), result.map(|res| res.modified_count).unwrap_or(0));
+    let mut names : Vec<String> = player.past_names.iter().map(|past| past.name_lower.clone()).collect();
+    names.push(player.name_lower.clone());
+    let audit_entries = state.database.audit_entries.clone_with_type::<Document>();
+    let opts = FindOptions::builder().projection(doc! { "before": 1, "after": 1 }).batch_size(Some(1_000)).build();
+    let mut scrubbed = 0u64;
+    if let Ok(mut cursor) = audit_entries.find(doc! { "createdAt": { "$gte": player.first_joined_at } }, Some(opts)).await {
+        while let Some(Ok(mut entry)) = cursor.next().await {
+            let mut update = Document::new();
+            for field in ["before", "after"] {
+                if let Ok(snapshot) = entry.get_document_mut(field) {
+                    if scrub_snapshot(snapshot, &names, &player.ips, &anonymised_name) {
+                        update.insert(field, snapshot.clone());
+                    };
+                };
+            };
+            if !update.is_empty() {
+                let _ = audit_entries.update_one(doc! { "_id": entry.get("_id").cloned().unwrap_or(Bson::Null) }, doc! { "$set": update }, None).await;
+                scrubbed += 1;
+            };
+        }
+    };
+    modified.insert(String::from("audit_entry.mentions"), scrubbed);
+
+    clear_direct_message_state(state, &id).await;
+    remove_presence(state, &id).await;
+    for leaderboard in state.leaderboards.all() {
+        leaderboard.remove_player(&id).await;
+    };
+
+    state.player_cache.invalidate(&player.name).await;
+    for past_name in player.past_names.iter() {
+        state.player_cache.invalidate(&past_name.name).await;
+    };
+
+    info!("Erased personal data of player {}", id);
+    let response = PlayerErasureResponse { player_id: id, anonymised_name, modified };
+    // the erased data itself is never recorded
+    audit.record_created(
+        state, AuditAction::PlayerErase, AuditTarget::new(AuditTargetKind::Player, &response.player_id, &response.anonymised_name),
+        &doc! { "anonymisedName": &response.anonymised_name }
+    ).await;
+    Ok(JsonResponder::ok(response))
+}
+
+// replaces the player's names and removes their ips anywhere in an audit snapshot, true if anything changed
+fn scrub_snapshot(snapshot: &mut Document, names: &[String], ips: &[String], anonymised_name: &str) -> bool {
+    let mut changed = false;
+    for (_, value) in snapshot.iter_mut() {
+        changed |= scrub_value(value, names, ips, anonymised_name);
+    };
+    changed
+}
+
+fn scrub_value(value: &mut Bson, names: &[String], ips: &[String], anonymised_name: &str) -> bool {
+    match value {
+        Bson::String(text) if names.contains(&text.to_lowercase()) => {
+            *text = anonymised_name.to_owned();
+            true
+        },
+        Bson::String(text) if ips.contains(text) => {
+            text.clear();
+            true
+        },
+        Bson::Document(document) => scrub_snapshot(document, names, ips, anonymised_name),
+        Bson::Array(values) => {
+            let length = values.len();
+            values.retain(|value| !matches!(value, Bson::String(text) if ips.contains(text)));
+            let mut changed = values.len() != length;
+            for value in values.iter_mut() {
+                changed |= scrub_value(value, names, ips, anonymised_name);
+            };
+            changed
+        },
+        _ => false
+    }
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/privacy", routes![export_player_data, erase_player_data])
+}
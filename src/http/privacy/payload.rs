@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::database::models::{death::Death, ip_identity::IpIdentity, participant::Participant, player::{Player, SimplePlayer}, preference::PlayerPreferences, cosmetic::CosmeticOwnership, currency::CurrencyTransaction, chat_message::ChatMessage, chat_filter::ChatFilterHit, appeal::Appeal, report::Report, punishment::{Punishment, StaffNote}, session::Session};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerDataExport {
+    pub exported_at: u64,
+    pub player: Player,
+    pub sessions: Vec<Session>,
+    pub punishments_received: Vec<Punishment>,
+    pub punishments_issued: Vec<Punishment>,
+    pub notes_authored: Vec<AuthoredNote>,
+    pub deaths: Vec<Death>,
+    pub match_participations: Vec<MatchParticipation>,
+    pub ip_identities: Vec<IpIdentity>,
+    pub preferences: Option<PlayerPreferences>,
+    pub cosmetics: Vec<CosmeticOwnership>,
+    pub currency_transactions: Vec<CurrencyTransaction>,
+    pub chat_messages: Vec<ChatMessage>,
+    pub chat_filter_hits: Vec<ChatFilterHit>,
+    pub appeals: Vec<Appeal>,
+    pub reports_filed: Vec<Report>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthoredNote {
+    pub subject: SimplePlayer,
+    pub note: StaffNote
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchParticipation {
+    pub match_id: String,
+    pub server_id: String,
+    pub loaded_at: u64,
+    pub started_at: Option<u64>,
+    pub ended_at: Option<u64>,
+    pub participant: Participant
+}
+
+// match document projected down to the requested participant
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchParticipationProjection {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub loaded_at: u64,
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    #[serde(default)]
+    pub ended_at: Option<u64>,
+    pub server_id: String,
+    #[serde(default)]
+    pub participants: HashMap<String, Participant>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerErasureResponse {
+    pub player_id: String,
+    pub anonymised_name: String,
+    pub modified: HashMap<String, u64>
+}
@@ -1,8 +1,13 @@
+use futures::StreamExt;
+use mongodb::{bson::{doc, Bson, Document}, options::FindOptions};
 use rocket::{Rocket, Build, serde::json::Json, State};
 
-use crate::{database::{models::punishment::{PunishmentType, Punishment, PunishmentReversion}, Database}, MarsAPIState, util::{error::ApiErrorResponder, auth::AuthorizationToken, r#macro::unwrap_helper, time::get_u64_time_millis}};
+use crate::{socket::{player::player_context::send_player_update_to_online_player, update::player_update_listener::{PlayerUpdateData, PlayerUpdateReason}}, database::{models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, player::SimplePlayer, punishment::{PunishmentType, Punishment, PunishmentEdit, PunishmentEditableValues, PunishmentReason, PunishmentReversion}}, Database}, MarsAPIState, util::{audit::AuditContext, error::ApiErrorResponder, auth::AuthorizationToken, pagination::PageCursor, r#macro::unwrap_helper, responder::JsonResponder, time::get_u64_time_millis}};
 
-use self::payloads::PunishmentRevertRequest;
+use self::payloads::{PunishmentCounts, PunishmentEditRequest, PunishmentRevertRequest, PunishmentSearchQuery, PunishmentSearchResponse, PunisherCount};
+
+const SEARCH_LIMIT_MAX : i64 = 200;
+const PUNISHER_COUNT_LIMIT : i64 = 25;
 
 pub mod payloads;
 
@@ -11,6 +16,118 @@ fn get_pun_types(state: &State<MarsAPIState>, _auth_guard: AuthorizationToken) -
     Json(&state.config.data.punishment_types)
 }
 
+#[get("/?<query..>")]
+async fn search_puns(
+    state: &State<MarsAPIState>,
+    query: PunishmentSearchQuery<'_>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<PunishmentSearchResponse>, ApiErrorResponder> {
+    let PunishmentSearchQuery { punisher, target, kind, reason, server, state: pun_state, silent, from, to, before, limit } = query;
+    let mut filter = Document::new();
+    if let Some(punisher) = punisher {
+        let punisher = unwrap_helper::return_default!(state.player_cache.get(&state.database, punisher).await, Err(ApiErrorResponder::missing_player()));
+        filter.insert("punisher.id", punisher.id);
+    };
+    if let Some(target) = target {
+        let target = unwrap_helper::return_default!(state.player_cache.get(&state.database, target).await, Err(ApiErrorResponder::missing_player()));
+        filter.insert("target.id", target.id);
+    };
+    if let Some(kind) = kind {
+        filter.insert("action.kind", kind.to_uppercase());
+    };
+    if let Some(reason) = reason {
+        filter.insert("reason.name", reason);
+    };
+    if let Some(server) = server {
+        filter.insert("serverId", server);
+    };
+    if let Some(silent) = silent {
+        filter.insert("silent", silent);
+    };
+    if let Some(pun_state) = pun_state {
+        let now = get_u64_time_millis() as f64;
+        let expires_at = doc! { "$add": ["$issuedAt", "$action.length"] };
+        match pun_state.to_lowercase().as_str() {
+            "reverted" => { filter.insert("reversion", doc! { "$ne": null }); },
+            "active" => {
+                filter.insert("reversion", Bson::Null);
+                filter.insert("$or", vec![
+                    doc! { "action.length": -1 },
+                    doc! { "$expr": { "$gt": [expires_at, now] } }
+                ]);
+            },
+            "expired" => {
+                filter.insert("reversion", Bson::Null);
+                filter.insert("action.length", doc! { "$ne": -1 });
+                filter.insert("$expr", doc! { "$lte": [expires_at, now] });
+            },
+            _ => return Err(ApiErrorResponder::validation_error_with_message("State must be active, expired or reverted"))
+        };
+    };
+    let mut issued_at = Document::new();
+    if let Some(from) = from {
+        issued_at.insert("$gte", from as f64);
+    };
+    if let Some(to) = to {
+        issued_at.insert("$lte", to as f64);
+    };
+    if !issued_at.is_empty() {
+        filter.insert("issuedAt", issued_at);
+    };
+
+    let counts = count_puns(state, &filter).await;
+    // the cursor only narrows the page, the counts cover the whole result
+    let mut page_filter = filter;
+    if let Some(before) = before {
+        // bulk and alt bans share an issue time, so the id breaks ties
+        let before = unwrap_helper::return_default!(PageCursor::parse(before), Err(ApiErrorResponder::validation_error_with_message("Invalid cursor")));
+        before.apply(&mut page_filter, "issuedAt");
+    };
+    let limit = limit.unwrap_or(50).clamp(1, SEARCH_LIMIT_MAX);
+    let opts = FindOptions::builder().sort(PageCursor::sort("issuedAt")).limit(limit).build();
+    let cursor = state.database.punishments.find(page_filter, Some(opts)).await.ok();
+    let punishments : Vec<Punishment> = Database::consume_cursor_into_owning_vec_option(cursor).await;
+    let next_cursor = PageCursor::next(&punishments, limit, |punishment| (punishment.issued_at as u64, &punishment.id));
+    Ok(JsonResponder::ok(PunishmentSearchResponse { punishments, next_cursor, counts }))
+}
+
+async fn count_puns(state: &MarsAPIState, filter: &Document) -> PunishmentCounts {
+    let pipeline = vec![
+        doc! { "$match": filter.clone() },
+        doc! { "$facet": {
+            "byKind": [{ "$group": { "_id": "$action.kind", "count": { "$sum": 1 } } }],
+            "byPunisher": [
+                { "$group": { "_id": "$punisher.id", "name": { "$first": "$punisher.name" }, "count": { "$sum": 1 } } },
+                { "$sort": { "count": -1 } },
+                { "$limit": PUNISHER_COUNT_LIMIT }
+            ]
+        } }
+    ];
+    let mut cursor = match state.database.punishments.aggregate(pipeline, None).await {
+        Ok(cursor) => cursor,
+        Err(_) => return PunishmentCounts::default()
+    };
+    let result = match cursor.next().await {
+        Some(Ok(result)) => result,
+        _ => return PunishmentCounts::default()
+    };
+    let get_count = |group: &Document| group.get_i32("count").map(|count| count as u64).unwrap_or(0);
+    let mut counts = PunishmentCounts::default();
+    for group in result.get_array("byKind").map(|groups| groups.iter().filter_map(|group| group.as_document()).collect::<Vec<_>>()).unwrap_or_default() {
+        let count = get_count(group);
+        counts.total += count;
+        counts.by_kind.insert(group.get_str("_id").unwrap_or_default().to_owned(), count);
+    };
+    for group in result.get_array("byPunisher").map(|groups| groups.iter().filter_map(|group| group.as_document()).collect::<Vec<_>>()).unwrap_or_default() {
+        counts.by_punisher.push(PunisherCount {
+            punisher_id: group.get_str("_id").ok().map(String::from),
+            punisher_name: group.get_str("name").ok().map(String::from),
+            count: get_count(group)
+        });
+    };
+    counts
+}
+
 #[get("/<punishment_id>")]
 async fn get_pun(
     state: &State<MarsAPIState>, 
@@ -25,23 +142,114 @@ async fn revert_pun(
     state: &State<MarsAPIState>, 
     punishment_id: &str, 
     revert_req: Json<PunishmentRevertRequest>, 
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<Json<Punishment>, ApiErrorResponder> {
     let data = revert_req.0;
+    let punishment = unwrap_helper::return_default!(Database::find_by_id(&state.database.punishments, punishment_id).await, Err(ApiErrorResponder::missing_punishment()));
+    Ok(Json(revert_punishment(state, &audit, punishment, data.reverter, data.reason).await))
+}
+
+#[post("/<punishment_id>/edit", format = "json", data = "<edit_req>")]
+async fn edit_pun(
+    state: &State<MarsAPIState>, 
+    punishment_id: &str, 
+    edit_req: Json<PunishmentEditRequest>, 
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<Json<Punishment>, ApiErrorResponder> {
+    let data = edit_req.0;
+    if data.justification.trim().is_empty() {
+        return Err(ApiErrorResponder::validation_error_with_message("A justification is required"));
+    };
     let mut punishment = unwrap_helper::return_default!(Database::find_by_id(&state.database.punishments, punishment_id).await, Err(ApiErrorResponder::missing_punishment()));
-    punishment.reversion = Some(PunishmentReversion { reverted_at: get_u64_time_millis(), reverter: data.reverter, reason: data.reason });
-    state.database.save(&punishment).await;
+    if punishment.reversion.is_some() {
+        return Err(ApiErrorResponder::validation_error_with_message("Reverted punishments cannot be edited"));
+    };
+    let before = punishment.clone();
+    let previous = PunishmentEditableValues::from(&punishment);
+
+    if let Some(reason) = &data.reason {
+        let punishment_type = unwrap_helper::return_default!(
+            state.config.data.punishment_types.iter().find(|punishment_type| punishment_type.name == *reason),
+            Err(ApiErrorResponder::validation_error_with_message("Unknown punishment type"))
+        );
+        if punishment_type.name != punishment.reason.name {
+            // the offence is renumbered against the new type, counting only what came before this punishment
+            let prior = state.database.get_prior_offences(&punishment.target.id, punishment_type, state.config.options.punishment_offence_decay_days).await;
+            punishment.offence = prior.iter().filter(|prior| prior.issued_at < punishment.issued_at).count() as u32 + 1;
+            punishment.reason = PunishmentReason::from(punishment_type);
+            // the action follows the new type's ladder, an explicit length below still overrides it
+            punishment.action = unwrap_helper::return_default!(
+                punishment_type.get_action_for_offence(punishment.offence),
+                Err(ApiErrorResponder::validation_error_with_message("The punishment type has no actions"))
+            ).clone();
+        };
+    };
+    if let Some(length) = data.length {
+        if length < -1 {
+            return Err(ApiErrorResponder::validation_error_with_message("Length must be -1 or positive"));
+        };
+        punishment.action.length = length;
+    };
+    if let Some(note) = data.note {
+        punishment.note = if note.trim().is_empty() { None } else { Some(note) };
+    };
+    if let Some(silent) = data.silent {
+        punishment.silent = silent;
+    };
+
+    let edit = PunishmentEdit { editor: data.editor, edited_at: get_u64_time_millis(), justification: data.justification, previous };
+    // only the edited fields are written, so a reversion or expiry landing meanwhile is not overwritten
+    let punishment = unwrap_helper::return_default!(
+        state.database.apply_punishment_edit(&punishment, &edit).await,
+        Err(ApiErrorResponder::validation_error_with_message("Reverted punishments cannot be edited"))
+    );
+    audit.acting_as(Some(&edit.editor)).record_changed(
+        state, AuditAction::PunishmentEdit, AuditTarget::new(AuditTargetKind::Punishment, &punishment.id, &punishment.target.name), &before, &punishment
+    ).await;
     {
         // take ownership for the spawned task
         let pun_clone = punishment.clone();
         let state_clone = state.config.clone();
         tokio::spawn(async move {
-            state_clone.webhooks.send_punishment_reversion_webhook(&pun_clone).await;
+            state_clone.webhooks.send_punishment_edit_webhook(&pun_clone, &edit).await;
         });
     }
+    if let Some(target) = state.player_cache.get(&state.database, &punishment.target.id).await {
+        send_player_update_to_online_player(
+            state, 
+            target, 
+            PlayerUpdateData::PunishmentUpdateData { punishment: Box::new(punishment.clone()) }, 
+            PlayerUpdateReason::PunishmentEdit
+        ).await;
+    };
     Ok(Json(punishment))
 }
 
+// a punishment that is already reverted, possibly by a concurrent request, is returned as it is
+pub async fn revert_punishment(state: &MarsAPIState, audit: &AuditContext, punishment: Punishment, reverter: SimplePlayer, reason: String) -> Punishment {
+    let before = punishment;
+    let audit = audit.acting_as(Some(&reverter));
+    let reversion = PunishmentReversion { reverted_at: get_u64_time_millis(), reverter, reason };
+    let punishment = match state.database.revert_punishment(&before.id, &reversion).await {
+        Some(punishment) => punishment,
+        None => return Database::find_by_id(&state.database.punishments, &before.id).await.unwrap_or(before)
+    };
+    audit.record_changed(
+        state, AuditAction::PunishmentRevert, AuditTarget::new(AuditTargetKind::Punishment, &punishment.id, &punishment.target.name), &before, &punishment
+    ).await;
+    {
+        // take ownership for the spawned task
+        let pun_clone = punishment.clone();
+        let state_clone = state.config.clone();
+        tokio::spawn(async move {
+            state_clone.webhooks.send_punishment_reversion_webhook(&pun_clone).await;
+        });
+    }
+    punishment
+}
+
 pub fn mount(rocket: Rocket<Build>, state: &MarsAPIState) -> Rocket<Build> {
-    rocket.mount("/mc/punishments", routes![get_pun_types, get_pun, revert_pun])
+    rocket.mount("/mc/punishments", routes![get_pun_types, search_puns, get_pun, edit_pun, revert_pun])
 }
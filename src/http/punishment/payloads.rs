@@ -1,20 +1,100 @@
+use std::collections::HashMap;
+
+use rocket::FromForm;
 use serde::{Serialize, Deserialize};
 
-use crate::database::models::{punishment::{PunishmentReason, PunishmentAction}, player::SimplePlayer};
+use crate::database::models::{punishment::{Punishment, PunishmentReason, PunishmentAction}, player::SimplePlayer};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PunishmentIssueRequest {
     pub reason: PunishmentReason,
-    pub offence: u32,
-    pub action: PunishmentAction,
+    // only used for reasons without a configured punishment type, the ladder decides otherwise
+    #[serde(default)]
+    pub offence: Option<u32>,
+    #[serde(default)]
+    pub action: Option<PunishmentAction>,
     #[serde(default)]
     pub note: Option<String>,
     #[serde(default)]
     pub punisher: Option<SimplePlayer>,
     pub target_name: String,
     pub target_ips: Vec<String>,
-    pub silent: bool
+    pub silent: bool,
+    #[serde(default)]
+    pub message_ids: Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PunishmentPreviewResponse {
+    pub reason: PunishmentReason,
+    pub offence: u32,
+    pub action: PunishmentAction,
+    // the prior punishments that counted towards the offence number
+    pub prior_offences: Vec<Punishment>
+}
+
+#[derive(FromForm)]
+pub struct PunishmentSearchQuery<'r> {
+    pub punisher: Option<&'r str>,
+    pub target: Option<&'r str>,
+    pub kind: Option<&'r str>,
+    pub reason: Option<&'r str>,
+    pub server: Option<&'r str>,
+    // one of active, expired or reverted
+    pub state: Option<&'r str>,
+    pub silent: Option<bool>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    // cursor from a previous page
+    pub before: Option<&'r str>,
+    pub limit: Option<i64>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PunishmentSearchResponse {
+    pub punishments: Vec<Punishment>,
+    // pass as `before` to fetch the next page
+    pub next_cursor: Option<String>,
+    // over every match of the filters, not just this page
+    pub counts: PunishmentCounts
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PunishmentCounts {
+    pub total: u64,
+    pub by_kind: HashMap<String, u64>,
+    pub by_punisher: Vec<PunisherCount>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PunisherCount {
+    // none for punishments issued by the console or the chat filter
+    pub punisher_id: Option<String>,
+    pub punisher_name: Option<String>,
+    pub count: u64
+}
+
+// unset fields are left as they are
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PunishmentEditRequest {
+    pub editor: SimplePlayer,
+    pub justification: String,
+    #[serde(default)]
+    pub length: Option<i64>,
+    // name of the new punishment type
+    #[serde(default)]
+    pub reason: Option<String>,
+    // an empty note clears it
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub silent: Option<bool>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -5,8 +5,8 @@ use mongodb::bson::doc;
 use rocket::{Build, Rocket, serde::json::Json, State};
 use uuid::Uuid;
 
-use crate::{database::{Database, models::{player::Player, rank::Rank}}, http::rank::payload::RankCreateRequest, MarsAPIState, util::{auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, time::get_u64_time_millis}};
-use crate::database::models::player::SimplePlayer;
+use crate::{database::{Database, models::{player::Player, rank::Rank}}, http::rank::payload::RankCreateRequest, MarsAPIState, util::{audit::AuditContext, auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, time::get_u64_time_millis}};
+use crate::database::models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, player::SimplePlayer};
 
 use self::payload::RankUpdateRequest;
 
@@ -16,7 +16,8 @@ mod payload;
 async fn create_rank(
     state: &State<MarsAPIState>, 
     create_req: Json<RankCreateRequest>,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<Json<Rank>, ApiErrorResponder> {
     let data = create_req.0;
     let conflict = state.database.find_by_name::<Rank>(&data.name).await;
@@ -41,6 +42,7 @@ async fn create_rank(
     };
 
     state.database.save(&rank).await;
+    audit.record_created(state, AuditAction::RankCreate, AuditTarget::new(AuditTargetKind::Rank, &rank.id, &rank.name), &rank).await;
 
     Ok(Json(rank))
 }
@@ -68,7 +70,8 @@ async fn get_players_by_rank_by_id(state: &State<MarsAPIState>, rank_id: &str) -
 
 
 #[delete("/<rank_id>")]
-async fn delete_rank(state: &State<MarsAPIState>, rank_id: &str, _auth_guard: AuthorizationToken) -> Result<(), ApiErrorResponder> {
+async fn delete_rank(state: &State<MarsAPIState>, rank_id: &str, _auth_guard: AuthorizationToken, audit: AuditContext) -> Result<(), ApiErrorResponder> {
+    let rank = unwrap_helper::return_default!(Database::find_by_id(&state.database.ranks, rank_id).await, Err(ApiErrorResponder::missing_rank()));
     let delete_count = match state.database.delete_by_id::<Rank>(rank_id).await {
         Some(delete_result) => delete_result.deleted_count,
         None => 0
@@ -95,6 +98,7 @@ async fn delete_rank(state: &State<MarsAPIState>, rank_id: &str, _auth_guard: Au
     join_all(cache_updates).await;
 
     info!("Rank '{}' was deleted. Affected players: {}", rank_id, formatted_player_names);
+    audit.record_deleted(state, AuditAction::RankDelete, AuditTarget::new(AuditTargetKind::Rank, &rank.id, &rank.name), &rank).await;
     Ok(())
 }
 
@@ -103,7 +107,8 @@ async fn update_rank(
     state: &State<MarsAPIState>, 
     rank_update_req: Json<RankUpdateRequest>, 
     rank_id: &str, 
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<Json<Rank>, ApiErrorResponder> {
     let data = rank_update_req.0;
     let existing_rank = unwrap_helper::return_default!(Database::find_by_id(&state.database.ranks, rank_id).await, Err(ApiErrorResponder::missing_rank()));
@@ -116,7 +121,7 @@ async fn update_rank(
     let mut perms = data.permissions;
     perms.dedup();
     let updated_rank = Rank { 
-        id: existing_rank.id, 
+        id: existing_rank.id.clone(), 
         name: data.name, 
         name_lower: rank_lower_name,
         display_name: data.display_name, 
@@ -129,6 +134,7 @@ async fn update_rank(
     };
 
     state.database.save(&updated_rank).await;
+    audit.record_changed(state, AuditAction::RankUpdate, AuditTarget::new(AuditTargetKind::Rank, &updated_rank.id, &updated_rank.name), &existing_rank, &updated_rank).await;
     Ok(Json(updated_rank))
 }
 
@@ -1,29 +1,180 @@
 mod payload;
 
+use mongodb::{bson::{doc, Document}, options::FindOptions};
 use rocket::{serde::json::Json, State, Build, Rocket};
+use uuid::Uuid;
 
-use crate::{util::{auth::AuthorizationToken, error::ApiErrorResponder}, MarsAPIState};
+use crate::{database::{models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, report::{Report, ReportStatus, ReportSubmission}}, Database}, socket::server::server_context::get_current_match_id_key, util::{audit::AuditContext, auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, responder::JsonResponder, time::get_u64_time_millis}, MarsAPIState};
 
-use self::payload::ReportCreateRequest;
+use self::payload::{ReportAssignRequest, ReportCreateRequest, ReportStatusRequest};
 
+fn get_cooldown_key(reporter_id: &str) -> String {
+    format!("report:cooldown:{}", reporter_id)
+}
 
 #[post("/", format = "json", data = "<report>")]
 pub async fn new_report(
     state: &State<MarsAPIState>,
     report: Json<ReportCreateRequest>,
     auth_guard: AuthorizationToken,
-) -> Result<(), ApiErrorResponder> {
+) -> Result<JsonResponder<Report>, ApiErrorResponder> {
     let data = report.0;
+    let options = &state.config.options;
+    if options.report_cooldown_seconds > 0 {
+        let recent = state.redis.increment_with_expiry(&get_cooldown_key(&data.reporter.id), (options.report_cooldown_seconds * 1000) as usize).await.unwrap_or(0);
+        if recent > 1 {
+            return Err(ApiErrorResponder::report_cooldown());
+        };
+    };
+
+    let now = get_u64_time_millis();
+    let submission = ReportSubmission { reporter: data.reporter.clone(), reason: data.reason.clone(), server_id: auth_guard.server_id.clone(), created_at: now };
+    let merge_since = now.saturating_sub(options.report_merge_window_minutes * 60_000);
+    if let Some(existing) = state.database.merge_into_report(&data.target.id, merge_since, &submission, &data.message_ids).await {
+        return Ok(JsonResponder::ok(existing));
+    };
+
+    let messages = state.database.get_chat_messages_by_ids(&data.message_ids).await;
+    let report = Report {
+        id: Uuid::new_v4().to_string(),
+        target: data.target,
+        reporter: data.reporter,
+        reason: data.reason,
+        server_id: auth_guard.server_id.clone(),
+        match_id: state.redis.get_unchecked(&get_current_match_id_key(&auth_guard.server_id)).await,
+        online_staff: data.online_staff,
+        message_ids: data.message_ids,
+        status: ReportStatus::Open,
+        assignee: None,
+        punishment_id: None,
+        count: 1,
+        submissions: vec![submission],
+        created_at: now,
+        last_reported_at: now,
+        closed_at: None
+    };
+    state.database.insert_one(&report).await;
     state.config.webhooks.send_report_webhook(
-        &auth_guard.server_id,
-        &data.reporter, 
-        &data.target, 
-        &data.reason, 
-        &data.online_staff
+        &report.server_id,
+        &report.reporter, 
+        &report.target, 
+        &report.reason, 
+        &report.online_staff,
+        &messages
+    ).await;
+    Ok(JsonResponder::created(report))
+}
+
+#[get("/?<status>&<target>&<assignee>&<before>&<limit>")]
+pub async fn get_reports(
+    state: &State<MarsAPIState>,
+    status: Option<&str>,
+    target: Option<&str>,
+    assignee: Option<&str>,
+    before: Option<u64>,
+    limit: Option<i64>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Vec<Report>>, ApiErrorResponder> {
+    let mut filter = Document::new();
+    if let Some(status) = status {
+        filter.insert("status", status.to_uppercase());
+    };
+    if let Some(target) = target {
+        let target = unwrap_helper::return_default!(state.player_cache.get(&state.database, target).await, Err(ApiErrorResponder::missing_player()));
+        filter.insert("target.id", target.id);
+    };
+    if let Some(assignee) = assignee {
+        let assignee = unwrap_helper::return_default!(state.player_cache.get(&state.database, assignee).await, Err(ApiErrorResponder::missing_player()));
+        filter.insert("assignee.id", assignee.id);
+    };
+    if let Some(before) = before {
+        filter.insert("createdAt", doc! { "$lt": before as i64 });
+    };
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+    let opts = FindOptions::builder().sort(doc! { "createdAt": -1 }).limit(limit).build();
+    let cursor = state.database.reports.find(filter, Some(opts)).await.ok();
+    Ok(JsonResponder::ok(Database::consume_cursor_into_owning_vec_option(cursor).await))
+}
+
+#[get("/<report_id>")]
+pub async fn get_report(
+    state: &State<MarsAPIState>,
+    report_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Report>, ApiErrorResponder> {
+    let report = unwrap_helper::return_default!(Database::find_by_id(&state.database.reports, report_id).await, Err(ApiErrorResponder::report_missing()));
+    Ok(JsonResponder::ok(report))
+}
+
+#[post("/<report_id>/assign", format = "json", data = "<assign_req>")]
+pub async fn assign_report(
+    state: &State<MarsAPIState>,
+    report_id: &str,
+    assign_req: Json<ReportAssignRequest>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<Report>, ApiErrorResponder> {
+    let mut report = unwrap_helper::return_default!(Database::find_by_id(&state.database.reports, report_id).await, Err(ApiErrorResponder::report_missing()));
+    if !report.status.is_active() {
+        return Err(ApiErrorResponder::report_closed());
+    };
+    let before = report.clone();
+    let assignee = assign_req.0.assignee;
+    report.assignee = Some(assignee.clone());
+    report.status = ReportStatus::Claimed;
+    state.database.save(&report).await;
+    audit.acting_as(Some(&assignee)).record_changed(
+        state, AuditAction::ReportAssign, AuditTarget::new(AuditTargetKind::Report, &report.id, &report.target.name), &before, &report
+    ).await;
+    Ok(JsonResponder::ok(report))
+}
+
+#[post("/<report_id>/status", format = "json", data = "<status_req>")]
+pub async fn update_report_status(
+    state: &State<MarsAPIState>,
+    report_id: &str,
+    status_req: Json<ReportStatusRequest>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<Report>, ApiErrorResponder> {
+    let data = status_req.0;
+    let mut report = unwrap_helper::return_default!(Database::find_by_id(&state.database.reports, report_id).await, Err(ApiErrorResponder::report_missing()));
+    if !report.status.is_active() {
+        return Err(ApiErrorResponder::report_closed());
+    };
+    let before = report.clone();
+    let audit = audit.acting_as(Some(&data.staff));
+    if let Some(punishment_id) = &data.punishment_id {
+        if Database::find_by_id(&state.database.punishments, punishment_id).await.is_none() {
+            return Err(ApiErrorResponder::missing_punishment());
+        };
+        report.punishment_id = Some(punishment_id.clone());
+    };
+    match data.status {
+        // unclaiming hands the report back to the queue
+        ReportStatus::Open => report.assignee = None,
+        ReportStatus::Claimed => report.assignee = Some(data.staff),
+        ReportStatus::Resolved | ReportStatus::Dismissed => {
+            if report.assignee.is_none() {
+                report.assignee = Some(data.staff);
+            };
+            report.closed_at = Some(get_u64_time_millis());
+        }
+    };
+    report.status = data.status;
+    state.database.save(&report).await;
+    audit.record_changed(
+        state, AuditAction::ReportStatusUpdate, AuditTarget::new(AuditTargetKind::Report, &report.id, &report.target.name), &before, &report
     ).await;
-    Ok(())
+    Ok(JsonResponder::ok(report))
 }
 
-pub fn mount(rocket_build: Rocket<Build>, state: &MarsAPIState) -> Rocket<Build> {
-    rocket_build.mount("/mc/reports", routes![new_report])
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/reports", routes![
+        new_report,
+        get_reports,
+        get_report,
+        assign_report,
+        update_report_status
+    ])
 }
@@ -1,6 +1,6 @@
 use serde::{Serialize, Deserialize};
 
-use crate::database::models::player::SimplePlayer;
+use crate::database::models::{player::SimplePlayer, report::ReportStatus};
 
 #[derive(Serialize, Deserialize)]
 pub struct ReportCreateRequest {
@@ -9,4 +9,22 @@ pub struct ReportCreateRequest {
     pub reason: String,
     #[serde(rename = "onlineStaff")]
     pub online_staff: Vec<SimplePlayer>,
+    #[serde(default, rename = "messageIds")]
+    pub message_ids: Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportAssignRequest {
+    pub assignee: SimplePlayer
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportStatusRequest {
+    pub status: ReportStatus,
+    pub staff: SimplePlayer,
+    // the punishment the report resulted in
+    #[serde(default)]
+    pub punishment_id: Option<String>
 }
@@ -2,7 +2,7 @@ use futures::future::join_all;
 use mongodb::bson::doc;
 use rocket::{Rocket, Build, State, http::Status, serde::json::Json};
 
-use crate::{MarsAPIState, util::{auth::AuthorizationToken, error::ApiErrorResponder, time::get_u64_time_millis, r#macro::unwrap_helper, responder::JsonResponder}, database::{models::{r#match::Match, session::Session, player::Player, server::ServerEvents}, Database}, http::server::payloads::{ServerStatusResponse, XPMultiplierRequest}};
+use crate::{MarsAPIState, util::{audit::AuditContext, auth::AuthorizationToken, error::ApiErrorResponder, time::get_u64_time_millis, r#macro::unwrap_helper, responder::JsonResponder}, database::{models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, r#match::Match, session::Session, player::Player, server::ServerEvents}, Database}, http::server::payloads::{ServerStatusResponse, XPMultiplierRequest}};
 
 pub mod payloads;
 
@@ -113,7 +113,8 @@ async fn xp_multiplier_event(
     state: &State<MarsAPIState>, 
     server_id: &str,
     xp_multiplier_request: Json<XPMultiplierRequest>,
-    auth_guard: AuthorizationToken
+    auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<ServerEvents>, ApiErrorResponder> {
     if server_id != auth_guard.server_id {
         return Err(ApiErrorResponder::unauthorized());
@@ -121,8 +122,12 @@ async fn xp_multiplier_event(
     let mut events : ServerEvents = state.redis.get_unchecked(&format!("server:{}:events", server_id)).await.unwrap_or(ServerEvents { 
         xp_multiplier: None  
     });
+    let before = events.clone();
     events.xp_multiplier = if xp_multiplier_request.value == 1f32 { None } else { Some(xp_multiplier_request.to_xp_multiplier()) };
     state.redis.set(&format!("server:{}:events", server_id), &events).await;
+    audit.acting_as(events.xp_multiplier.as_ref().and_then(|multiplier| multiplier.player.as_ref())).record_changed(
+        state, AuditAction::XpMultiplierUpdate, AuditTarget::new(AuditTargetKind::Server, server_id, server_id), &before, &events
+    ).await;
     Ok(JsonResponder::ok(events))
 }
 
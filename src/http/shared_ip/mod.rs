@@ -0,0 +1,94 @@
+mod payload;
+
+use ipnet::IpNet;
+use mongodb::{bson::doc, options::FindOptions, results::DeleteResult};
+use rocket::{serde::json::Json, Build, Rocket, State};
+use uuid::Uuid;
+
+use crate::{database::{models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, shared_ip::{SharedIp, SharedIpKind}}, Database}, http::player::hash_ip, util::{audit::AuditContext, auth::AuthorizationToken, error::ApiErrorResponder, r#macro::unwrap_helper, responder::JsonResponder, time::get_u64_time_millis}, MarsAPIState};
+
+use self::payload::SharedIpCreateRequest;
+
+#[post("/", format = "json", data = "<create_req>")]
+pub async fn add_shared_ip(
+    state: &State<MarsAPIState>,
+    create_req: Json<SharedIpCreateRequest>,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<JsonResponder<SharedIp>, ApiErrorResponder> {
+    let data = create_req.0;
+    let raw_value = data.value.trim().to_lowercase();
+    let kind = unwrap_helper::return_default!(
+        SharedIpKind::detect(&raw_value),
+        Err(ApiErrorResponder::validation_error_with_message("Value must be an IP, a CIDR range or an IP hash"))
+    );
+    let (kind, value, key) = match kind {
+        // store ranges by their network address so equivalent ranges collide
+        SharedIpKind::Cidr => (kind, raw_value.parse::<IpNet>().map(|range| range.trunc().to_string()).unwrap_or(raw_value), None),
+        // with hashing on the raw address is not kept, same as everywhere else
+        SharedIpKind::Ip if state.config.ip_hasher.is_enabled() => (SharedIpKind::Hash, hash_ip(state, &raw_value), None),
+        SharedIpKind::Ip => (kind, raw_value.clone(), Some(raw_value)),
+        SharedIpKind::Hash => (kind, raw_value, None)
+    };
+    if state.database.shared_ips.find_one(doc! { "value": &value }, None).await.ok().flatten().is_some() {
+        return Err(ApiErrorResponder::shared_ip_conflict());
+    };
+
+    let shared_ip = SharedIp {
+        id: Uuid::new_v4().to_string(),
+        value,
+        kind,
+        key,
+        reason: data.reason,
+        added_by: data.added_by,
+        created_at: get_u64_time_millis()
+    };
+    state.database.insert_one(&shared_ip).await;
+    state.database.invalidate_shared_ip_list().await;
+    audit.acting_as(Some(&shared_ip.added_by)).record_created(
+        state, AuditAction::SharedIpAdd, AuditTarget::new(AuditTargetKind::SharedIp, &shared_ip.id, &shared_ip.value), &shared_ip
+    ).await;
+    Ok(JsonResponder::created(shared_ip))
+}
+
+#[get("/")]
+pub async fn get_shared_ips(
+    state: &State<MarsAPIState>,
+    _auth_guard: AuthorizationToken
+) -> JsonResponder<Vec<SharedIp>> {
+    let opts = FindOptions::builder().sort(doc! { "createdAt": -1 }).build();
+    let cursor = state.database.shared_ips.find(doc! {}, Some(opts)).await.ok();
+    JsonResponder::ok(Database::consume_cursor_into_owning_vec_option(cursor).await)
+}
+
+#[delete("/<shared_ip_id>")]
+pub async fn remove_shared_ip(
+    state: &State<MarsAPIState>,
+    shared_ip_id: &str,
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
+) -> Result<(), ApiErrorResponder> {
+    let shared_ip = unwrap_helper::return_default!(
+        Database::find_by_id(&state.database.shared_ips, shared_ip_id).await,
+        Err(ApiErrorResponder::shared_ip_missing())
+    );
+    match state.database.delete_by_id::<SharedIp>(shared_ip_id).await {
+        Some(DeleteResult { deleted_count: 0, .. }) | None => {
+            return Err(ApiErrorResponder::shared_ip_missing());
+        },
+        _ => {}
+    };
+    state.database.invalidate_shared_ip_list().await;
+    audit.record_deleted(
+        state, AuditAction::SharedIpRemove, AuditTarget::new(AuditTargetKind::SharedIp, &shared_ip.id, &shared_ip.value), &shared_ip
+    ).await;
+    Ok(())
+}
+
+pub fn mount(rocket_build: Rocket<Build>, _state: &MarsAPIState) -> Rocket<Build> {
+    rocket_build.mount("/mc/shared-ips", routes![
+        add_shared_ip,
+        get_shared_ips,
+        remove_shared_ip
+    ])
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::models::player::SimplePlayer;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedIpCreateRequest {
+    // an ip, a CIDR range or an already hashed ip
+    pub value: String,
+    pub reason: String,
+    pub added_by: SimplePlayer
+}
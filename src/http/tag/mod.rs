@@ -3,7 +3,7 @@ use mongodb::{results::DeleteResult, bson::doc};
 use rocket::{State, Rocket, Build, http::Status, serde::json::Json};
 use uuid::Uuid;
 
-use crate::{util::{auth::AuthorizationToken, responder::JsonResponder, error::{ApiErrorResponder}, time::get_u64_time_millis, r#macro::unwrap_helper}, MarsAPIState, database::{models::tag::Tag, Database}};
+use crate::{util::{audit::AuditContext, auth::AuthorizationToken, responder::JsonResponder, error::{ApiErrorResponder}, time::get_u64_time_millis, r#macro::unwrap_helper}, MarsAPIState, database::{models::{audit_entry::{AuditAction, AuditTarget, AuditTargetKind}, tag::Tag}, Database}};
 
 use self::payload::TagCreateRequest;
 
@@ -13,7 +13,8 @@ mod payload;
 async fn create_tag(
     state: &State<MarsAPIState>,
     tag_create_req: Json<TagCreateRequest>,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<Tag>, ApiErrorResponder> {
     match state.database.find_by_id_or_name::<Tag>(&tag_create_req.name).await {
         Some(_tag) => return Err(ApiErrorResponder::tag_conflict()),
@@ -31,6 +32,7 @@ async fn create_tag(
     };
 
     state.database.save::<Tag>(&tag).await;
+    audit.record_created(state, AuditAction::TagCreate, AuditTarget::new(AuditTargetKind::Tag, &tag.id, &tag.name), &tag).await;
     return Ok(JsonResponder::from(tag, Status::Ok));
 }
 
@@ -77,8 +79,10 @@ async fn get_tag_by_id(
 async fn delete_tag(
     state: &State<MarsAPIState>,
     tag_id: &str,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<(), ApiErrorResponder> {
+    let tag = unwrap_helper::return_default!(Database::find_by_id(&state.database.tags, tag_id).await, Err(ApiErrorResponder::tag_missing()));
     match state.database.delete_by_id::<Tag>(tag_id).await {
         Some(DeleteResult { deleted_count: 0, .. }) | None => {
             return Err(ApiErrorResponder::tag_missing());
@@ -106,6 +110,7 @@ async fn delete_tag(
             .collect::<Vec<String>>()
             .join(", ")
     );
+    audit.record_deleted(state, AuditAction::TagDelete, AuditTarget::new(AuditTargetKind::Tag, &tag.id, &tag.name), &tag).await;
     Ok(())
 }
 
@@ -114,7 +119,8 @@ async fn update_tag(
     state: &State<MarsAPIState>,
     tag_update_req: Json<TagCreateRequest>,
     tag_id: &str,
-    _auth_guard: AuthorizationToken
+    _auth_guard: AuthorizationToken,
+    audit: AuditContext
 ) -> Result<JsonResponder<Tag>, ApiErrorResponder> {
     match state.database.find_by_id_or_name::<Tag>(tag_id).await {
         Some(tag) => {
@@ -134,6 +140,7 @@ async fn update_tag(
                     ).await;
                 }
             };
+            audit.record_changed(state, AuditAction::TagUpdate, AuditTarget::new(AuditTargetKind::Tag, &tag.id, &updated_tag.name), &tag, &updated_tag).await;
             Ok(JsonResponder::ok(updated_tag))
         }
         None => {
@@ -0,0 +1,9 @@
+use crate::MarsAPIState;
+
+pub mod stat_snapshot;
+pub mod punishment_expiry;
+
+pub fn spawn_jobs(state: &MarsAPIState) {
+    tokio::spawn(stat_snapshot::run_stat_snapshot_scheduler(state.clone()));
+    tokio::spawn(punishment_expiry::run_punishment_expiry_scheduler(state.clone()));
+}
@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use mongodb::{bson::{doc, Bson, Document}, options::FindOptions};
+
+use crate::{database::{models::punishment::Punishment, Database}, socket::{player::player_context::send_player_update_to_online_player, update::player_update_listener::{PlayerUpdateData, PlayerUpdateReason}}, util::time::get_u64_time_millis, MarsAPIState};
+
+// punishments issued while sleeping are only noticed on the next wake-up
+const MAX_SLEEP_MILLIS : u64 = 30_000;
+// expiries older than this (punishments from before the job existed, or downtime) are marked without being announced
+const ANNOUNCE_GRACE_MILLIS : u64 = 600_000;
+const PAGE_SIZE : i64 = 250;
+
+// nothing is kept in memory, every pass re-reads what is due from mongo so restarts lose nothing
+pub async fn run_punishment_expiry_scheduler(state: MarsAPIState) {
+    loop {
+        let now = get_u64_time_millis();
+        mark_stale_expiries(&state.database, now).await;
+        let due = get_due_punishments(&state.database, now).await;
+        let more = due.len() as i64 >= PAGE_SIZE;
+        for punishment in due {
+            expire_punishment(&state, punishment, now).await;
+        };
+        if more {
+            continue;
+        };
+        let next = get_next_expiry(&state.database).await.unwrap_or(u64::MAX);
+        let sleep_millis = next.saturating_sub(get_u64_time_millis()).clamp(1_000, MAX_SLEEP_MILLIS);
+        tokio::time::sleep(Duration::from_millis(sleep_millis)).await;
+    }
+}
+
+// timed punishments that have not been reverted or processed yet, permanent (-1) and instant (0) ones never expire
+fn get_pending_filter() -> Document {
+    doc! { "expiredAt": Bson::Null, "reversion": Bson::Null, "action.length": { "$gt": 0 } }
+}
+
+fn get_expired_before_filter(time: u64) -> Document {
+    let mut filter = get_pending_filter();
+    filter.insert("$expr", doc! { "$lte": [{ "$add": ["$issuedAt", "$action.length"] }, time as f64] });
+    filter
+}
+
+async fn mark_stale_expiries(database: &Database, now: u64) {
+    let filter = get_expired_before_filter(now.saturating_sub(ANNOUNCE_GRACE_MILLIS));
+    match database.punishments.update_many(filter, doc! { "$set": { "expiredAt": now as i64 } }, None).await {
+        Ok(result) if result.modified_count > 0 => info!("Marked {} long expired punishment(s) as expired without announcing them", result.modified_count),
+        Ok(_) => {},
+        Err(e) => warn!("Could not mark long expired punishments: {}", e)
+    };
+}
+
+async fn get_due_punishments(database: &Database, now: u64) -> Vec<Punishment> {
+    let opts = FindOptions::builder().limit(PAGE_SIZE).build();
+    let cursor = database.punishments.find(get_expired_before_filter(now), Some(opts)).await.ok();
+    Database::consume_cursor_into_owning_vec_option(cursor).await
+}
+
+async fn get_next_expiry(database: &Database) -> Option<u64> {
+    let pipeline = vec![
+        doc! { "$match": get_pending_filter() },
+        doc! { "$project": { "expiresAt": { "$add": ["$issuedAt", "$action.length"] } } },
+        doc! { "$sort": { "expiresAt": 1 } },
+        doc! { "$limit": 1 }
+    ];
+    let mut cursor = database.punishments.aggregate(pipeline, None).await.ok()?;
+    let result = cursor.next().await?.ok()?;
+    match result.get("expiresAt")? {
+        Bson::Double(expires_at) => Some(*expires_at as u64),
+        Bson::Int64(expires_at) => Some(*expires_at as u64),
+        Bson::Int32(expires_at) => Some(*expires_at as u64),
+        _ => None
+    }
+}
+
+async fn expire_punishment(state: &MarsAPIState, mut punishment: Punishment, now: u64) {
+    // the expiredAt guard keeps a second instance from announcing the same expiry
+    let result = state.database.punishments.update_one(
+        doc! { "_id": &punishment.id, "expiredAt": Bson::Null },
+        doc! { "$set": { "expiredAt": now as i64 } },
+        None
+    ).await;
+    match result {
+        Ok(result) if result.modified_count > 0 => {},
+        Ok(_) => return,
+        Err(e) => {
+            warn!("Could not mark punishment {} as expired: {}", punishment.id, e);
+            return;
+        }
+    };
+    punishment.expired_at = Some(now);
+    {
+        // take ownership for the spawned task
+        let pun_clone = punishment.clone();
+        let state_clone = state.config.clone();
+        tokio::spawn(async move {
+            state_clone.webhooks.send_punishment_expiry_webhook(&pun_clone).await;
+        });
+    }
+    // lets the server lift a mute without waiting for the player to rejoin
+    if let Some(target) = state.player_cache.get(&state.database, &punishment.target.id).await {
+        send_player_update_to_online_player(
+            state, 
+            target, 
+            PlayerUpdateData::PunishmentUpdateData { punishment: Box::new(punishment) }, 
+            PlayerUpdateReason::PunishmentExpire
+        ).await;
+    };
+}
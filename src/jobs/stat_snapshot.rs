@@ -0,0 +1,129 @@
+use std::{collections::HashMap, time::Duration};
+
+use futures::StreamExt;
+use mongodb::bson::{self, doc};
+
+use crate::{database::{models::{player::Player, stat_snapshot::{PlayerStatSnapshot, StatSnapshotValues}}, Database}, util::time::get_u64_time_millis, MarsAPIState};
+
+const LAST_SNAPSHOT_KEY : &str = "stat_snapshot:last_day";
+const DAY_MILLIS : u64 = 86_400_000;
+const PLAYER_BATCH_SIZE : usize = 500;
+
+pub async fn run_stat_snapshot_scheduler(state: MarsAPIState) {
+    loop {
+        let now = get_u64_time_millis();
+        let today = now - (now % DAY_MILLIS);
+        let yesterday = today - DAY_MILLIS;
+        // the last snapshotted day is kept in redis so restarts neither skip nor repeat a day
+        let last_snapshot_day : Option<u64> = state.redis.get_unchecked(LAST_SNAPSHOT_KEY).await;
+        if last_snapshot_day.map(|day| day < yesterday).unwrap_or(true) {
+            let t1 = get_u64_time_millis();
+            let count = snapshot_day(&state, yesterday).await;
+            state.redis.set(LAST_SNAPSHOT_KEY, &yesterday).await;
+            info!("Stored {} player stat snapshots in {}ms", count, get_u64_time_millis() - t1);
+            prune_snapshots(&state, now).await;
+        };
+        tokio::time::sleep(Duration::from_millis(today + DAY_MILLIS - now + 60_000)).await;
+    }
+}
+
+async fn snapshot_day(state: &MarsAPIState, day: u64) -> usize {
+    let active_ids : Vec<String> = match state.database.sessions.distinct("player.id", doc! {
+        "createdAt": { "$lt": (day + DAY_MILLIS) as i64 },
+        "$or": [{ "endedAt": null }, { "endedAt": { "$gte": day as i64 } }]
+    }, None).await {
+        Ok(ids) => ids.into_iter().filter_map(|id| id.as_str().map(String::from)).collect(),
+        Err(e) => {
+            warn!("Could not find active players for stat snapshot: {}", e);
+            return 0;
+        }
+    };
+
+    let mut count = 0;
+    for chunk in active_ids.chunks(PLAYER_BATCH_SIZE) {
+        let players = get_fresh_players(state, chunk).await;
+        let previous = get_latest_snapshots(&state.database, chunk, day).await;
+        for player in players.iter() {
+            let snapshot = create_snapshot(player, day, previous.get(&player.id));
+            state.database.save(&snapshot).await;
+            count += 1;
+        };
+    };
+    count
+}
+
+// mongo can lag behind the cache for players who are mid-session
+async fn get_fresh_players(state: &MarsAPIState, ids: &[String]) -> Vec<Player> {
+    let stored = Database::consume_cursor_into_owning_vec_option(
+        state.database.players.find(doc! { "_id": { "$in": ids } }, None).await.ok()
+    ).await;
+    let names : Vec<String> = stored.iter().map(|player| player.name.clone()).collect();
+    let cached = state.player_cache.query_many(&names).await;
+    stored.into_iter().zip(cached).map(|(stored, cached)| {
+        match cached {
+            Some(cached) if cached.id == stored.id => cached,
+            _ => stored
+        }
+    }).collect()
+}
+
+async fn get_latest_snapshots(database: &Database, ids: &[String], before: u64) -> HashMap<String, PlayerStatSnapshot> {
+    let pipeline = vec![
+        doc! { "$match": { "playerId": { "$in": ids }, "date": { "$lt": before as i64 } } },
+        doc! { "$sort": { "date": -1 } },
+        doc! { "$group": { "_id": "$playerId", "snapshot": { "$first": "$$ROOT" } } }
+    ];
+    let mut cursor = match database.stat_snapshots.aggregate(pipeline, None).await {
+        Ok(cursor) => cursor,
+        Err(_) => return HashMap::new()
+    };
+    let mut snapshots = HashMap::new();
+    while let Some(Ok(grouped)) = cursor.next().await {
+        let snapshot = match grouped.get_document("snapshot").map(|snapshot| bson::from_document::<PlayerStatSnapshot>(snapshot.clone())) {
+            Ok(Ok(snapshot)) => snapshot,
+            _ => continue
+        };
+        snapshots.insert(snapshot.player_id.clone(), snapshot);
+    };
+    snapshots
+}
+
+fn create_snapshot(player: &Player, day: u64, previous: Option<&PlayerStatSnapshot>) -> PlayerStatSnapshot {
+    let totals = StatSnapshotValues::of(&player.stats);
+    let gamemode_totals : HashMap<_, _> = player.gamemode_stats.iter()
+        .map(|(gamemode, stats)| (gamemode.clone(), StatSnapshotValues::of(stats)))
+        .collect();
+    let (delta, gamemode_deltas) = match previous {
+        Some(previous) => {
+            let delta = totals.since(&previous.totals);
+            let gamemode_deltas = gamemode_totals.iter().filter_map(|(gamemode, values)| {
+                let previous_values = previous.gamemode_totals.get(gamemode).cloned().unwrap_or_default();
+                let gamemode_delta = values.since(&previous_values);
+                if gamemode_delta.is_empty() { None } else { Some((gamemode.clone(), gamemode_delta)) }
+            }).collect();
+            (delta, gamemode_deltas)
+        },
+        None => (StatSnapshotValues::default(), HashMap::new())
+    };
+    PlayerStatSnapshot {
+        id: PlayerStatSnapshot::create_id(&player.id, day),
+        player_id: player.id.clone(),
+        date: day,
+        baseline: previous.is_none(),
+        totals,
+        delta,
+        gamemode_totals,
+        gamemode_deltas
+    }
+}
+
+async fn prune_snapshots(state: &MarsAPIState, now: u64) {
+    let retention_days = state.config.options.stat_snapshot_retention_days;
+    if retention_days == 0 {
+        return;
+    };
+    let cutoff = now.saturating_sub(retention_days * DAY_MILLIS);
+    if let Err(e) = state.database.stat_snapshots.delete_many(doc! { "date": { "$lt": cutoff as i64 } }, None).await {
+        warn!("Could not prune stat snapshots: {}", e);
+    };
+}
@@ -8,7 +8,7 @@ use database::{Database, cache::{Cache, get_redis_pool, RedisAdapter}, models::{
 use http::map::{MapState, image::{ImageState, image_queue_processor}};
 use rocket::{figment::Figment, http::Method, Build, Config, Rocket, Shutdown};
 use rocket_cors::{AllowedOrigins, CorsOptions};
-use socket::leaderboard::MarsLeaderboards;
+use socket::{leaderboard::MarsLeaderboards, server::server_registry::ServerRegistry};
 use tokio::sync::{RwLock, Semaphore};
 use crate::database::migrations::MigrationExecutor;
 
@@ -19,6 +19,7 @@ mod config;
 mod database;
 mod http;
 mod socket;
+mod jobs;
 
 fn setup_logger() -> Result<(), fern::InitError> {
     fern::Dispatch::new()
@@ -49,7 +50,8 @@ pub struct MarsAPIState {
     pub match_cache: Arc<Cache<Match>>,
     pub leaderboards: Arc<MarsLeaderboards>,
     pub image_state: Arc<Option<ImageState>>,
-    pub map_state: Arc<MapState>
+    pub map_state: Arc<MapState>,
+    pub server_registry: Arc<ServerRegistry>
 }
 
 fn rocket(state: MarsAPIState) -> Rocket<Build> {
@@ -67,7 +69,16 @@ fn rocket(state: MarsAPIState) -> Rocket<Build> {
         &http::leaderboard::mount,
         &http::report::mount,
         &http::r#match::mount,
-        &http::achievements::mount
+        &http::achievements::mount,
+        &http::privacy::mount,
+        &http::friend::mount,
+        &http::clan::mount,
+        &http::currency::mount,
+        &http::chat::mount,
+        &http::appeal::mount,
+        &http::audit::mount,
+        &http::shared_ip::mount,
+        &http::gate::mount
     ];
     let is_debug = env::var("MARS_DEBUG").unwrap_or("false".to_owned()).parse::<bool>().unwrap_or(false);
     let http_port = env::var("MARS_HTTP_PORT").unwrap_or("8000".to_owned()).parse::<u32>().unwrap_or(8000);
@@ -211,7 +222,8 @@ async fn main() -> Result<(), String> {
             last_update: Arc::new(
                 RwLock::new(0)
             ) 
-        })
+        }),
+        server_registry: Arc::new(ServerRegistry::new())
     };
 
 
@@ -219,7 +231,7 @@ async fn main() -> Result<(), String> {
         let migration = env::var("MARS_DATABASE_MIGRATION").unwrap_or("NONE".to_owned());
         info!("API will not run, migration is set");
         info!("Executing migration '{}'...", migration.to_owned());
-        let migration_executor = MigrationExecutor::new();
+        let migration_executor = MigrationExecutor::new(&state.config);
         let migration_found = migration_executor.execute_migration_by_name(
             &*state.database,
             migration.to_owned()
@@ -230,6 +242,8 @@ async fn main() -> Result<(), String> {
         return Ok(());
     };
 
+    jobs::spawn_jobs(&state);
+
     let ws_port = env::var("MARS_WS_PORT").unwrap_or("7000".to_owned()).parse::<u32>().unwrap_or(7000);
     let res = tokio::try_join!(
         setup_rocket(state.clone()),
@@ -0,0 +1,234 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{database::models::{audit_entry::{AuditAction, AuditActor, AuditTarget, AuditTargetKind}, chat_filter::{ChatFilterAction, ChatFilterHit, ChatFilterMatcher, ChatFilterRule}, player::SimplePlayer, punishment::{Punishment, PunishmentReason}}, socket::{event_type::EventType, player::player_events::PlayerChatData}, util::{audit::AuditContext, string::escape_regex, time::get_u64_time_millis}, MarsAPIState};
+
+const URL_PATTERN : &str = r"(?i)\b(?:https?://)?((?:[a-z0-9-]+\.)+[a-z]{2,})(?::\d{1,5})?(?:/\S*)?";
+const ADVERTISING_PATTERN : &str = r"(?i)\b(?:\d{1,3}[.,]){3}\d{1,3}(?::\d{1,5})?\b|\b(?:play|mc|pvp|hub|server)\s*[.,]\s*[a-z0-9-]+\s*[.,]\s*[a-z]{2,}\b";
+
+// rules with their patterns compiled once at startup
+#[derive(Default)]
+pub struct ChatFilter {
+    rules: Vec<CompiledChatFilterRule>
+}
+
+struct CompiledChatFilterRule {
+    rule: ChatFilterRule,
+    pattern: Option<Regex>
+}
+
+#[derive(Serialize, Deserialize)]
+struct RepeatState {
+    message: String,
+    count: u32
+}
+
+impl ChatFilter {
+    // invalid rules are skipped so one bad pattern does not disable filtering
+    pub fn compile(rules: &[ChatFilterRule]) -> Self {
+        let mut compiled = Vec::new();
+        // built-in patterns are shared by every rule that uses them
+        let urls = Regex::new(URL_PATTERN);
+        let advertising = Regex::new(ADVERTISING_PATTERN);
+        for rule in rules.iter() {
+            let pattern = match &rule.matcher {
+                ChatFilterMatcher::Regex { pattern } => Some(Regex::new(pattern)),
+                ChatFilterMatcher::Wordlist { words } => {
+                    // an empty alternation would match every message
+                    if words.iter().all(|word| word.is_empty()) {
+                        warn!("Skipping chat filter rule {}: the wordlist is empty", rule.id);
+                        continue;
+                    };
+                    let alternatives = words.iter().filter(|word| !word.is_empty()).map(|word| escape_regex(word)).collect::<Vec<_>>().join("|");
+                    Some(Regex::new(&format!(r"(?i)\b(?:{})\b", alternatives)))
+                },
+                ChatFilterMatcher::Links { .. } => Some(urls.clone()),
+                ChatFilterMatcher::Advertising => Some(advertising.clone()),
+                ChatFilterMatcher::Spam { .. } | ChatFilterMatcher::Repeat { .. } => None
+            };
+            let pattern = match pattern.transpose() {
+                Ok(pattern) => pattern,
+                Err(e) => {
+                    warn!("Skipping chat filter rule {}: {}", rule.id, e);
+                    continue;
+                }
+            };
+            compiled.push(CompiledChatFilterRule { rule: rule.clone(), pattern });
+        }
+        ChatFilter { rules: compiled }
+    }
+
+    // every rule the message trips, in configuration order
+    pub async fn evaluate(&self, api_state: &MarsAPIState, data: &PlayerChatData) -> Vec<&ChatFilterRule> {
+        let mut hits = Vec::new();
+        for compiled in self.rules.iter() {
+            let rule = &compiled.rule;
+            if !rule.channels.is_empty() && !rule.channels.contains(&data.channel) {
+                continue;
+            };
+            let hit = match (&rule.matcher, &compiled.pattern) {
+                (ChatFilterMatcher::Links { allowed_domains }, Some(pattern)) => {
+                    pattern.captures_iter(&data.message).any(|captures| {
+                        let domain = captures.get(1).map(|domain| domain.as_str().to_lowercase()).unwrap_or_default();
+                        !allowed_domains.iter().any(|allowed| domain == *allowed || domain.ends_with(&format!(".{}", allowed)))
+                    })
+                },
+                (_, Some(pattern)) => pattern.is_match(&data.message),
+                (ChatFilterMatcher::Spam { max_messages, window_seconds }, None) => {
+                    let key = format!("chat_filter:spam:{}:{}", rule.id, data.player.id);
+                    let count = api_state.redis.increment_with_expiry(&key, (*window_seconds * 1000) as usize).await.unwrap_or(0);
+                    count > *max_messages
+                },
+                (ChatFilterMatcher::Repeat { max_repeats, window_seconds }, None) => {
+                    Self::is_repeated(api_state, rule, data, *max_repeats, *window_seconds).await
+                },
+                _ => false
+            };
+            if hit {
+                hits.push(rule);
+            };
+        }
+        hits
+    }
+
+    async fn is_repeated(api_state: &MarsAPIState, rule: &ChatFilterRule, data: &PlayerChatData, max_repeats: u32, window_seconds: u64) -> bool {
+        let key = format!("chat_filter:repeat:{}:{}", rule.id, data.player.id);
+        let normalized = data.message.trim().to_lowercase();
+        let count = match api_state.redis.get_unchecked::<RepeatState>(&key).await {
+            Some(state) if state.message == normalized => state.count + 1,
+            _ => 1
+        };
+        api_state.redis.set_with_expiry(&key, &RepeatState { message: normalized, count }, Some((window_seconds * 1000) as usize)).await;
+        count > max_repeats
+    }
+}
+
+// sent back to the origin server, `allowed` false means the message must not be shown
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatFilterVerdictData {
+    pub nonce: Option<String>,
+    pub player_id: String,
+    // the logged message, for servers to attach to reports and punishments
+    pub message_id: String,
+    pub allowed: bool,
+    pub action: Option<ChatFilterAction>,
+    pub rule_ids: Vec<String>
+}
+
+// broadcast to every server for online staff
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatFilterAlertData {
+    pub player: SimplePlayer,
+    pub server_id: String,
+    pub message: String,
+    pub rule_ids: Vec<String>,
+    pub action: ChatFilterAction
+}
+
+// evaluates the message, carries out the most severe action and records every hit
+pub async fn apply_chat_filter(api_state: &MarsAPIState, server_id: &str, data: &PlayerChatData, message_id: &str) -> ChatFilterVerdictData {
+    let hits = api_state.config.data.chat_filter.evaluate(api_state, data).await;
+    let action = hits.iter().map(|rule| rule.action.clone()).max();
+
+    let mut punishment = None;
+    if action == Some(ChatFilterAction::Punish) {
+        for rule in hits.iter().filter(|rule| rule.action == ChatFilterAction::Punish) {
+            punishment = issue_filter_punishment(api_state, rule, data, server_id, message_id).await;
+            if punishment.is_some() {
+                break;
+            };
+        };
+    };
+
+    let rule_ids : Vec<String> = hits.iter().map(|rule| rule.id.clone()).collect();
+    if let Some(action) = &action {
+        let alert = ChatFilterAlertData {
+            player: data.player.clone(),
+            server_id: server_id.to_owned(),
+            message: data.message.clone(),
+            rule_ids: rule_ids.clone(),
+            action: action.clone()
+        };
+        api_state.server_registry.broadcast(&EventType::ChatFilterAlert, alert, None).await;
+    };
+
+    let created_at = get_u64_time_millis();
+    for rule in hits.iter() {
+        let hit = ChatFilterHit {
+            id: Uuid::new_v4().to_string(),
+            rule_id: rule.id.clone(),
+            action: rule.action.clone(),
+            player: data.player.clone(),
+            server_id: server_id.to_owned(),
+            message: data.message.clone(),
+            message_id: Some(message_id.to_owned()),
+            punishment_id: punishment.as_ref().filter(|_| rule.action == ChatFilterAction::Punish).map(|punishment: &Punishment| punishment.id.clone()),
+            created_at
+        };
+        api_state.database.insert_one(&hit).await;
+    };
+
+    ChatFilterVerdictData {
+        nonce: data.nonce.clone(),
+        player_id: data.player.id.clone(),
+        message_id: message_id.to_owned(),
+        allowed: action.as_ref().map(|action| *action == ChatFilterAction::Flag).unwrap_or(true),
+        action,
+        rule_ids
+    }
+}
+
+async fn issue_filter_punishment(
+    api_state: &MarsAPIState, 
+    rule: &ChatFilterRule, 
+    data: &PlayerChatData, 
+    server_id: &str, 
+    message_id: &str
+) -> Option<Punishment> {
+    let type_name = rule.punishment_type.as_ref()?;
+    let punishment_type = match api_state.config.data.punishment_types.iter().find(|punishment_type| punishment_type.name == *type_name) {
+        Some(punishment_type) => punishment_type,
+        None => {
+            warn!("Chat filter rule {} references unknown punishment type {}", rule.id, type_name);
+            return None;
+        }
+    };
+    let player = api_state.player_cache.get(&api_state.database, &data.player.id).await?;
+    let prior = api_state.database.get_prior_offences(&player.id, punishment_type, api_state.config.options.punishment_offence_decay_days).await;
+    let offence = prior.len() as u32 + 1;
+    let action = punishment_type.get_action_for_offence(offence)?;
+    let punishment = Punishment {
+        id: Uuid::new_v4().to_string(),
+        reason: PunishmentReason::from(punishment_type),
+        issued_at: get_u64_time_millis() as f64,
+        silent: false,
+        offence,
+        action: action.clone(),
+        note: Some(format!("Chat filter rule {}", rule.id)),
+        punisher: None,
+        target: player.to_simple(),
+        target_ips: api_state.database.without_shared_ips(&api_state.config.ip_hasher, player.ips.clone()).await,
+        reversion: None,
+        server_id: Some(server_id.to_owned()),
+        message_ids: vec![message_id.to_owned()],
+        edits: Vec::new(),
+        expired_at: None
+    };
+    api_state.database.insert_one(&punishment).await;
+    let audit = AuditContext { actor: AuditActor { player: None, server_id: Some(server_id.to_owned()) } };
+    audit.record_created(
+        api_state, AuditAction::PunishmentIssue, AuditTarget::new(AuditTargetKind::Punishment, &punishment.id, &punishment.target.name), &punishment
+    ).await;
+    {
+        let pun_clone = punishment.clone();
+        let config_clone = api_state.config.clone();
+        tokio::spawn(async move {
+            config_clone.webhooks.send_punishment_webhook(&pun_clone).await;
+        });
+    }
+    api_state.server_registry.send(server_id, &EventType::PunishmentIssue, &punishment).await;
+    Some(punishment)
+}
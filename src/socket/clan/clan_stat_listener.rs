@@ -0,0 +1,66 @@
+use mongodb::{bson::doc, options::{FindOneAndUpdateOptions, ReturnDocument}};
+
+use crate::{database::models::{player::Player, r#match::Match}, socket::{participant::participant_context::PlayerMatchResult, player::player_listener::PlayerListener, r#match::match_events::MatchEndData, server::server_context::ServerContext}};
+
+// rolls each member's match contribution up into their clan's aggregate stats and clan leaderboards
+pub struct ClanStatListener {}
+
+#[async_trait]
+impl PlayerListener for ClanStatListener {
+    type Context = Player;
+
+    async fn on_match_end_v2(
+        &self,
+        server_context: &mut ServerContext,
+        current_match: &mut Match,
+        context: &mut Self::Context,
+        end_data: &mut MatchEndData
+    ) {
+        let clan_id = match &context.clan_id {
+            Some(clan_id) => clan_id.clone(),
+            None => return
+        };
+        if !current_match.is_tracking_stats() {
+            return;
+        };
+        let participant = match current_match.participants.get(&context.id) {
+            Some(participant) => participant,
+            None => return
+        };
+        let result = current_match.get_participant_match_result(participant, end_data);
+        let result_field = match result {
+            PlayerMatchResult::Win => "stats.wins",
+            PlayerMatchResult::Lose => "stats.losses",
+            PlayerMatchResult::Tie => "stats.ties",
+            PlayerMatchResult::Intermediate => return
+        };
+        let kills = participant.stats.kills;
+        let deaths = participant.stats.deaths;
+        let game_playtime = participant.stats.game_playtime;
+
+        let api_state = &server_context.api_state;
+        let opts = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        let clan = match api_state.database.clans.find_one_and_update(doc! { "_id": &clan_id }, doc! {
+            "$inc": {
+                "stats.kills": kills,
+                "stats.deaths": deaths,
+                "stats.matchesPlayed": 1,
+                "stats.gamePlaytime": game_playtime as i64,
+                result_field: 1
+            }
+        }, opts).await {
+            Ok(Some(clan)) => clan,
+            _ => return
+        };
+
+        let clan_lbs = &api_state.leaderboards.clans;
+        let id_name = clan.id_name();
+        clan_lbs.kills.increment(&id_name, Some(kills)).await;
+        clan_lbs.deaths.increment(&id_name, Some(deaths)).await;
+        clan_lbs.matches_played.increment(&id_name, Some(1)).await;
+        clan_lbs.game_playtime.increment(&id_name, Some(u32::try_from(game_playtime).unwrap_or(0))).await;
+        if let PlayerMatchResult::Win = result {
+            clan_lbs.wins.increment(&id_name, Some(1)).await;
+        };
+    }
+}
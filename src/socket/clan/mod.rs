@@ -0,0 +1 @@
+pub mod clan_stat_listener;
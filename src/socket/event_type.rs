@@ -30,11 +30,17 @@ pub enum EventType {
 
     // bi-directional
     PlayerChat,
+    DirectMessage,
 
     // plugin-bound
     PlayerXpGain,
     ForceMatchEnd,
     Message,
     DisconnectPlayer,
-    PlayerUpdate
+    PlayerUpdate,
+    DirectMessageResult,
+    ChatFilterVerdict,
+    ChatFilterAlert,
+    PunishmentIssue,
+    BanEvasionAlert
 }
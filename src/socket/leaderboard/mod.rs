@@ -1,7 +1,7 @@
 use std::sync::Arc;
 use mongodb::{bson::doc, Cursor};
 use num_traits::cast::FromPrimitive;
-use redis::{aio::Connection, ToRedisArgs};
+use redis::{aio::Connection, RedisResult, ToRedisArgs};
 use serde::{Serialize, Deserialize};
 use strum_macros::{Display, EnumIter, EnumString};
 use strum::IntoEnumIterator;
@@ -156,8 +156,13 @@ impl ScoreType {
     }
 }
 
+const PLAYER_LEADERBOARD_PREFIX : &str = "lb";
+const CLAN_LEADERBOARD_PREFIX : &str = "clan_lb";
+
 pub struct Leaderboard {
     pub score_type: ScoreType,
+    // "lb" for player boards, "clan_lb" for clan boards
+    pub key_prefix: &'static str,
     pub database: Arc<Database>,
     pub cache: Arc<RedisAdapter>
 }
@@ -267,8 +272,67 @@ impl Leaderboard {
         }).await.unwrap_or(None) // this unwrap occurs if a connection can't be obtained
     }
 
+    // removes every entry belonging to the player across all periods, including past names
+    pub async fn remove_player(&self, player_id: &String) {
+        let key_pattern = format!("{}:{}:*", self.key_prefix, self.score_type);
+        let member_pattern = format!("{}/*", player_id);
+        let _ = self.cache.submit(|mut conn| async move {
+            let mut keys : Vec<String> = Vec::new();
+            let mut cursor : u64 = 0;
+            loop {
+                let (next, mut batch) = match redis::cmd("SCAN").arg(cursor).arg("MATCH").arg(&key_pattern).arg("COUNT").arg(500u32)
+                    .query_async::<Connection, (u64, Vec<String>)>(&mut conn).await {
+                        Ok(res) => res,
+                        Err(_) => break
+                    };
+                keys.append(&mut batch);
+                cursor = next;
+                if cursor == 0 {
+                    break;
+                };
+            };
+            for key in keys.iter() {
+                let mut members : Vec<String> = Vec::new();
+                let mut cursor : u64 = 0;
+                loop {
+                    let (next, batch) = match redis::cmd("ZSCAN").arg(key).arg(cursor).arg("MATCH").arg(&member_pattern)
+                        .query_async::<Connection, (u64, Vec<String>)>(&mut conn).await {
+                            Ok(res) => res,
+                            Err(_) => break
+                        };
+                    // reply alternates member, score
+                    members.extend(batch.into_iter().step_by(2));
+                    cursor = next;
+                    if cursor == 0 {
+                        break;
+                    };
+                };
+                if !members.is_empty() {
+                    let _ = redis::cmd("ZREM").arg(key).arg(&members).query_async::<Connection, ()>(&mut conn).await;
+                };
+            };
+        }).await;
+    }
+
+    // members are stored as "id/name", so a rename moves the score onto the new member
+    pub async fn rename_member(&self, old_id: &String, new_id: &String) {
+        let _ = self.cache.submit(|mut conn| async move {
+            for period in LeaderboardPeriod::iter() {
+                let key = self.get_id(&period);
+                let score = match redis::cmd("ZSCORE").arg(&key).arg(old_id).query_async::<Connection, Option<f64>>(&mut conn).await {
+                    Ok(Some(score)) => score,
+                    _ => continue
+                };
+                let _ : RedisResult<()> = redis::pipe().atomic()
+                    .cmd("ZINCRBY").arg(&key).arg(score).arg(new_id).ignore()
+                    .cmd("ZREM").arg(&key).arg(old_id).ignore()
+                    .query_async(&mut *conn).await;
+            };
+        }).await;
+    }
+
     fn get_id(&self, period: &LeaderboardPeriod) -> String {
-        format!("lb:{}:{}", self.score_type, period.get_today_id())
+        format!("{}:{}:{}", self.key_prefix, self.score_type, period.get_today_id())
     }
 }
 
@@ -298,41 +362,83 @@ pub struct MarsLeaderboards {
     pub wool_pickups: Leaderboard,
     pub wool_defends: Leaderboard,
     pub control_point_captures: Leaderboard,
-    pub highest_killstreak: Leaderboard
+    pub highest_killstreak: Leaderboard,
+    pub clans: ClanLeaderboards
+}
+
+// clan members are keyed as "clanId/clanName", mirroring player entries
+pub struct ClanLeaderboards {
+    pub kills: Leaderboard,
+    pub deaths: Leaderboard,
+    pub wins: Leaderboard,
+    pub matches_played: Leaderboard,
+    pub game_playtime: Leaderboard
+}
+
+impl ClanLeaderboards {
+    pub fn new(redis: Arc<RedisAdapter>, database: Arc<Database>) -> Self {
+        ClanLeaderboards {
+            kills: Leaderboard { key_prefix: CLAN_LEADERBOARD_PREFIX, score_type: ScoreType::Kills, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            deaths: Leaderboard { key_prefix: CLAN_LEADERBOARD_PREFIX, score_type: ScoreType::Deaths, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wins: Leaderboard { key_prefix: CLAN_LEADERBOARD_PREFIX, score_type: ScoreType::Wins, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            matches_played: Leaderboard { key_prefix: CLAN_LEADERBOARD_PREFIX, score_type: ScoreType::MatchesPlayed, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            game_playtime: Leaderboard { key_prefix: CLAN_LEADERBOARD_PREFIX, score_type: ScoreType::GamePlaytime, cache: Arc::clone(&redis), database: Arc::clone(&database) }
+        }
+    }
+
+    pub fn all(&self) -> Vec<&Leaderboard> {
+        vec![&self.kills, &self.deaths, &self.wins, &self.matches_played, &self.game_playtime]
+    }
+
+    pub fn by_score_type(&self, score_type: &ScoreType) -> Option<&Leaderboard> {
+        self.all().into_iter().find(|leaderboard| &leaderboard.score_type == score_type)
+    }
 }
 
 impl MarsLeaderboards {
     pub fn new(redis: Arc<RedisAdapter>, database: Arc<Database>) -> Self {
         MarsLeaderboards {
-            kills: Leaderboard { score_type: ScoreType::Kills, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            deaths: Leaderboard { score_type: ScoreType::Deaths, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            first_bloods: Leaderboard { score_type: ScoreType::FirstBloods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wins: Leaderboard { score_type: ScoreType::Wins, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            losses: Leaderboard { score_type: ScoreType::Losses, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            ties: Leaderboard { score_type: ScoreType::Ties, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            xp: Leaderboard { score_type: ScoreType::Xp, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            messages_sent: Leaderboard { score_type: ScoreType::MessagesSent, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            matches_played: Leaderboard { score_type: ScoreType::MatchesPlayed, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            server_playtime: Leaderboard { score_type: ScoreType::ServerPlaytime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            game_playtime: Leaderboard { score_type: ScoreType::GamePlaytime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            core_leaks: Leaderboard { score_type: ScoreType::CoreLeaks, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            core_block_destroys: Leaderboard { score_type: ScoreType::CoreBlockDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            destroyable_destroys: Leaderboard { score_type: ScoreType::DestroyableDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            destroyable_block_destroys: Leaderboard { score_type: ScoreType::DestroyableBlockDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_captures: Leaderboard { score_type: ScoreType::FlagCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_drops: Leaderboard { score_type: ScoreType::FlagDrops, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_pickups: Leaderboard { score_type: ScoreType::FlagPickups, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_defends: Leaderboard { score_type: ScoreType::FlagDefends, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_hold_time: Leaderboard { score_type: ScoreType::FlagHoldTime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wool_captures: Leaderboard { score_type: ScoreType::WoolCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wool_drops: Leaderboard { score_type: ScoreType::WoolDrops, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wool_pickups: Leaderboard { score_type: ScoreType::WoolPickups, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wool_defends: Leaderboard { score_type: ScoreType::WoolDefends, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            control_point_captures: Leaderboard { score_type: ScoreType::ControlPointCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            highest_killstreak: Leaderboard { score_type: ScoreType::HighestKillstreak, cache: Arc::clone(&redis), database: Arc::clone(&database) }
+            kills: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::Kills, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            deaths: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::Deaths, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            first_bloods: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::FirstBloods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wins: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::Wins, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            losses: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::Losses, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            ties: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::Ties, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            xp: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::Xp, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            messages_sent: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::MessagesSent, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            matches_played: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::MatchesPlayed, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            server_playtime: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::ServerPlaytime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            game_playtime: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::GamePlaytime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            core_leaks: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::CoreLeaks, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            core_block_destroys: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::CoreBlockDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            destroyable_destroys: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::DestroyableDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            destroyable_block_destroys: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::DestroyableBlockDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_captures: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::FlagCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_drops: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::FlagDrops, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_pickups: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::FlagPickups, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_defends: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::FlagDefends, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_hold_time: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::FlagHoldTime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wool_captures: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::WoolCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wool_drops: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::WoolDrops, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wool_pickups: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::WoolPickups, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wool_defends: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::WoolDefends, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            control_point_captures: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::ControlPointCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            highest_killstreak: Leaderboard { key_prefix: PLAYER_LEADERBOARD_PREFIX, score_type: ScoreType::HighestKillstreak, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            clans: ClanLeaderboards::new(Arc::clone(&redis), Arc::clone(&database))
         }
     }
 
+    pub fn all(&self) -> Vec<&Leaderboard> {
+        vec![
+            &self.kills, &self.deaths, &self.first_bloods, &self.wins, &self.losses, &self.ties,
+            &self.xp, &self.messages_sent, &self.matches_played, &self.server_playtime, &self.game_playtime,
+            &self.core_leaks, &self.core_block_destroys, &self.destroyable_destroys, &self.destroyable_block_destroys,
+            &self.flag_captures, &self.flag_drops, &self.flag_pickups, &self.flag_defends, &self.flag_hold_time,
+            &self.wool_captures, &self.wool_drops, &self.wool_pickups, &self.wool_defends,
+            &self.control_point_captures, &self.highest_killstreak
+        ]
+    }
+
     pub fn from_score_type(&self, score_type: ScoreType) -> &Leaderboard {
         match score_type {
             ScoreType::Kills => &self.kills,
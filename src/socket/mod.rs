@@ -9,3 +9,5 @@ pub mod player;
 pub mod map;
 pub mod objective;
 pub mod update;
+pub mod clan;
+pub mod chat;
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::BanEvasionPolicy, database::models::{ip_identity::IpIdentity, player::SimplePlayer, punishment::Punishment}, socket::event_type::EventType, MarsAPIState};
+
+// broadcast to every server for online staff
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BanEvasionAlertData {
+    pub player: SimplePlayer,
+    pub server_id: String,
+    pub new: bool,
+    pub blocked: bool,
+    pub linked_bans: Vec<Punishment>
+}
+
+fn get_alert_cooldown_key(player_id: &str) -> String {
+    format!("ban_evasion_alert:{}", player_id)
+}
+
+// other accounts' bans are never sent to the joining player's server
+pub const BAN_EVASION_KICK_MESSAGE : &str = "Your connection is linked to a banned account";
+
+// looks for active bans on other accounts seen from the ip, alerts staff and
+// returns whether the join should be refused (only when the policy blocks)
+pub async fn check_ban_evasion(api_state: &MarsAPIState, server_id: &str, player: &SimplePlayer, ip: &String, new: bool) -> bool {
+    let options = &api_state.config.options;
+    if options.ban_evasion_policy == BanEvasionPolicy::Off {
+        return false;
+    };
+    let linked_ids : Vec<String> = match IpIdentity::get_ip_identity_by_ip(&api_state.database.ip_identities, ip).await {
+        Some(ip_identity) => ip_identity.players.into_iter().filter(|id| id != &player.id).collect(),
+        None => return false
+    };
+    let linked_bans = api_state.database.get_active_bans_for_players(&linked_ids).await;
+    if linked_bans.is_empty() {
+        return false;
+    };
+
+    let blocked = options.ban_evasion_policy == BanEvasionPolicy::Block;
+    let should_alert = if options.ban_evasion_alert_cooldown_minutes > 0 {
+        let recent = api_state.redis.increment_with_expiry(
+            &get_alert_cooldown_key(&player.id), (options.ban_evasion_alert_cooldown_minutes * 60_000) as usize
+        ).await.unwrap_or(0);
+        recent <= 1
+    } else { true };
+    if should_alert {
+        let alert = BanEvasionAlertData {
+            player: player.clone(),
+            server_id: server_id.to_owned(),
+            new,
+            blocked,
+            linked_bans: linked_bans.clone()
+        };
+        api_state.server_registry.broadcast(&EventType::BanEvasionAlert, alert, None).await;
+
+        let config = api_state.config.clone();
+        let server_id = server_id.to_owned();
+        let player = player.clone();
+        tokio::spawn(async move {
+            config.webhooks.send_ban_evasion_webhook(&server_id, &player, &linked_bans, blocked).await;
+        });
+    };
+
+    blocked
+}
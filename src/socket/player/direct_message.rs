@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{database::models::player::SimplePlayer, socket::event_type::EventType, util::time::get_u64_time_millis, MarsAPIState};
+
+use super::presence::get_presence;
+
+const REPLY_TARGET_EXPIRY_MS : usize = 3_600_000;
+const OFFLINE_QUEUE_LENGTH : usize = 50;
+
+// sent by the sender's server, `recipient` is a name or id and is ignored for replies
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectMessageData {
+    pub sender: SimplePlayer,
+    pub recipient: Option<String>,
+    #[serde(default)]
+    pub reply: bool,
+    pub message: String
+}
+
+// sent to the recipient's server
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectMessageDeliveryData {
+    pub sender: SimplePlayer,
+    pub recipient: SimplePlayer,
+    pub message: String,
+    pub sent_at: u64,
+    // true when the message was queued while the recipient was offline
+    pub offline: bool
+}
+
+// sent back to the sender's server
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectMessageResultData {
+    pub sender: SimplePlayer,
+    pub recipient: Option<SimplePlayer>,
+    pub message: String,
+    pub status: DirectMessageStatus
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DirectMessageStatus {
+    Delivered,
+    // queued until the recipient next logs in
+    Offline,
+    Ignored,
+    RateLimited,
+    UnknownPlayer,
+    NoReplyTarget
+}
+
+fn get_reply_target_key(player_id: &str) -> String {
+    format!("dm:reply:{}", player_id)
+}
+
+fn get_offline_queue_key(player_id: &str) -> String {
+    format!("dm:offline:{}", player_id)
+}
+
+fn get_rate_limit_key(player_id: &str) -> String {
+    format!("dm:rate:{}", player_id)
+}
+
+// everything kept about the player's conversations, used when their data is erased
+pub async fn clear_direct_message_state(api_state: &MarsAPIState, player_id: &str) {
+    api_state.redis.delete(&get_reply_target_key(player_id)).await;
+    api_state.redis.delete(&get_offline_queue_key(player_id)).await;
+    api_state.redis.delete(&get_rate_limit_key(player_id)).await;
+}
+
+pub async fn route_direct_message(api_state: &MarsAPIState, data: DirectMessageData) -> DirectMessageResultData {
+    let sender = data.sender;
+    let result = |recipient: Option<SimplePlayer>, status: DirectMessageStatus| DirectMessageResultData {
+        sender: sender.clone(), recipient, message: data.message.clone(), status
+    };
+
+    let options = &api_state.config.options;
+    let sent_recently = api_state.redis.increment_with_expiry(
+        &get_rate_limit_key(&sender.id),
+        (options.direct_message_rate_limit_window_seconds * 1000) as usize
+    ).await.unwrap_or(0);
+    if sent_recently > options.direct_message_rate_limit as u64 {
+        return result(None, DirectMessageStatus::RateLimited);
+    };
+
+    let recipient_key = if data.reply {
+        match api_state.redis.get_unchecked::<String>(&get_reply_target_key(&sender.id)).await {
+            Some(recipient_id) => recipient_id,
+            None => return result(None, DirectMessageStatus::NoReplyTarget)
+        }
+    } else {
+        match &data.recipient {
+            Some(recipient) => recipient.clone(),
+            None => return result(None, DirectMessageStatus::UnknownPlayer)
+        }
+    };
+    let recipient = match api_state.player_cache.get(&api_state.database, &recipient_key).await {
+        Some(recipient) => recipient,
+        None => return result(None, DirectMessageStatus::UnknownPlayer)
+    };
+    if recipient.ignored_player_ids.contains(&sender.id) {
+        return result(Some(recipient.to_simple()), DirectMessageStatus::Ignored);
+    };
+
+    // both sides can reply to each other
+    api_state.redis.set_with_expiry(&get_reply_target_key(&sender.id), &recipient.id, Some(REPLY_TARGET_EXPIRY_MS)).await;
+    api_state.redis.set_with_expiry(&get_reply_target_key(&recipient.id), &sender.id, Some(REPLY_TARGET_EXPIRY_MS)).await;
+
+    let mut delivery = DirectMessageDeliveryData {
+        sender: sender.clone(),
+        recipient: recipient.to_simple(),
+        message: data.message.clone(),
+        sent_at: get_u64_time_millis(),
+        offline: false
+    };
+    if let Some(presence) = get_presence(api_state, &recipient.id).await {
+        if api_state.server_registry.send(&presence.server_id, &EventType::DirectMessage, delivery.clone()).await {
+            return result(Some(recipient.to_simple()), DirectMessageStatus::Delivered);
+        };
+    };
+    delivery.offline = true;
+    api_state.redis.push_capped(
+        &get_offline_queue_key(&recipient.id),
+        &delivery,
+        OFFLINE_QUEUE_LENGTH,
+        (options.direct_message_offline_retention_days * 86_400_000) as usize
+    ).await;
+    result(Some(recipient.to_simple()), DirectMessageStatus::Offline)
+}
+
+pub async fn deliver_offline_messages(api_state: &MarsAPIState, player_id: &str, server_id: &str) {
+    let queued : Vec<DirectMessageDeliveryData> = api_state.redis.take_list(&get_offline_queue_key(player_id)).await;
+    for delivery in queued {
+        api_state.server_registry.send(server_id, &EventType::DirectMessage, delivery).await;
+    };
+}
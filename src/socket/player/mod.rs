@@ -5,3 +5,6 @@ pub mod player_stat_listener;
 pub mod player_gamemode_stat_listener;
 pub mod player_xp_listener;
 pub mod player_record_listener;
+pub mod presence;
+pub mod direct_message;
+pub mod ban_evasion;
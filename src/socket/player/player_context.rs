@@ -4,7 +4,9 @@
 
 
 
-use crate::{database::models::{player::{Player}, r#match::Match}, socket::{server::server_context::ServerContext, event_type::EventType}};
+use std::collections::HashMap;
+
+use crate::{database::models::{player::{Player}, r#match::Match}, socket::{server::server_context::ServerContext, event_type::EventType, update::player_update_listener::{PlayerUpdate, PlayerUpdateData, PlayerUpdateReason}}, MarsAPIState};
 
 use super::player_events::MessageData;
 
@@ -37,3 +39,24 @@ pub async fn send_message_to_player(server_context: &mut ServerContext, player:
     };
     server_context.call(&EventType::Message, message_data).await;
 }
+
+// delivers to whichever servers the players are currently on, skipping anyone offline
+pub async fn send_message_to_online_players(api_state: &MarsAPIState, player_ids: &[String], message: &str, sound: Option<String>) {
+    let sessions = api_state.database.get_active_sessions_for_players(player_ids).await;
+    let mut players_by_server : HashMap<String, Vec<String>> = HashMap::new();
+    for session in sessions {
+        players_by_server.entry(session.server_id).or_default().push(session.player.id);
+    };
+    for (server_id, player_ids) in players_by_server {
+        let message_data = MessageData { message: message.to_owned(), sound: sound.clone(), player_ids };
+        api_state.server_registry.send(&server_id, &EventType::Message, message_data).await;
+    };
+}
+
+// pushes the update to the server the player is on, if they are online
+pub async fn send_player_update_to_online_player(api_state: &MarsAPIState, player: Player, data: PlayerUpdateData, reason: PlayerUpdateReason) {
+    let session = api_state.database.get_active_sessions_for_players(std::slice::from_ref(&player.id)).await.into_iter().next();
+    if let Some(session) = session {
+        api_state.server_registry.send(&session.server_id, &EventType::PlayerUpdate, PlayerUpdate { updated: player, data, reason }).await;
+    };
+}
@@ -36,16 +36,30 @@ pub struct PlayerChatData {
     pub channel: ChatChannel,
     pub message: String,
     pub server_id: String,
+    // echoed in the filter verdict so the plugin can match it to the pending message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, strum_macros::EnumString, strum_macros::Display)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 pub enum ChatChannel {
     Staff,
     Global,
     Team
 }
 
+// PlayerChat as sent to the other servers
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayedChatData {
+    #[serde(flatten)]
+    pub chat: PlayerChatData,
+    pub permission: Option<String>,
+    pub relayed: bool
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct KillstreakData {
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{database::models::session::Session, MarsAPIState};
+
+// refreshed on every login, only guards against a missed logout
+const PRESENCE_EXPIRY_MS : usize = 86_400_000;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPresence {
+    pub server_id: String,
+    pub session_id: String,
+    pub since: u64
+}
+
+impl From<&Session> for PlayerPresence {
+    fn from(session: &Session) -> Self {
+        PlayerPresence { server_id: session.server_id.clone(), session_id: session.id.clone(), since: session.created_at }
+    }
+}
+
+fn get_presence_key(player_id: &str) -> String {
+    format!("presence:{}", player_id)
+}
+
+pub async fn set_presence(api_state: &MarsAPIState, player_id: &str, presence: &PlayerPresence) {
+    api_state.redis.set_with_expiry(&get_presence_key(player_id), presence, Some(PRESENCE_EXPIRY_MS)).await;
+}
+
+// a logout from an older session must not clear the presence of a newer one
+pub async fn clear_presence(api_state: &MarsAPIState, player_id: &str, session_id: &str) {
+    let key = get_presence_key(player_id);
+    let current : Option<PlayerPresence> = api_state.redis.get_unchecked(&key).await;
+    if current.map(|presence| presence.session_id == session_id).unwrap_or(true) {
+        api_state.redis.delete(&key).await;
+    };
+}
+
+// regardless of the session, for when the player's data is erased
+pub async fn remove_presence(api_state: &MarsAPIState, player_id: &str) {
+    api_state.redis.delete(&get_presence_key(player_id)).await;
+}
+
+// falls back to the session records when the key is missing, e.g. after a redis flush
+pub async fn get_presence(api_state: &MarsAPIState, player_id: &str) -> Option<PlayerPresence> {
+    if let Some(presence) = api_state.redis.get_unchecked::<PlayerPresence>(&get_presence_key(player_id)).await {
+        return Some(presence);
+    };
+    let session = api_state.database.get_active_sessions_for_players(&[player_id.to_owned()]).await.into_iter().next()?;
+    let presence = PlayerPresence::from(&session);
+    set_presence(api_state, player_id, &presence).await;
+    Some(presence)
+}
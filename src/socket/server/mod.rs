@@ -1,2 +1,3 @@
 pub mod server_context;
 pub mod server_events;
+pub mod server_registry;
@@ -37,14 +37,11 @@ impl ServerContext {
     }
 
     pub async fn call<T: Serialize>(&mut self, event_type: &EventType, data: T) {
-        let packet = Packet { event: event_type.clone(), data };
-        let body = serde_json::to_string(&packet).unwrap();
-        let binary = Message::Binary(deflate_string(body.as_bytes()).unwrap());
-        let _ = self.stream.send(binary).await;
+        let _ = self.stream.send(encode_packet(event_type, data)).await;
     }
 
     fn get_current_match_id_key(&self) -> String {
-        format!("server:{}:current_match_id", self.id)
+        get_current_match_id_key(&self.id)
     }
 
     fn get_last_alive_time_key(&self) -> String {
@@ -56,6 +53,16 @@ impl ServerContext {
     }
 }
 
+pub fn get_current_match_id_key(server_id: &str) -> String {
+    format!("server:{}:current_match_id", server_id)
+}
+
+pub fn encode_packet<T: Serialize>(event_type: &EventType, data: T) -> Message {
+    let packet = Packet { event: event_type.clone(), data };
+    let body = serde_json::to_string(&packet).unwrap();
+    Message::Binary(deflate_string(body.as_bytes()).unwrap())
+}
+
 #[derive(Serialize, Deserialize)]
 struct Packet<T> {
     #[serde(rename = "e")]
@@ -0,0 +1,64 @@
+use std::{collections::HashMap, sync::atomic::{AtomicU64, Ordering}};
+
+use serde::Serialize;
+use tokio::sync::{mpsc::{self, UnboundedReceiver, UnboundedSender}, RwLock};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::socket::event_type::EventType;
+
+use super::server_context::encode_packet;
+
+// lets code outside of a server's own socket task (http handlers, other servers' routers) push packets to it
+#[derive(Default)]
+pub struct ServerRegistry {
+    connections: RwLock<HashMap<String, (u64, UnboundedSender<Message>)>>,
+    next_connection_id: AtomicU64
+}
+
+impl ServerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn register(&self, server_id: &str) -> (u64, UnboundedReceiver<Message>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let connection_id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        self.connections.write().await.insert(server_id.to_owned(), (connection_id, tx));
+        (connection_id, rx)
+    }
+
+    // a reconnecting server may register again before its old socket closes, so only drop our own entry
+    pub async fn unregister(&self, server_id: &String, connection_id: u64) {
+        let mut connections = self.connections.write().await;
+        if connections.get(server_id).map(|(id, _)| *id == connection_id).unwrap_or(false) {
+            connections.remove(server_id);
+        };
+    }
+
+    pub async fn is_connected(&self, server_id: &str) -> bool {
+        self.connections.read().await.contains_key(server_id)
+    }
+
+    pub async fn get_server_ids(&self) -> Vec<String> {
+        self.connections.read().await.keys().cloned().collect()
+    }
+
+    pub async fn send<T: Serialize>(&self, server_id: &str, event_type: &EventType, data: T) -> bool {
+        let connections = self.connections.read().await;
+        match connections.get(server_id) {
+            Some((_, tx)) => tx.send(encode_packet(event_type, data)).is_ok(),
+            None => false
+        }
+    }
+
+    pub async fn broadcast<T: Serialize>(&self, event_type: &EventType, data: T, except_server_id: Option<&str>) {
+        let packet = encode_packet(event_type, data);
+        let connections = self.connections.read().await;
+        for (server_id, (_, tx)) in connections.iter() {
+            if except_server_id.map(|except| except == server_id).unwrap_or(false) {
+                continue;
+            };
+            let _ = tx.send(packet.clone());
+        };
+    }
+}
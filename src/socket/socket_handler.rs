@@ -3,7 +3,7 @@ use std::collections::{HashMap, HashSet};
 use std::io::{Read};
 use std::sync::Arc;
 
-use futures::StreamExt;
+use futures::{SinkExt, StreamExt};
 use log::info;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Mutex;
@@ -86,8 +86,20 @@ async fn accept_connection(
     };
     
     let mut router = SocketRouter::new(server);
+    let registry = socket_session.api_state.server_registry.clone();
+    let (connection_id, mut outgoing) = registry.register(&server_id).await;
 
-    while let Some(msg) = router.server.stream.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = router.server.stream.next() => match msg {
+                Some(msg) => msg,
+                None => break
+            },
+            Some(packet) = outgoing.recv() => {
+                let _ = router.server.stream.send(packet).await;
+                continue;
+            }
+        };
         let msg = unwrap_helper::continue_default!(msg.ok());
         let data = match msg {
             tokio_tungstenite::tungstenite::Message::Binary(data) => data,
@@ -123,6 +135,7 @@ async fn accept_connection(
         router.server.set_last_time_alive(get_u64_time_millis()).await;
         info!("[{}:{}] {}", server_id, event, socket_data_serialized);
     }
+    registry.unregister(&server_id, connection_id).await;
     info!("WebSocket connection closed from server {}", socket_session.server_id.clone());
     let _ = router.server.stream.close(Some(CloseFrame { code: CloseCode::Normal, reason: std::borrow::Cow::Borrowed("Connection closed")  })).await;
 
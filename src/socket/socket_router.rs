@@ -8,9 +8,11 @@ use rocket::serde::{json::{serde_json, Value}, DeserializeOwned};
 
 use uuid::Uuid;
 
-use crate::{database::models::{death::Death, achievement::Achievement, r#match::{FirstBlood, MatchState}, participant::{Participant, SimpleParticipant}, player::{AchievementData, Player}}, socket::r#match::match_phase_listener::MatchPhaseListener, util::{r#macro::unwrap_helper, time::get_u64_time_millis}};
+use mongodb::bson::DateTime as BsonDateTime;
 
-use super::{event_type::EventType, leaderboard::leaderboard_listener::LeaderboardListener, map::map_record_listener::MapRecordListener, r#match::match_events::{MatchEndData, MatchStartData}, objective::objective_events::{ControlPointCaptureData, CoreLeakData, DestroyableDamageData, DestroyableDestroyData, FlagDropData, FlagEventData, WoolDropData, WoolEventData}, participant::{participant_party_listener::ParticipantPartyListener, participant_stat_listener::ParticipantStatListener}, player::{player_events::{KillstreakData, PartyJoinData, PartyLeaveData, PlayerAchievementData, PlayerChatData, PlayerDeathData}, player_gamemode_stat_listener::PlayerGamemodeStatListener, player_listener::PlayerListener, player_record_listener::PlayerRecordListener, player_stat_listener::PlayerStatListener, player_xp_listener::PlayerXPListener}, server::{server_context::ServerContext, server_events::MatchLoadData}, update::player_update_listener::PlayerUpdateListener};
+use crate::{database::models::{chat_message::ChatMessage, death::Death, achievement::Achievement, r#match::{FirstBlood, MatchState}, participant::{Participant, SimpleParticipant}, player::{AchievementData, Player}}, socket::r#match::match_phase_listener::MatchPhaseListener, util::{r#macro::unwrap_helper, time::get_u64_time_millis}};
+
+use super::{chat::chat_filter::apply_chat_filter, clan::clan_stat_listener::ClanStatListener, event_type::EventType, leaderboard::leaderboard_listener::LeaderboardListener, map::map_record_listener::MapRecordListener, r#match::match_events::{MatchEndData, MatchStartData}, objective::objective_events::{ControlPointCaptureData, CoreLeakData, DestroyableDamageData, DestroyableDestroyData, FlagDropData, FlagEventData, WoolDropData, WoolEventData}, participant::{participant_party_listener::ParticipantPartyListener, participant_stat_listener::ParticipantStatListener}, player::{direct_message::{route_direct_message, DirectMessageData}, player_events::{ChatChannel, RelayedChatData, KillstreakData, PartyJoinData, PartyLeaveData, PlayerAchievementData, PlayerChatData, PlayerDeathData}, player_gamemode_stat_listener::PlayerGamemodeStatListener, player_listener::PlayerListener, player_record_listener::PlayerRecordListener, player_stat_listener::PlayerStatListener, player_xp_listener::PlayerXPListener}, server::{server_context::ServerContext, server_events::MatchLoadData}, update::player_update_listener::PlayerUpdateListener};
 use crate::database::Database;
 
 pub struct SocketRouter {
@@ -49,6 +51,7 @@ impl SocketRouter {
                 Box::new(PlayerXPListener {}),
                 Box::new(PlayerRecordListener {}),
                 Box::new(PlayerUpdateListener {}),
+                Box::new(ClanStatListener {}),
             ]
         }
     }
@@ -76,6 +79,7 @@ impl SocketRouter {
             EventType::WoolDefend =>                            self.on_wool_defend(Self::parse_data(data)).await,
             EventType::ControlPointCapture =>                   self.on_control_point_capture(Self::parse_data(data)).await,
             EventType::AchievementEarn =>                       self.on_achievement_complete(Self::parse_data(data)).await,
+            EventType::DirectMessage =>                         self.on_direct_message(Self::parse_data(data)).await,
             _ => {warn!("Event (srv {}) fell through router: {} - {}", self.server.id, event_type, data.to_string()); return}
         };
         match response {
@@ -237,6 +241,16 @@ impl SocketRouter {
     }
 
     async fn on_player_chat(&mut self, mut data: PlayerChatData) -> Result<(), SocketError> {
+        let message_id = self.log_chat(&data).await;
+        let verdict = apply_chat_filter(&self.server.api_state, &self.server.id, &data, &message_id).await;
+        let allowed = verdict.allowed;
+        if data.nonce.is_some() || verdict.action.is_some() {
+            self.server.call(&EventType::ChatFilterVerdict, verdict).await;
+        };
+        // relay before the match lookup, staff chat should work between matches too
+        if allowed {
+            self.relay_chat(&mut data).await;
+        };
         let mut current_match = unwrap_helper::return_default!(self.server.get_match().await, Err(SocketError::InvalidMatchState));
         let participant = match current_match.participants.get(&data.player.id) {
             Some(participant_ref) => Some(participant_ref.to_owned()),
@@ -267,6 +281,41 @@ impl SocketRouter {
         Ok(())
     }
 
+    async fn relay_chat(&self, data: &mut PlayerChatData) {
+        // team chat is scoped to a match and never leaves the server
+        if data.channel == ChatChannel::Team {
+            return;
+        };
+        let settings = self.server.api_state.config.data.chat_channels.iter().find(|settings| settings.channel == data.channel && settings.relay);
+        let settings = match settings {
+            Some(settings) => settings,
+            None => return
+        };
+        // trust the socket over the payload for the origin
+        data.server_id = self.server.id.clone();
+        let mut chat = data.clone();
+        chat.nonce = None;
+        let relayed = RelayedChatData { chat, permission: settings.permission.clone(), relayed: true };
+        self.server.api_state.server_registry.broadcast(&EventType::PlayerChat, relayed, Some(&self.server.id)).await;
+    }
+
+    async fn log_chat(&self, data: &PlayerChatData) -> String {
+        let retention_days = self.server.api_state.config.options.chat_log_retention_days;
+        let created_at = get_u64_time_millis();
+        let message = ChatMessage {
+            id: Uuid::new_v4().to_string(),
+            player: data.player.clone(),
+            server_id: self.server.id.clone(),
+            match_id: self.server.get_current_match_id().await,
+            channel: data.channel.clone(),
+            message: data.message.clone(),
+            created_at,
+            expires_at: if retention_days == 0 { None } else { Some(BsonDateTime::from_millis((created_at + retention_days * 86_400_000) as i64)) }
+        };
+        self.server.api_state.database.insert_one(&message).await;
+        message.id
+    }
+
     async fn on_killstreak(&mut self, data: KillstreakData) -> Result<(), SocketError> {
         let mut current_match = unwrap_helper::return_default!(self.server.get_match().await, Err(SocketError::InvalidMatchState));
         if current_match.get_state() != MatchState::InProgress {
@@ -687,6 +736,12 @@ impl SocketRouter {
         Ok(())
     }
 
+    async fn on_direct_message(&mut self, data: DirectMessageData) -> Result<(), SocketError> {
+        let result = route_direct_message(&self.server.api_state, data).await;
+        self.server.call(&EventType::DirectMessageResult, result).await;
+        Ok(())
+    }
+
     fn parse_data<T: DeserializeOwned>(data: Value) -> T {
         let debug_res = format!("Socket passed malformed data.. {data:?}");
         serde_json::from_value(data).expect(&debug_res)
@@ -1,4 +1,6 @@
-use crate::{database::models::{r#match::{DestroyableGoal, Match}, player::Player}, socket::{event_type::EventType, r#match::match_events::MatchEndData, player::{player_events::{PlayerChatData, PlayerDeathData}, player_listener::PlayerListener}, server::server_context::ServerContext}};
+use std::collections::HashMap;
+
+use crate::{database::models::{r#match::{DestroyableGoal, Match}, player::Player, preference::PreferenceValue, punishment::Punishment}, socket::{event_type::EventType, r#match::match_events::MatchEndData, player::{player_events::{PlayerChatData, PlayerDeathData}, player_listener::PlayerListener}, server::server_context::ServerContext}};
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
 
@@ -27,6 +29,10 @@ pub enum PlayerUpdateReason {
     WoolPickup,
     WoolDefend,
     ControlPointCapture,
+    Preferences,
+    Currency,
+    PunishmentEdit,
+    PunishmentExpire,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -55,6 +61,12 @@ pub enum PlayerUpdateData {
     MonumentDropUpdateData { held_time: u64 },
     #[serde(rename = "ControlPointCaptureUpdateData", rename_all = "camelCase")]
     ControlPointCaptureUpdateData { contributors: u32 },
+    #[serde(rename = "PreferencesUpdateData", rename_all = "camelCase")]
+    PreferencesUpdateData { preferences: HashMap<String, PreferenceValue> },
+    #[serde(rename = "CurrencyUpdateData", rename_all = "camelCase")]
+    CurrencyUpdateData { balance: i64, change: i64 },
+    #[serde(rename = "PunishmentUpdateData", rename_all = "camelCase")]
+    PunishmentUpdateData { punishment: Box<Punishment> },
     #[serde(rename = "NoArgs", rename_all = "camelCase")]
     NoArgs
 }
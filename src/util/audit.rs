@@ -0,0 +1,89 @@
+use mongodb::bson::{self, Document};
+use rocket::{request::{self, FromRequest}, Request};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{database::models::{audit_entry::{AuditAction, AuditActor, AuditEntry, AuditTarget}, player::SimplePlayer}, util::time::get_u64_time_millis, MarsAPIState};
+
+// set by the plugin when a staff member runs a command that calls the API
+const ACTOR_ID_HEADER : &str = "Mars-Actor-ID";
+const ACTOR_NAME_HEADER : &str = "Mars-Actor-Name";
+
+// request guard for mutating routes, never fails so requests without actor headers are still recorded
+pub struct AuditContext {
+    pub actor: AuditActor
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuditContext {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, ()> {
+        let headers = req.headers();
+        let player = match (headers.get_one(ACTOR_ID_HEADER), headers.get_one(ACTOR_NAME_HEADER)) {
+            (Some(id), Some(name)) => Some(SimplePlayer { id: id.to_owned(), name: name.to_owned() }),
+            _ => None
+        };
+        let server_id = headers.get_one("Mars-Server-ID").map(String::from);
+        request::Outcome::Success(AuditContext { actor: AuditActor { player, server_id } })
+    }
+}
+
+impl AuditContext {
+    // staff named in the request body take precedence over the headers
+    pub fn acting_as(&self, player: Option<&SimplePlayer>) -> Self {
+        let mut actor = self.actor.clone();
+        if let Some(player) = player {
+            actor.player = Some(player.clone());
+        };
+        AuditContext { actor }
+    }
+
+    pub async fn record_created<T: Serialize>(&self, state: &MarsAPIState, action: AuditAction, target: AuditTarget, after: &T) {
+        self.record(state, action, target, None, to_document(after)).await;
+    }
+
+    pub async fn record_changed<T: Serialize>(&self, state: &MarsAPIState, action: AuditAction, target: AuditTarget, before: &T, after: &T) {
+        self.record(state, action, target, to_document(before), to_document(after)).await;
+    }
+
+    pub async fn record_deleted<T: Serialize>(&self, state: &MarsAPIState, action: AuditAction, target: AuditTarget, before: &T) {
+        self.record(state, action, target, to_document(before), None).await;
+    }
+
+    async fn record(&self, state: &MarsAPIState, action: AuditAction, target: AuditTarget, before: Option<Document>, after: Option<Document>) {
+        let (before, after) = diff(before, after);
+        let entry = AuditEntry {
+            id: Uuid::new_v4().to_string(),
+            actor: self.actor.clone(),
+            action,
+            target,
+            before,
+            after,
+            created_at: get_u64_time_millis()
+        };
+        state.database.insert_one(&entry).await;
+    }
+}
+
+fn to_document<T: Serialize>(value: &T) -> Option<Document> {
+    bson::to_document(value).ok()
+}
+
+// drops the fields both sides agree on so an entry only carries the change
+fn diff(before: Option<Document>, after: Option<Document>) -> (Option<Document>, Option<Document>) {
+    match (before, after) {
+        (Some(mut before), Some(mut after)) => {
+            let unchanged : Vec<String> = before.iter()
+                .filter(|(key, value)| after.get(key.as_str()) == Some(*value))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in unchanged.iter() {
+                before.remove(key);
+                after.remove(key);
+            };
+            (Some(before), Some(after))
+        },
+        other => other
+    }
+}
@@ -166,6 +166,198 @@ impl ApiErrorResponder {
             "An achievement already exists with that name"
         )
     }
+
+    pub fn friendship_missing() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound,
+            &ApiExceptionType::FriendshipMissing, 
+            "The friendship or friend request does not exist"
+        )
+    }
+
+    pub fn friendship_conflict() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::FriendshipConflict, 
+            "The players are already friends or a request is pending"
+        )
+    }
+
+    pub fn friend_limit_reached() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::FriendLimitReached, 
+            "The friend limit has been reached"
+        )
+    }
+
+    pub fn clan_missing() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound,
+            &ApiExceptionType::ClanMissing, 
+            "The clan does not exist"
+        )
+    }
+
+    pub fn clan_conflict() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::ClanConflict, 
+            "A clan already exists with that name or tag"
+        )
+    }
+
+    pub fn clan_membership_conflict() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::ClanMembershipConflict, 
+            "The player is already in a clan"
+        )
+    }
+
+    pub fn clan_full() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::ClanFull, 
+            "The clan has reached its member limit"
+        )
+    }
+
+    pub fn clan_insufficient_role() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Forbidden,
+            &ApiExceptionType::ClanInsufficientRole, 
+            "The player's clan role does not allow this action"
+        )
+    }
+
+    pub fn clan_rename_cooldown() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::TooManyRequests,
+            &ApiExceptionType::ClanRenameCooldown, 
+            "The clan was renamed too recently"
+        )
+    }
+
+    pub fn cosmetic_missing() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound,
+            &ApiExceptionType::CosmeticMissing, 
+            "The cosmetic does not exist"
+        )
+    }
+
+    pub fn cosmetic_already_owned() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::CosmeticAlreadyOwned, 
+            "The player already owns this cosmetic"
+        )
+    }
+
+    pub fn cosmetic_not_owned() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Forbidden,
+            &ApiExceptionType::CosmeticNotOwned, 
+            "The player does not own this cosmetic"
+        )
+    }
+
+    pub fn cosmetic_not_permitted() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Forbidden,
+            &ApiExceptionType::CosmeticNotPermitted, 
+            "The player's ranks do not permit this cosmetic"
+        )
+    }
+
+    pub fn insufficient_funds() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::PaymentRequired,
+            &ApiExceptionType::InsufficientFunds, 
+            "The player's balance is too low"
+        )
+    }
+
+    pub fn transaction_missing() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound,
+            &ApiExceptionType::TransactionMissing, 
+            "The transaction does not exist"
+        )
+    }
+
+    pub fn transaction_conflict() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::TransactionConflict, 
+            "The transaction has already been refunded or cannot be refunded"
+        )
+    }
+
+    pub fn appeal_missing() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound,
+            &ApiExceptionType::AppealMissing, 
+            "The appeal does not exist"
+        )
+    }
+
+    pub fn appeal_conflict() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::AppealConflict, 
+            "The punishment already has an active appeal"
+        )
+    }
+
+    pub fn appeal_closed() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::AppealClosed, 
+            "The appeal cannot be moved to that status"
+        )
+    }
+
+    pub fn report_missing() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound,
+            &ApiExceptionType::ReportMissing, 
+            "The report does not exist"
+        )
+    }
+
+    pub fn report_cooldown() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::TooManyRequests,
+            &ApiExceptionType::ReportCooldown, 
+            "The reporter must wait before reporting again"
+        )
+    }
+
+    pub fn report_closed() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::ReportClosed, 
+            "The report has already been resolved or dismissed"
+        )
+    }
+
+    pub fn shared_ip_missing() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound,
+            &ApiExceptionType::SharedIpMissing, 
+            "The shared IP entry does not exist"
+        )
+    }
+
+    pub fn shared_ip_conflict() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict,
+            &ApiExceptionType::SharedIpConflict, 
+            "The IP or range is already marked as shared"
+        )
+    }
 }
 
 impl<'r> Responder<'r, 'static> for ApiErrorResponder {
@@ -235,5 +427,29 @@ enum ApiExceptionType {
     AchievementMising,
     PunishmentMissing,
     NoteMissing,
+    FriendshipMissing,
+    FriendshipConflict,
+    FriendLimitReached,
+    ClanMissing,
+    ClanConflict,
+    ClanMembershipConflict,
+    ClanFull,
+    ClanInsufficientRole,
+    ClanRenameCooldown,
+    CosmeticMissing,
+    CosmeticAlreadyOwned,
+    CosmeticNotOwned,
+    CosmeticNotPermitted,
+    InsufficientFunds,
+    TransactionMissing,
+    TransactionConflict,
+    AppealMissing,
+    AppealConflict,
+    AppealClosed,
+    ReportMissing,
+    ReportCooldown,
+    ReportClosed,
+    SharedIpMissing,
+    SharedIpConflict,
     Anonymous
 }
@@ -0,0 +1,147 @@
+use std::{collections::HashSet, net::IpAddr};
+
+use anyhow::anyhow;
+
+use super::string::{hmac_sha256_formatted, sha256_hash_formatted};
+
+// New values are the HMAC of the raw ip under the current key, stored as "v<version>:<hmac>".
+// Values stored under an older key (or as an unkeyed SHA-256 hash, origin 0) are brought forward
+// without the raw ip by chaining an HMAC through every newer key, stored as
+// "v<version><<origin>:<hmac>". Chained values still depend on the keys they passed through, so
+// while dual-read is on they are replaced by a direct hash the next time the player joins. Once
+// every stored value is direct under the current key (see is_current) older keys can be removed.
+#[derive(Clone, Default)]
+pub struct IpHasher {
+    enabled: bool,
+    // ascending by version, the last one is current
+    keys: Vec<IpHashKey>,
+    // accept values stored under older keys until they have been migrated
+    dual_read: bool
+}
+
+#[derive(Clone)]
+struct IpHashKey {
+    version: u32,
+    secret: Vec<u8>
+}
+
+impl IpHasher {
+    // keys are given as comma separated "version:secret" pairs, e.g. "1:abc,2:def"
+    pub fn new(enabled: bool, keys: Option<String>, dual_read: bool) -> anyhow::Result<Self> {
+        let mut parsed : Vec<IpHashKey> = Vec::new();
+        for entry in keys.unwrap_or_default().split(',').map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+            let (version, secret) = entry.split_once(':').ok_or(anyhow!("IP hash key '{}' must be in the form version:secret", entry))?;
+            let version = version.parse::<u32>().map_err(|_| anyhow!("IP hash key version '{}' is not a number", version))?;
+            if secret.is_empty() {
+                return Err(anyhow!("IP hash key {} has an empty secret", version));
+            };
+            parsed.push(IpHashKey { version, secret: secret.as_bytes().to_vec() });
+        }
+        if parsed.iter().any(|key| key.version == 0) {
+            return Err(anyhow!("IP hash key versions start at 1, 0 stands for unkeyed hashes"));
+        };
+        parsed.sort_by_key(|key| key.version);
+        if parsed.windows(2).any(|pair| pair[0].version == pair[1].version) {
+            return Err(anyhow!("IP hash key versions must be unique"));
+        };
+        if enabled && parsed.is_empty() {
+            warn!("IP hashing is enabled without any keys, falling back to unkeyed SHA-256");
+        };
+        Ok(IpHasher { enabled, keys: parsed, dual_read })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn current_version(&self) -> Option<u32> {
+        self.keys.last().map(|key| key.version)
+    }
+
+    // the value new records store the ip under
+    pub fn hash(&self, ip: &String) -> String {
+        if !self.enabled {
+            return ip.clone();
+        };
+        match self.keys.last() {
+            Some(key) => Self::direct(key, ip),
+            None => sha256_hash_formatted(ip)
+        }
+    }
+
+    // every value the ip may currently be stored under, current first
+    pub fn candidates(&self, ip: &String) -> Vec<String> {
+        if !self.enabled {
+            return vec![ip.clone()];
+        };
+        let mut candidates = vec![self.hash(ip)];
+        if !self.dual_read {
+            return candidates;
+        };
+        let mut origins = vec![(0, sha256_hash_formatted(ip))];
+        origins.extend(self.keys.iter().map(|key| (key.version, Self::direct(key, ip))));
+        for (origin, value) in origins.into_iter() {
+            let mut chained = value.clone();
+            candidates.push(value);
+            for key in self.keys.iter().filter(|key| key.version > origin) {
+                chained = Self::chain_step(key, origin, &chained);
+                candidates.push(chained.clone());
+            }
+        }
+        // stored before hashing was turned on
+        candidates.push(ip.clone());
+        let mut seen = HashSet::new();
+        candidates.retain(|candidate| seen.insert(candidate.clone()));
+        candidates
+    }
+
+    // brings a stored value forward to the current key, None when it is as current as it can get
+    // without the raw ip; raw ips are hashed directly, anything else is chained
+    pub fn rekey(&self, stored: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        };
+        if stored.parse::<IpAddr>().is_ok() {
+            return Some(self.hash(&stored.to_owned()));
+        };
+        let (version, origin) = match Self::parse_version(stored) {
+            Some((version, origin)) => (version, origin.unwrap_or(version)),
+            None if Self::is_unkeyed_hash(stored) => (0, 0),
+            None => return None
+        };
+        let newer : Vec<&IpHashKey> = self.keys.iter().filter(|key| key.version > version).collect();
+        if newer.is_empty() {
+            return None;
+        };
+        Some(newer.into_iter().fold(stored.to_owned(), |value, key| Self::chain_step(key, origin, &value)))
+    }
+
+    // hashed directly under the current key, chained values are not as they still need older keys
+    pub fn is_current(&self, stored: &str) -> bool {
+        match self.current_version() {
+            Some(current) => Self::parse_version(stored) == Some((current, None)),
+            None => !self.enabled || Self::is_unkeyed_hash(stored)
+        }
+    }
+
+    fn direct(key: &IpHashKey, ip: &str) -> String {
+        format!("v{}:{}", key.version, hmac_sha256_formatted(&key.secret, ip))
+    }
+
+    fn chain_step(key: &IpHashKey, origin: u32, value: &str) -> String {
+        format!("v{}<{}:{}", key.version, origin, hmac_sha256_formatted(&key.secret, value))
+    }
+
+    // the version and, for chained values, the origin they were first hashed under
+    pub fn parse_version(stored: &str) -> Option<(u32, Option<u32>)> {
+        let (label, _) = stored.strip_prefix('v')?.split_once(':')?;
+        match label.split_once('<') {
+            Some((version, origin)) => Some((version.parse().ok()?, Some(origin.parse().ok()?))),
+            None => Some((label.parse().ok()?, None))
+        }
+    }
+
+    fn is_unkeyed_hash(stored: &str) -> bool {
+        stored.len() == 64 && stored.chars().all(|c| c.is_ascii_hexdigit())
+    }
+}
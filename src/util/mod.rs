@@ -7,4 +7,8 @@ pub mod time;
 pub mod r#macro;
 pub mod responder;
 pub mod webhook;
-pub mod stream;
\ No newline at end of file
+pub mod stream;
+pub mod audit;
+pub mod ip_hash;
+pub mod pagination;
+
@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+use mongodb::bson::{doc, Bson, Document};
+
+// a position in a listing sorted newest first by a timestamp and then by id, passed to
+// clients as "<timestamp>_<id>" so rows sharing a timestamp are never skipped between pages;
+// a bare timestamp from older clients is still accepted
+pub struct PageCursor {
+    pub at: u64,
+    pub id: Option<String>
+}
+
+impl PageCursor {
+    pub fn new(at: u64, id: &str) -> Self {
+        PageCursor { at, id: Some(id.to_owned()) }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.split_once('_') {
+            Some((at, id)) if !id.is_empty() => Some(PageCursor { at: at.parse().ok()?, id: Some(id.to_owned()) }),
+            Some(_) => None,
+            None => Some(PageCursor { at: text.parse().ok()?, id: None })
+        }
+    }
+
+    // narrows the filter to the rows after the cursor, alongside any conditions already on the field
+    pub fn apply(&self, filter: &mut Document, field: &str) {
+        let at = self.at as i64;
+        let condition = match &self.id {
+            Some(id) => doc! { "$or": [{ field: { "$lt": at } }, { field: at, "_id": { "$lt": id } }] },
+            None => doc! { field: { "$lt": at } }
+        };
+        let mut conditions = match filter.remove("$and") {
+            Some(Bson::Array(conditions)) => conditions,
+            _ => Vec::new()
+        };
+        conditions.push(Bson::Document(condition));
+        filter.insert("$and", conditions);
+    }
+
+    // the sort every listing paged with a cursor must use
+    pub fn sort(field: &str) -> Document {
+        doc! { field: -1, "_id": -1 }
+    }
+
+    // the cursor for the next page, none once a page comes back short
+    pub fn next<T>(rows: &[T], limit: i64, position: impl Fn(&T) -> (u64, &str)) -> Option<String> {
+        if (rows.len() as i64) < limit {
+            return None;
+        };
+        rows.last().map(|row| {
+            let (at, id) = position(row);
+            PageCursor::new(at, id).to_string()
+        })
+    }
+}
+
+impl Display for PageCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.id {
+            Some(id) => write!(f, "{}_{}", self.at, id),
+            None => write!(f, "{}", self.at)
+        }
+    }
+}
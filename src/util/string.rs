@@ -1,11 +1,33 @@
 use std::io::Write;
 
 use flate2::{write::ZlibEncoder, Compression};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
 
 pub fn to_utf8_byte_array(text: &String) -> &[u8] {
     text.as_bytes()
 }
 
+pub fn sha256_hash_formatted(digest: &String) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(to_utf8_byte_array(digest));
+    to_hex(&hasher.finalize())
+}
+
+pub fn hmac_sha256_formatted(key: &[u8], digest: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(digest.as_bytes());
+    to_hex(&mac.finalize().into_bytes())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::from(""), |mut hex_str, elem| {
+        let formatted = format!("{:02x}", elem);
+        hex_str.push_str(&formatted);
+        hex_str
+    })
+}
+
 pub fn deflate_string(data: &[u8]) -> anyhow::Result<Vec<u8>> {
     // https://github.com/madler/zlib/blob/master/zlib.h#L239
     let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(6));
@@ -15,4 +37,50 @@ pub fn deflate_string(data: &[u8]) -> anyhow::Result<Vec<u8>> {
 
 pub fn enumify(target: &str) -> String {
     target.trim().to_uppercase().replace(" ", "_")
-}
\ No newline at end of file
+}
+pub fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let b_chars : Vec<char> = b.chars().collect();
+    let mut previous : Vec<usize> = (0..=b_chars.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b_chars.len() + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b_chars.len()]
+}
+
+// bigrams of the lowercase name padded with ^ and $, what fuzzy name search is indexed on
+pub fn name_grams(name_lower: &str) -> Vec<String> {
+    let mut grams = query_name_grams(name_lower);
+    if let Some(last) = name_lower.chars().last() {
+        grams.push(format!("{}$", last));
+    };
+    grams.sort();
+    grams.dedup();
+    grams
+}
+
+// without the end padding, so names that merely start with the query still share every gram
+pub fn query_name_grams(query: &str) -> Vec<String> {
+    let chars : Vec<char> = std::iter::once('^').chain(query.chars()).collect();
+    chars.windows(2).map(|pair| pair.iter().collect()).collect()
+}
+
+pub fn is_valid_player_name_query(text: &str) -> bool {
+    !text.is_empty() && text.len() <= 16 && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// makes user input safe to embed in a mongo $regex
+pub fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        };
+        escaped.push(c);
+    }
+    escaped
+}
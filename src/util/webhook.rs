@@ -3,26 +3,39 @@
 use std::error::Error;
 
 use anyhow::anyhow;
-use crate::database::models::{player::SimplePlayer, punishment::{Punishment, StaffNote}};
+use crate::database::models::{appeal::{Appeal, AppealStatus}, chat_message::ChatMessage, player::SimplePlayer, punishment::{Punishment, PunishmentEdit, StaffNote}};
 use serde::Serialize;
 
 pub struct WebhookUtils {
     pub reports_webhook_client: Option<WebhookClient>,
     pub punishments_webhook_client: Option<WebhookClient>,
-    pub notes_webhook_client: Option<WebhookClient>
+    pub notes_webhook_client: Option<WebhookClient>,
+    pub appeals_webhook_client: Option<WebhookClient>,
+    pub evasion_webhook_client: Option<WebhookClient>
 }
 
 impl WebhookUtils {
     const COLOR_NEW_REPORT : u32 = 0xFFEE00;
     const COLOR_NEW_PUNISHMENT : u32 = 0x0077FF;
     const COLOR_PUNISHMENT_REVERTED : u32 = 0x00FF4C;
+    const COLOR_PUNISHMENT_EDITED : u32 = 0x00C8FF;
+    const COLOR_PUNISHMENT_EXPIRED : u32 = 0xAAAAAA;
     const COLOR_NEW_NOTE : u32 = 0xFF77FF;
     const COLOR_DEL_NOTE : u32 = 0xFF4F55;
+    const COLOR_APPEAL_OPEN : u32 = 0xFFAA00;
+    const COLOR_APPEAL_REVIEW : u32 = 0x9B59B6;
+    const COLOR_APPEAL_ACCEPTED : u32 = 0x00FF4C;
+    const COLOR_APPEAL_DENIED : u32 = 0xFF4F55;
+    const COLOR_BAN_EVASION : u32 = 0xFF0000;
+    // Discord allows 25 fields per embed
+    const MAX_LINKED_BAN_FIELDS : usize = 20;
 
     pub fn new(
         reports_webhook_url: &Option<String>, 
         punishments_webhook_url: &Option<String>,
-        notes_webhook_url: &Option<String>
+        notes_webhook_url: &Option<String>,
+        appeals_webhook_url: &Option<String>,
+        evasion_webhook_url: &Option<String>
     ) -> Self {
         Self {
             reports_webhook_client: reports_webhook_url.as_ref().map(|url| {
@@ -33,6 +46,12 @@ impl WebhookUtils {
             }),
             notes_webhook_client: notes_webhook_url.as_ref().map(|url| {
                 WebhookClient { url: url.to_owned(), client: reqwest::Client::new() }
+            }),
+            appeals_webhook_client: appeals_webhook_url.as_ref().map(|url| {
+                WebhookClient { url: url.to_owned(), client: reqwest::Client::new() }
+            }),
+            evasion_webhook_client: evasion_webhook_url.as_ref().map(|url| {
+                WebhookClient { url: url.to_owned(), client: reqwest::Client::new() }
             })
         }
     }
@@ -43,7 +62,8 @@ impl WebhookUtils {
         reporter: &SimplePlayer, 
         target: &SimplePlayer, 
         reason: &String, 
-        online_staff: &Vec<SimplePlayer>
+        online_staff: &Vec<SimplePlayer>,
+        messages: &[ChatMessage]
     ) {
         if let Some(reports_client) = &self.reports_webhook_client {
             let mut embed = DiscordEmbed::default();
@@ -80,6 +100,19 @@ impl WebhookUtils {
                         inline: false 
                     }
                 );
+            if !messages.is_empty() {
+                embed.add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Reported messages"), 
+                        value: truncate_field_value(messages
+                            .iter()
+                            .map(|m| format!("**{}**: {}", m.player.name, escape_markdown(&m.message, false)))
+                            .collect::<Vec<String>>()
+                            .join("\n")), 
+                        inline: false 
+                    }
+                );
+            };
             reports_client.send(
                 &WebhookMessage::default().add_embed(embed)
             ).await;
@@ -204,6 +237,120 @@ impl WebhookUtils {
         };
     }
 
+    pub async fn send_punishment_expiry_webhook(
+        &self, 
+        punishment: &Punishment
+    ) {
+        if let Some(punishments_client) = &self.punishments_webhook_client {
+            let mut embed = DiscordEmbed::default();
+            embed
+                .color(Self::COLOR_PUNISHMENT_EXPIRED)
+                .title(String::from("Punishment expired"))
+                .footer(DiscordEmbedFooter { 
+                    text: format!("Pun ID: {}", punishment.id), 
+                    icon_url: None 
+                })
+                .thumbnail(punishment.target.get_mini_icon_url())
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Target"), 
+                        value: punishment.target.name.to_owned(),
+                        inline: true 
+                    }
+                )
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Punishment"), 
+                        value: format!("{} - {} ({})", 
+                           punishment.action.kind, 
+                           escape_markdown(&punishment.reason.name, false), 
+                           punishment.offence
+                        ),
+                        inline: true 
+                    }
+                )
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Length"), 
+                        value: format_length(punishment.action.length),
+                        inline: true 
+                    }
+                );
+            let _ = punishments_client.send(
+                WebhookMessage::default().add_embed(embed)
+            ).await;
+        }
+    }
+
+    pub async fn send_punishment_edit_webhook(
+        &self, 
+        punishment: &Punishment,
+        edit: &PunishmentEdit
+    ) {
+        if let Some(punishments_client) = &self.punishments_webhook_client {
+            let previous = &edit.previous;
+            let mut changes = Vec::new();
+            if previous.reason.name != punishment.reason.name || previous.offence != punishment.offence {
+                changes.push(format!("Reason: {} ({}) → {} ({})", 
+                    escape_markdown(&previous.reason.name, false), previous.offence,
+                    escape_markdown(&punishment.reason.name, false), punishment.offence
+                ));
+            }
+            if previous.length != punishment.action.length {
+                changes.push(format!("Length: {} → {}", format_length(previous.length), format_length(punishment.action.length)));
+            }
+            if previous.note != punishment.note {
+                changes.push(format!("Note: {} → {}", 
+                    previous.note.as_ref().map(|note| escape_markdown(note, false)).unwrap_or(String::from("none")),
+                    punishment.note.as_ref().map(|note| escape_markdown(note, false)).unwrap_or(String::from("none"))
+                ));
+            }
+            if previous.silent != punishment.silent {
+                changes.push(format!("Silent: {} → {}", previous.silent, punishment.silent));
+            }
+            let mut embed = DiscordEmbed::default();
+            embed
+                .color(Self::COLOR_PUNISHMENT_EDITED)
+                .title(String::from("Punishment edited"))
+                .footer(DiscordEmbedFooter { 
+                    text: format!("Pun ID: {}", punishment.id), 
+                    icon_url: None 
+                })
+                .thumbnail(punishment.target.get_mini_icon_url())
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Target"), 
+                        value: punishment.target.name.to_owned(),
+                        inline: true 
+                    }
+                )
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Editor"), 
+                        value: escape_markdown(&edit.editor.name, false),
+                        inline: true 
+                    }
+                )
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Changes"), 
+                        value: if changes.is_empty() { String::from("None") } else { truncate_field_value(changes.join("\n")) },
+                        inline: false 
+                    }
+                )
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Justification"), 
+                        value: truncate_field_value(escape_markdown(&edit.justification, false)),
+                        inline: false 
+                    }
+                );
+            let _ = punishments_client.send(
+                WebhookMessage::default().add_embed(embed)
+            ).await;
+        }
+    }
+
     pub async fn send_new_note_webhook(
         &self, 
         player: &SimplePlayer,
@@ -288,6 +435,142 @@ impl WebhookUtils {
         }
     }
 
+    pub async fn send_appeal_webhook(
+        &self, 
+        appeal: &Appeal,
+        punishment: &Punishment
+    ) {
+        if let Some(appeals_client) = &self.appeals_webhook_client {
+            let (color, title) = match appeal.status {
+                AppealStatus::Open => (Self::COLOR_APPEAL_OPEN, "New appeal"),
+                AppealStatus::UnderReview => (Self::COLOR_APPEAL_REVIEW, "Appeal under review"),
+                AppealStatus::Accepted => (Self::COLOR_APPEAL_ACCEPTED, "Appeal accepted"),
+                AppealStatus::Denied => (Self::COLOR_APPEAL_DENIED, "Appeal denied")
+            };
+            let mut embed = DiscordEmbed::default();
+            embed
+                .color(color)
+                .title(String::from(title))
+                .footer(DiscordEmbedFooter { 
+                    text: format!("Appeal ID: {} | Pun ID: {}", appeal.id, punishment.id), 
+                    icon_url: None 
+                })
+                .thumbnail(appeal.player.get_mini_icon_url())
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Player"), 
+                        value: escape_markdown(&appeal.player.name, false),
+                        inline: true 
+                    }
+                )
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Punishment"), 
+                        value: format!("{} - {} ({})", 
+                           punishment.action.kind, 
+                           escape_markdown(&punishment.reason.name, false), 
+                           punishment.offence
+                        ),
+                        inline: true 
+                    }
+                )
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Statement"), 
+                        value: truncate_field_value(escape_markdown(&appeal.statement, false)),
+                        inline: false 
+                    }
+                );
+            if let Some(reviewer) = &appeal.reviewer {
+                embed.add_field(DiscordEmbedField { 
+                    name: String::from("Staff"), 
+                    value: escape_markdown(&reviewer.name, false), 
+                    inline: true
+                });
+            }
+            let _ = appeals_client.send(
+                WebhookMessage::default().add_embed(embed)
+            ).await;
+        }
+    }
+
+
+    pub async fn send_ban_evasion_webhook(
+        &self, 
+        server_id: &String,
+        player: &SimplePlayer,
+        linked_bans: &[Punishment],
+        blocked: bool
+    ) {
+        if let Some(evasion_client) = &self.evasion_webhook_client {
+            let mut embed = DiscordEmbed::default();
+            embed
+                .color(Self::COLOR_BAN_EVASION)
+                .title(String::from(if blocked { "Ban evasion blocked" } else { "Possible ban evasion" }))
+                .footer(DiscordEmbedFooter { 
+                    text: format!("Server: {}", server_id), 
+                    icon_url: None 
+                })
+                .thumbnail(player.get_mini_icon_url())
+                .add_field(
+                    DiscordEmbedField { 
+                        name: String::from("Player"), 
+                        value: escape_markdown(&player.name, false),
+                        inline: true 
+                    }
+                );
+            for ban in linked_bans.iter().take(Self::MAX_LINKED_BAN_FIELDS) {
+                embed.add_field(DiscordEmbedField { 
+                    name: format!("Linked to {}", escape_markdown(&ban.target.name, false)), 
+                    value: format!("{} - {} ({})\nPun ID: {}", 
+                        ban.action.kind, 
+                        escape_markdown(&ban.reason.name, false), 
+                        format_length(ban.action.length),
+                        ban.id
+                    ), 
+                    inline: false
+                });
+            }
+            if linked_bans.len() > Self::MAX_LINKED_BAN_FIELDS {
+                embed.add_field(DiscordEmbedField { 
+                    name: String::from("More linked bans"), 
+                    value: format!("{} more not shown", linked_bans.len() - Self::MAX_LINKED_BAN_FIELDS), 
+                    inline: false
+                });
+            };
+            let _ = evasion_client.send(
+                WebhookMessage::default().add_embed(embed)
+            ).await;
+        }
+    }
+}
+
+fn format_length(length: i64) -> String {
+    if length == -1 {
+        return String::from("Permanent");
+    }
+    let minutes = length / 60_000;
+    match (minutes / 1440, (minutes % 1440) / 60, minutes % 60) {
+        (0, 0, minutes) => format!("{}m", minutes),
+        (0, hours, minutes) => format!("{}h {}m", hours, minutes),
+        (days, hours, _) => format!("{}d {}h", days, hours)
+    }
+}
+
+// Discord rejects embeds with longer field values
+const MAX_FIELD_VALUE_LENGTH : usize = 1024;
+
+fn truncate_field_value(value: String) -> String {
+    if value.chars().count() <= MAX_FIELD_VALUE_LENGTH {
+        return value;
+    }
+    let mut truncated : String = value.chars().take(MAX_FIELD_VALUE_LENGTH - 3).collect();
+    // do not leave half an escape behind
+    if truncated.ends_with('\\') {
+        truncated.pop();
+    }
+    truncated.push_str("...");
+    truncated
 }
 
 fn escape_markdown(s: &String, html_mode: bool) -> String {